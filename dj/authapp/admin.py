@@ -1,3 +1,40 @@
 from django.contrib import admin
 
 # Register your models here.
+from authapp.models import AuditEvent, Role, UserRole, UserScope
+
+
+@admin.register(Role)
+class RoleAdmin(admin.ModelAdmin):
+    list_display = ("name", "scopes", "description")
+    search_fields = ("name",)
+
+
+@admin.register(UserRole)
+class UserRoleAdmin(admin.ModelAdmin):
+    list_display = ("user", "role", "created_on")
+    list_filter = ("role",)
+    raw_id_fields = ("user",)
+
+
+@admin.register(UserScope)
+class UserScopeAdmin(admin.ModelAdmin):
+    list_display = ("user", "scope", "created_on")
+    raw_id_fields = ("user",)
+
+
+@admin.register(AuditEvent)
+class AuditEventAdmin(admin.ModelAdmin):
+    list_display = ("created_on", "event", "outcome", "user_id", "email", "ip")
+    list_filter = ("event", "outcome")
+    search_fields = ("email", "ip", "request_id")
+
+    # Note: the events are append-only, the table rejects the changes as well
+    def has_add_permission(self, request):
+        return False
+
+    def has_change_permission(self, request, obj=None):
+        return False
+
+    def has_delete_permission(self, request, obj=None):
+        return False
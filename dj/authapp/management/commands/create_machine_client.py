@@ -0,0 +1,26 @@
+import hashlib
+import secrets
+
+from django.core.management.base import BaseCommand
+
+from authapp.models import MachineClient
+
+
+class Command(BaseCommand):
+    help = "Registers a machine client for the client credentials grant, the secret is printed only once"
+
+    def add_arguments(self, parser):
+        parser.add_argument("name")
+        parser.add_argument("--scopes", default="", help="space separated allowed scopes")
+
+    def handle(self, *args, **options):
+        client_id = secrets.token_urlsafe(24)
+        secret = secrets.token_urlsafe(32)
+        MachineClient.objects.create(
+            client_id=client_id,
+            client_secret_hash=hashlib.sha256(secret.encode()).hexdigest(),
+            name=options["name"],
+            allowed_scopes=options["scopes"],
+        )
+        self.stdout.write(f"client_id: {client_id}")
+        self.stdout.write(f"client_secret: {secret}")
@@ -0,0 +1,42 @@
+import hashlib
+import secrets
+
+from django.core.management.base import BaseCommand
+
+from authapp.models import OAuthClient
+
+
+class Command(BaseCommand):
+    help = "Registers an OpenID Connect client, the secret is printed only once"
+
+    def add_arguments(self, parser):
+        parser.add_argument("name")
+        parser.add_argument("--redirect-uri", action="append", required=True)
+        parser.add_argument("--scopes", default="openid email profile")
+        parser.add_argument(
+            "--public",
+            action="store_true",
+            help="client without secret (SPA, mobile, cli), must use PKCE",
+        )
+        parser.add_argument(
+            "--can-introspect",
+            action="store_true",
+            help="resource server introspecting the tokens of the other clients and the sessions",
+        )
+
+    def handle(self, *args, **options):
+        client_id = secrets.token_urlsafe(24)
+        secret = None if options["public"] else secrets.token_urlsafe(32)
+        OAuthClient.objects.create(
+            client_id=client_id,
+            client_secret_hash=hashlib.sha256(secret.encode()).hexdigest()
+            if secret
+            else None,
+            name=options["name"],
+            redirect_uris=options["redirect_uri"],
+            allowed_scopes=options["scopes"],
+            can_introspect=options["can_introspect"],
+        )
+        self.stdout.write(f"client_id: {client_id}")
+        if secret:
+            self.stdout.write(f"client_secret: {secret}")
@@ -0,0 +1,48 @@
+# Generated by Django 4.2.1 on 2026-10-19 10:12
+
+from django.db import migrations, models
+import django.db.models.deletion
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0002_auto_20230826_1142"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="UserProviderToken",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("provider", models.CharField(max_length=50)),
+                ("provider_user_id", models.CharField(max_length=255)),
+                ("access_token", models.BinaryField()),
+                (
+                    "user",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.PROTECT,
+                        to="authapp.customuser",
+                    ),
+                ),
+            ],
+            options={
+                "db_table": "authapp_user_provider_token",
+            },
+        ),
+        migrations.AddConstraint(
+            model_name="userprovidertoken",
+            constraint=models.UniqueConstraint(
+                fields=("user", "provider"), name="unique_user_provider_token"
+            ),
+        ),
+    ]
@@ -0,0 +1,27 @@
+# Generated by Django 4.2.1 on 2026-10-19 11:03
+
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0003_userprovidertoken"),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name="userprovidertoken",
+            name="refresh_token",
+            field=models.BinaryField(null=True),
+        ),
+        migrations.AddField(
+            model_name="userprovidertoken",
+            name="expires_at",
+            field=models.DateTimeField(null=True),
+        ),
+        migrations.AddField(
+            model_name="userprovidertoken",
+            name="refresh_token_expires_at",
+            field=models.DateTimeField(null=True),
+        ),
+    ]
@@ -0,0 +1,82 @@
+# Generated by Django 4.2.1 on 2026-10-19 12:20
+
+from django.db import migrations, models
+import django.db.models.deletion
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0004_userprovidertoken_refresh_token"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="OAuthClient",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("client_id", models.CharField(max_length=64, unique=True)),
+                ("client_secret_hash", models.CharField(max_length=64, null=True)),
+                ("name", models.CharField(max_length=127)),
+                ("redirect_uris", models.JSONField(default=list)),
+                (
+                    "allowed_scopes",
+                    models.CharField(default="openid email profile", max_length=255),
+                ),
+                ("active", models.BooleanField(default=True)),
+            ],
+            options={
+                "db_table": "authapp_oauth_client",
+            },
+        ),
+        migrations.CreateModel(
+            name="OAuthAuthorizationCode",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("code_hash", models.CharField(max_length=64, unique=True)),
+                ("redirect_uri", models.TextField()),
+                ("scope", models.CharField(max_length=255)),
+                ("nonce", models.CharField(max_length=255, null=True)),
+                ("code_challenge", models.CharField(max_length=128, null=True)),
+                ("code_challenge_method", models.CharField(max_length=10, null=True)),
+                ("expires_at", models.DateTimeField()),
+                ("used", models.BooleanField(default=False)),
+                (
+                    "client",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.PROTECT,
+                        to="authapp.oauthclient",
+                    ),
+                ),
+                (
+                    "user",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.PROTECT,
+                        to="authapp.customuser",
+                    ),
+                ),
+            ],
+            options={
+                "db_table": "authapp_oauth_code",
+            },
+        ),
+    ]
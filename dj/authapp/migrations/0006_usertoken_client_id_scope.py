@@ -0,0 +1,27 @@
+# Generated by Django 4.2.1 on 2026-10-19 14:10
+
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0005_oauthclient_oauthauthorizationcode"),
+    ]
+
+    operations = [
+        migrations.AlterField(
+            model_name="usertoken",
+            name="token",
+            field=models.TextField(db_index=True),
+        ),
+        migrations.AddField(
+            model_name="usertoken",
+            name="client_id",
+            field=models.CharField(max_length=64, null=True),
+        ),
+        migrations.AddField(
+            model_name="usertoken",
+            name="scope",
+            field=models.CharField(max_length=255, null=True),
+        ),
+    ]
@@ -0,0 +1,54 @@
+# Generated by Django 4.2.1 on 2026-10-19 15:02
+
+from django.db import migrations, models
+import django.db.models.deletion
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0006_usertoken_client_id_scope"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="OAuthDeviceCode",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("device_code_hash", models.CharField(max_length=64, unique=True)),
+                ("user_code", models.CharField(max_length=16, unique=True)),
+                ("scope", models.CharField(max_length=255)),
+                ("status", models.CharField(default="pending", max_length=16)),
+                ("poll_interval", models.IntegerField(default=5)),
+                ("last_polled_at", models.DateTimeField(null=True)),
+                ("expires_at", models.DateTimeField()),
+                (
+                    "client",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.PROTECT,
+                        to="authapp.oauthclient",
+                    ),
+                ),
+                (
+                    "user",
+                    models.ForeignKey(
+                        null=True,
+                        on_delete=django.db.models.deletion.PROTECT,
+                        to="authapp.customuser",
+                    ),
+                ),
+            ],
+            options={
+                "db_table": "authapp_oauth_device_code",
+            },
+        ),
+    ]
@@ -0,0 +1,36 @@
+# Generated by Django 4.2.1 on 2026-10-19 15:48
+
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0007_oauthdevicecode"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="MachineClient",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("client_id", models.CharField(max_length=64, unique=True)),
+                ("client_secret_hash", models.CharField(max_length=64)),
+                ("name", models.CharField(max_length=127)),
+                ("allowed_scopes", models.CharField(default="", max_length=255)),
+                ("active", models.BooleanField(default=True)),
+            ],
+            options={
+                "db_table": "authapp_machine_client",
+            },
+        ),
+    ]
@@ -0,0 +1,107 @@
+# Generated by Django 4.2.1 on 2026-10-19 17:05
+
+from django.db import migrations, models
+import django.db.models.deletion
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0009_userapikey"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="Role",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("name", models.CharField(max_length=63, unique=True)),
+                ("description", models.CharField(max_length=255, null=True)),
+                ("scopes", models.CharField(default="", max_length=1023)),
+            ],
+            options={
+                "db_table": "authapp_role",
+            },
+        ),
+        migrations.CreateModel(
+            name="UserRole",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                (
+                    "role",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.CASCADE,
+                        to="authapp.role",
+                    ),
+                ),
+                (
+                    "user",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.CASCADE,
+                        to="authapp.customuser",
+                    ),
+                ),
+            ],
+            options={
+                "db_table": "authapp_user_role",
+            },
+        ),
+        migrations.CreateModel(
+            name="UserScope",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True)),
+                ("updated_on", models.DateTimeField(auto_now=True)),
+                ("scope", models.CharField(max_length=127)),
+                (
+                    "user",
+                    models.ForeignKey(
+                        on_delete=django.db.models.deletion.CASCADE,
+                        to="authapp.customuser",
+                    ),
+                ),
+            ],
+            options={
+                "db_table": "authapp_user_scope",
+            },
+        ),
+        migrations.AddConstraint(
+            model_name="userrole",
+            constraint=models.UniqueConstraint(
+                fields=("user", "role"), name="unique_user_role"
+            ),
+        ),
+        migrations.AddConstraint(
+            model_name="userscope",
+            constraint=models.UniqueConstraint(
+                fields=("user", "scope"), name="unique_user_scope"
+            ),
+        ),
+    ]
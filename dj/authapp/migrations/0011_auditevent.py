@@ -0,0 +1,60 @@
+# Generated by Django 4.2.1 on 2026-10-19 21:40
+
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0010_role_userrole_userscope"),
+    ]
+
+    operations = [
+        migrations.CreateModel(
+            name="AuditEvent",
+            fields=[
+                (
+                    "id",
+                    models.BigAutoField(
+                        auto_created=True,
+                        primary_key=True,
+                        serialize=False,
+                        verbose_name="ID",
+                    ),
+                ),
+                ("created_on", models.DateTimeField(auto_now_add=True, db_index=True)),
+                ("event", models.CharField(db_index=True, max_length=63)),
+                ("outcome", models.CharField(max_length=15)),
+                ("reason", models.CharField(max_length=63, null=True)),
+                ("user_id", models.BigIntegerField(db_index=True, null=True)),
+                (
+                    "email",
+                    models.CharField(db_index=True, max_length=255, null=True),
+                ),
+                ("ip", models.CharField(max_length=45, null=True)),
+                ("user_agent", models.CharField(max_length=512, null=True)),
+                ("request_id", models.CharField(max_length=64, null=True)),
+            ],
+            options={
+                "db_table": "authapp_audit_event",
+            },
+        ),
+        # Note: append-only, the rows can not be changed or deleted even with the db credentials
+        # of the service. Drop the trigger to clean up the old events
+        migrations.RunSQL(
+            sql="""
+            CREATE FUNCTION authapp_audit_event_append_only() RETURNS trigger AS $$
+            BEGIN
+                RAISE EXCEPTION 'authapp_audit_event is append-only';
+            END;
+            $$ LANGUAGE plpgsql;
+
+            CREATE TRIGGER authapp_audit_event_append_only
+                BEFORE UPDATE OR DELETE ON authapp_audit_event
+                FOR EACH ROW EXECUTE FUNCTION authapp_audit_event_append_only();
+            """,
+            reverse_sql="""
+            DROP TRIGGER authapp_audit_event_append_only ON authapp_audit_event;
+            DROP FUNCTION authapp_audit_event_append_only();
+            """,
+        ),
+    ]
@@ -0,0 +1,17 @@
+# Generated by Django 4.2.1 on 2026-10-20 09:12
+
+from django.db import migrations, models
+
+
+class Migration(migrations.Migration):
+    dependencies = [
+        ("authapp", "0011_auditevent"),
+    ]
+
+    operations = [
+        migrations.AddField(
+            model_name="oauthclient",
+            name="can_introspect",
+            field=models.BooleanField(default=False),
+        ),
+    ]
@@ -30,10 +30,13 @@ class CustomUser(DateTimeBase):
 
 
 class UserToken(DateTimeBase):
-    token = models.CharField(max_length=255)
+    token = models.TextField(db_index=True)
     active = models.BooleanField(default=True)
     device_number = models.CharField(max_length=255, null=True)
     user = models.ForeignKey(CustomUser, on_delete=models.PROTECT)
+    # set for the access tokens issued to the oauth clients, null for the login sessions
+    client_id = models.CharField(max_length=64, null=True)
+    scope = models.CharField(max_length=255, null=True)
 
     class Meta:
         db_table = "authapp_user_token"
@@ -53,3 +56,169 @@ class UserOtp(DateTimeBase):
                 name="phone_and_email_not_both_null_otp",
             )
         ]
+
+
+class UserProviderToken(DateTimeBase):
+    user = models.ForeignKey(CustomUser, on_delete=models.PROTECT)
+    provider = models.CharField(max_length=50)
+    provider_user_id = models.CharField(max_length=255)
+    # AES-GCM encrypted access token, nonce prepended to the cipher text
+    access_token = models.BinaryField()
+    # GitHub App user tokens expire and come with a refresh token, encrypted same as access token
+    refresh_token = models.BinaryField(null=True)
+    expires_at = models.DateTimeField(null=True)
+    refresh_token_expires_at = models.DateTimeField(null=True)
+
+    class Meta:
+        db_table = "authapp_user_provider_token"
+        constraints = [
+            models.UniqueConstraint(
+                fields=["user", "provider"],
+                name="unique_user_provider_token",
+            )
+        ]
+
+
+class OAuthClient(DateTimeBase):
+    # apps using this service as OpenID Connect provider
+    client_id = models.CharField(max_length=64, unique=True)
+    # sha256 hex of the secret, public clients (SPA, mobile) have no secret and must use PKCE
+    client_secret_hash = models.CharField(max_length=64, null=True)
+    name = models.CharField(max_length=127)
+    # exact redirect uris allowed for the client
+    redirect_uris = models.JSONField(default=list)
+    # space separated scopes the client may ask for
+    allowed_scopes = models.CharField(max_length=255, default="openid email profile")
+    active = models.BooleanField(default=True)
+    # resource servers allowed to introspect the tokens of the other clients and the sessions,
+    # the others can introspect only their own tokens
+    can_introspect = models.BooleanField(default=False)
+
+    class Meta:
+        db_table = "authapp_oauth_client"
+
+
+class OAuthAuthorizationCode(DateTimeBase):
+    # sha256 hex of the code given to the client
+    code_hash = models.CharField(max_length=64, unique=True)
+    client = models.ForeignKey(OAuthClient, on_delete=models.PROTECT)
+    user = models.ForeignKey(CustomUser, on_delete=models.PROTECT)
+    redirect_uri = models.TextField()
+    scope = models.CharField(max_length=255)
+    nonce = models.CharField(max_length=255, null=True)
+    code_challenge = models.CharField(max_length=128, null=True)
+    code_challenge_method = models.CharField(max_length=10, null=True)
+    expires_at = models.DateTimeField()
+    used = models.BooleanField(default=False)
+
+    class Meta:
+        db_table = "authapp_oauth_code"
+
+
+class OAuthDeviceCode(DateTimeBase):
+    # RFC 8628 device authorization of the cli tools
+    # sha256 hex of the device code given to the device
+    device_code_hash = models.CharField(max_length=64, unique=True)
+    # code typed by the user on the `/device` page, stored without the dash
+    user_code = models.CharField(max_length=16, unique=True)
+    scope = models.CharField(max_length=255)
+    # pending, approved, denied, or used once the tokens are issued
+    status = models.CharField(max_length=16, default="pending")
+    # seconds the device has to wait between the polls
+    poll_interval = models.IntegerField(default=5)
+    last_polled_at = models.DateTimeField(null=True)
+    expires_at = models.DateTimeField()
+    client = models.ForeignKey(OAuthClient, on_delete=models.PROTECT)
+    # user who approved or denied the device
+    user = models.ForeignKey(CustomUser, on_delete=models.PROTECT, null=True)
+
+    class Meta:
+        db_table = "authapp_oauth_device_code"
+
+
+class MachineClient(DateTimeBase):
+    # backend jobs and services calling our apis with the client credentials grant, the tokens
+    # issued to them have the client_id as `sub`
+    client_id = models.CharField(max_length=64, unique=True)
+    # sha256 hex of the secret
+    client_secret_hash = models.CharField(max_length=64)
+    name = models.CharField(max_length=127)
+    # space separated scopes the client may ask for
+    allowed_scopes = models.CharField(max_length=255, default="")
+    active = models.BooleanField(default=True)
+
+    class Meta:
+        db_table = "authapp_machine_client"
+
+
+class UserApiKey(DateTimeBase):
+    # personal api keys of the users for scripting against our apis
+    user = models.ForeignKey(CustomUser, on_delete=models.PROTECT)
+    name = models.CharField(max_length=127)
+    # first characters of the key, shown in the list so the user can tell the keys apart
+    key_prefix = models.CharField(max_length=16)
+    # sha256 hex of the key, the key itself is shown only once on create
+    key_hash = models.CharField(max_length=64, unique=True)
+    # space separated scopes
+    scopes = models.CharField(max_length=255, default="")
+    expires_at = models.DateTimeField(null=True)
+    last_used_at = models.DateTimeField(null=True)
+    active = models.BooleanField(default=True)
+
+    class Meta:
+        db_table = "authapp_user_api_key"
+
+
+class Role(DateTimeBase):
+    # roles are granted to the users, every user of the role gets its scopes
+    name = models.CharField(max_length=63, unique=True)
+    description = models.CharField(max_length=255, null=True)
+    # space separated scopes, e.g. `ai:use reports:read`
+    scopes = models.CharField(max_length=1023, default="")
+
+    class Meta:
+        db_table = "authapp_role"
+
+
+class UserRole(DateTimeBase):
+    user = models.ForeignKey(CustomUser, on_delete=models.CASCADE)
+    role = models.ForeignKey(Role, on_delete=models.CASCADE)
+
+    class Meta:
+        db_table = "authapp_user_role"
+        constraints = [
+            models.UniqueConstraint(fields=["user", "role"], name="unique_user_role")
+        ]
+
+
+class UserScope(DateTimeBase):
+    # scopes granted to the user directly, besides the scopes of the roles
+    user = models.ForeignKey(CustomUser, on_delete=models.CASCADE)
+    scope = models.CharField(max_length=127)
+
+    class Meta:
+        db_table = "authapp_user_scope"
+        constraints = [
+            models.UniqueConstraint(fields=["user", "scope"], name="unique_user_scope")
+        ]
+
+
+class AuditEvent(models.Model):
+    # append-only log of the authentication events, a trigger rejects the updates and the deletes.
+    # user is not a foreign key so that the events outlive the users
+    created_on = models.DateTimeField(auto_now_add=True, db_index=True)
+    # otp-send, otp-verify, github-login, logout, session-revoke, profile-update, ...
+    event = models.CharField(max_length=63, db_index=True)
+    # success or failure
+    outcome = models.CharField(max_length=15)
+    # why it failed, e.g. `expired` for an otp
+    reason = models.CharField(max_length=63, null=True)
+    user_id = models.BigIntegerField(null=True, db_index=True)
+    # email of the otp events, there is no user before the otp is verified
+    email = models.CharField(max_length=255, null=True, db_index=True)
+    ip = models.CharField(max_length=45, null=True)
+    user_agent = models.CharField(max_length=512, null=True)
+    request_id = models.CharField(max_length=64, null=True)
+
+    class Meta:
+        db_table = "authapp_audit_event"
@@ -1,22 +1,160 @@
 use hyper::body::Incoming;
 use serde_json::json;
+use tracing::Instrument;
+
+// scope the callers need for the ai apis, granted to the users and the machine clients
+const AI_SCOPE: &str = "ai:use";
+
+// Note: jwts are verified with the auth client like the other services do, then the session is
+// checked with the database like `auth::authenticate` does. The secret is the `JWT_SECRET` of the
+// auth service, checked at the startup
+static VERIFIER: once_cell::sync::Lazy<auth_client::Verifier> = once_cell::sync::Lazy::new(|| {
+    let secret = match std::env::var("JWT_SECRET") {
+        Ok(secret) => secret,
+        Err(e) => panic!("{}{}", "JWT_SECRET not found in env ", e),
+    };
+    auth_client::Verifier::new(auth_client::KeySource::Secret(secret.into_bytes()))
+});
+
+// Note: comma separated oidc clients whose access tokens are accepted for their users, env
+// `AI_ALLOWED_CLIENTS`, none by default
+static ALLOWED_CLIENTS: once_cell::sync::Lazy<Vec<String>> =
+    once_cell::sync::Lazy::new(|| match std::env::var("AI_ALLOWED_CLIENTS") {
+        Ok(clients) => clients
+            .split(',')
+            .map(|client| client.trim().to_string())
+            .filter(|client| !client.is_empty())
+            .collect(),
+        Err(_) => vec![],
+    });
 
 #[derive(thiserror::Error, Debug)]
 pub enum AIError {
-    #[error("JWTError: {}", _0)]
-    JWT(#[from] auth::jwt::JWTError),
+    #[error("AuthenticateError: {}", _0)]
+    Authenticate(#[from] auth::authenticate::AuthenticateError),
+    #[error("VerifyError: {}", _0)]
+    Verify(#[from] auth_client::VerifyError),
+    // the route is not behind `authorize`
+    #[error("CallerNotFound")]
+    CallerNotFound,
+}
+
+impl AIError {
+    fn is_unauthorized(&self) -> bool {
+        match self {
+            AIError::Authenticate(err) => err.is_unauthorized(),
+            AIError::Verify(err) => err.is_unauthorized(),
+            AIError::CallerNotFound => false,
+        }
+    }
+}
+
+// Note: api keys are not jwts, they are looked up in the database
+async fn caller(
+    headers: &hyper::HeaderMap,
+    db_pool: &db::pg::DbPool,
+) -> Result<auth::authorization::Caller, AIError> {
+    // Note: the browsers without the authorization header call with the session cookie
+    if auth::api_key::from_headers(headers).is_some()
+        || auth_client::token_from_headers(headers).is_none()
+    {
+        return Ok(auth::authenticate::authenticate(headers, db_pool).await?);
+    }
+    let user = VERIFIER.verify_headers(headers).await?;
+    let caller = auth::authorization::Caller {
+        principal: match user.principal {
+            auth_client::Principal::User(user_id) => auth::jwt::Principal::User(user_id),
+            auth_client::Principal::Client(client_id) => auth::jwt::Principal::Client(client_id),
+            auth_client::Principal::Delegated { user_id, client_id } => {
+                auth::jwt::Principal::Delegated { user_id, client_id }
+            }
+        },
+        roles: user.roles,
+        scopes: user.scopes,
+    };
+    let token = auth_client::token_from_headers(headers).unwrap_or_default();
+    auth::authenticate::check_active(&caller.principal, token, db_pool).await?;
+    Ok(caller)
 }
 
-pub async fn handle(
+// Note: every route of the group needs the caller with the `AI_SCOPE`, there is one route for now
+// and it answers the caller for all the paths
+pub fn register(router: &mut router::Router<db::pg::DbPool>) {
+    router
+        .group("/api/ai")
+        .layer(authorize)
+        .any("/", handle)
+        .any("/{*path}", handle);
+}
+
+async fn authorize(
+    mut req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+    next: router::Next<db::pg::DbPool, Incoming>,
+) -> Result<hyper::Response<Vec<u8>>, router::BoxError> {
+    let caller = match caller(req.headers(), &db_pool).await {
+        Ok(caller) => caller,
+        Err(err) if err.is_unauthorized() => {
+            return Ok(auth::controller::response(
+                json!({"message": "unauthorized", "success": false}).to_string(),
+                hyper::StatusCode::UNAUTHORIZED,
+            ))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    if let auth::jwt::Principal::Delegated { ref client_id, .. } = caller.principal {
+        if !ALLOWED_CLIENTS.contains(client_id) {
+            return Ok(auth::controller::response(
+                json!({"message": "client is not allowed", "success": false}).to_string(),
+                hyper::StatusCode::FORBIDDEN,
+            ));
+        }
+    }
+    if let Err(err) = caller.require(AI_SCOPE) {
+        return Ok(err.response());
+    }
+    // Note: browsers calling with the session cookie send its csrf token as well
+    if !auth::session::verify_csrf(req.method(), req.headers()) {
+        return Ok(auth::controller::response(
+            json!({"message": "invalid csrf token", "success": false}).to_string(),
+            hyper::StatusCode::FORBIDDEN,
+        ));
+    }
+    let span = match &caller.principal {
+        auth::jwt::Principal::User(user_id) | auth::jwt::Principal::ApiKey { user_id, .. } => {
+            tracing::info_span!("user", user_id = user_id)
+        }
+        auth::jwt::Principal::Client(client_id) => {
+            tracing::info_span!("client", client_id = client_id.as_str())
+        }
+        auth::jwt::Principal::Delegated { user_id, client_id } => {
+            tracing::info_span!("user", user_id = user_id, client_id = client_id.as_str())
+        }
+    };
+    req.extensions_mut().insert(caller);
+    next.run(req, db_pool).instrument(span).await
+}
+
+async fn handle(
     req: hyper::Request<Incoming>,
     _db_pool: db::pg::DbPool,
 ) -> Result<hyper::Response<Vec<u8>>, AIError> {
-    let uid = auth::jwt::decode_jwt(req.headers())?;
+    let caller = req
+        .extensions()
+        .get::<auth::authorization::Caller>()
+        .ok_or(AIError::CallerNotFound)?;
+    // Note: backend jobs call with the client credentials token, they have no user
+    let data = match caller.principal {
+        auth::jwt::Principal::User(uid)
+        | auth::jwt::Principal::ApiKey { user_id: uid, .. }
+        | auth::jwt::Principal::Delegated { user_id: uid, .. } => {
+            json!({ "uid": uid.to_string() })
+        }
+        auth::jwt::Principal::Client(ref client_id) => json!({ "client_id": client_id }),
+    };
     let mut response = hyper::Response::new(
         json!({
-            "data": {
-                "uid": uid,
-            },
+            "data": data,
             "success": true
         })
         .to_string()
@@ -29,3 +167,48 @@ pub async fn handle(
     );
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    // Note: needs the database migrated by django in env `DATABASE_URL`, the test is skipped
+    // without it
+    fn pool() -> Option<db::pg::DbPool> {
+        let url = std::env::var("DATABASE_URL").ok()?;
+        let config = db::pg::PoolConfig {
+            max_size: 2,
+            ..Default::default()
+        };
+        Some(db::pg::get_connection_pool(url.as_str(), &config))
+    }
+
+    #[tokio::test]
+    async fn revoked_session_is_unauthorized() {
+        let pool = match pool() {
+            Some(pool) => pool,
+            None => return,
+        };
+        std::env::set_var("JWT_SECRET", "secret of the unit tests of the ai apis");
+        let email = format!("ai-{}@example.com", auth::crypto::random_token(6));
+        let user_id = db::user::upsert_with_email(email.as_str(), &pool)
+            .await
+            .unwrap();
+        let token = auth::jwt::create_jwt(user_id.to_string(), &Default::default()).unwrap();
+        db::user::create_token(user_id, token.as_str(), &pool)
+            .await
+            .unwrap();
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(
+            hyper::header::AUTHORIZATION,
+            format!("Bearer {}", token).parse().unwrap(),
+        );
+        assert!(super::caller(&headers, &pool).await.is_ok());
+
+        assert!(db::user::revoke_token(token.as_str(), &pool).await.unwrap());
+        let err = super::caller(&headers, &pool).await.unwrap_err();
+        assert!(matches!(
+            err,
+            super::AIError::Authenticate(auth::authenticate::AuthenticateError::RevokedToken)
+        ));
+        assert!(err.is_unauthorized());
+    }
+}
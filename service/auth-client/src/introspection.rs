@@ -0,0 +1,81 @@
+// Note: `/oauth/introspect` of the auth service (RFC 7662), asked with the credentials of a
+// confidential oidc client with `can_introspect`, else only its own tokens are answered as active.
+// Answers are cached for `cache_ttl` so that a token is asked about at most once in it, a revoked
+// token is accepted until its cached answer expires
+const MAX_CACHED: usize = 10_000;
+
+pub struct Introspection {
+    url: String,
+    client_id: String,
+    client_secret: String,
+    cache_ttl: std::time::Duration,
+    client: reqwest::Client,
+    cache: std::sync::Mutex<std::collections::HashMap<String, (bool, std::time::Instant)>>,
+}
+
+#[derive(serde::Deserialize)]
+struct IntrospectionRes {
+    active: bool,
+}
+
+impl Introspection {
+    pub fn new(
+        url: &str,
+        client_id: &str,
+        client_secret: &str,
+        cache_ttl: std::time::Duration,
+    ) -> Introspection {
+        Introspection {
+            url: url.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            cache_ttl,
+            client: reqwest::Client::new(),
+            cache: Default::default(),
+        }
+    }
+
+    pub(crate) async fn is_active(&self, token: &str) -> Result<bool, crate::VerifyError> {
+        if let Some(active) = self.cached(token) {
+            return Ok(active);
+        }
+        let body = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("token", token)
+            .finish();
+        let active = self
+            .client
+            .post(self.url.as_str())
+            .basic_auth(self.client_id.as_str(), Some(self.client_secret.as_str()))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "application/x-www-form-urlencoded",
+            )
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| crate::VerifyError::Introspection(e.to_string()))?
+            .json::<IntrospectionRes>()
+            .await
+            .map_err(|e| crate::VerifyError::Introspection(e.to_string()))?
+            .active;
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if cache.len() >= MAX_CACHED {
+            let ttl = self.cache_ttl;
+            cache.retain(|_, (_, at)| at.elapsed() < ttl);
+        }
+        if cache.len() < MAX_CACHED {
+            cache.insert(token.to_string(), (active, std::time::Instant::now()));
+        }
+        Ok(active)
+    }
+
+    fn cached(&self, token: &str) -> Option<bool> {
+        let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache
+            .get(token)
+            .filter(|(_, at)| at.elapsed() < self.cache_ttl)
+            .map(|(active, _)| *active)
+    }
+}
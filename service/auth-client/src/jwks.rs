@@ -0,0 +1,104 @@
+// Note: keys of the JWKS are fetched on the first use and cached for the ttl, an unknown `kid`
+// refetches them early (the auth service rotated its key), at most once in `MIN_REFETCH`
+const MIN_REFETCH: std::time::Duration = std::time::Duration::from_secs(30);
+
+pub(crate) struct Jwks {
+    url: String,
+    ttl: std::time::Duration,
+    client: reqwest::Client,
+    cache: tokio::sync::RwLock<Cache>,
+}
+
+#[derive(Default)]
+struct Cache {
+    keys: std::collections::HashMap<String, jsonwebtoken::DecodingKey>,
+    fetched_at: Option<std::time::Instant>,
+}
+
+#[derive(serde::Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(serde::Deserialize)]
+struct Jwk {
+    kid: Option<String>,
+    kty: String,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+impl Jwks {
+    pub(crate) fn new(url: String, ttl: std::time::Duration) -> Jwks {
+        Jwks {
+            url,
+            ttl,
+            client: reqwest::Client::new(),
+            cache: Default::default(),
+        }
+    }
+
+    pub(crate) async fn key(
+        &self,
+        kid: &str,
+    ) -> Result<jsonwebtoken::DecodingKey, crate::VerifyError> {
+        {
+            let cache = self.cache.read().await;
+            let fresh = cache.fetched_at.is_some_and(|at| at.elapsed() < self.ttl);
+            if let (true, Some(key)) = (fresh, cache.keys.get(kid)) {
+                return Ok(key.clone());
+            }
+        }
+
+        let mut cache = self.cache.write().await;
+        // Note: the other request may have fetched them while this one waited for the lock
+        let refetch = match cache.fetched_at {
+            None => true,
+            Some(at) if at.elapsed() >= self.ttl => true,
+            Some(at) => !cache.keys.contains_key(kid) && at.elapsed() >= MIN_REFETCH,
+        };
+        if refetch {
+            cache.keys = self.fetch().await?;
+            cache.fetched_at = Some(std::time::Instant::now());
+            tracing::info!(
+                message = "jwks fetched",
+                url = self.url,
+                keys = cache.keys.len()
+            );
+        }
+        cache
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| crate::VerifyError::UnknownKey(kid.to_string()))
+    }
+
+    async fn fetch(
+        &self,
+    ) -> Result<std::collections::HashMap<String, jsonwebtoken::DecodingKey>, crate::VerifyError>
+    {
+        let set = self
+            .client
+            .get(self.url.as_str())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| crate::VerifyError::Jwks(e.to_string()))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| crate::VerifyError::Jwks(e.to_string()))?;
+        Ok(set
+            .keys
+            .into_iter()
+            .filter(|jwk| jwk.kty.eq("RSA"))
+            .filter_map(|jwk| match (jwk.kid, jwk.n, jwk.e) {
+                (Some(kid), Some(n), Some(e)) => {
+                    jsonwebtoken::DecodingKey::from_rsa_components(n.as_str(), e.as_str())
+                        .ok()
+                        .map(|key| (kid, key))
+                }
+                _ => None,
+            })
+            .collect())
+    }
+}
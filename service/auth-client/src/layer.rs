@@ -0,0 +1,152 @@
+// Note: tower middleware, requests with a valid token reach the inner service with the
+// `AuthenticatedUser` extension, the others are answered with 401 (503 if the auth service could
+// not be asked) without calling it
+#[derive(Clone)]
+pub struct AuthLayer {
+    verifier: crate::Verifier,
+}
+
+impl AuthLayer {
+    pub fn new(verifier: crate::Verifier) -> AuthLayer {
+        AuthLayer { verifier }
+    }
+}
+
+impl<S> tower_layer::Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            verifier: self.verifier.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    verifier: crate::Verifier,
+}
+
+impl<S, B, ResBody> tower_service::Service<hyper::Request<B>> for AuthService<S>
+where
+    S: tower_service::Service<hyper::Request<B>, Response = hyper::Response<ResBody>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+    ResBody: From<String>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(
+        &mut self,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: hyper::Request<B>) -> Self::Future {
+        // Note: the ready inner service is taken, its clone is left for the next call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let verifier = self.verifier.clone();
+        Box::pin(async move {
+            match verifier.verify_headers(req.headers()).await {
+                Ok(user) => {
+                    req.extensions_mut().insert(user);
+                    inner.call(req).await
+                }
+                Err(err) => Ok(rejection(&err)),
+            }
+        })
+    }
+}
+
+fn rejection<ResBody: From<String>>(err: &crate::VerifyError) -> hyper::Response<ResBody> {
+    let status = match err.is_unauthorized() {
+        true => {
+            tracing::info!(message = "token rejected", error = err.to_string());
+            hyper::StatusCode::UNAUTHORIZED
+        }
+        false => {
+            tracing::error!(
+                message = "token could not be verified",
+                error = err.to_string()
+            );
+            hyper::StatusCode::SERVICE_UNAVAILABLE
+        }
+    };
+    let body = serde_json::json!({"message": status.canonical_reason(), "success": false});
+    let mut response = hyper::Response::new(ResBody::from(body.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    if status == hyper::StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(
+            hyper::header::WWW_AUTHENTICATE,
+            hyper::header::HeaderValue::from_static("Bearer"),
+        );
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    // echoes the user id of the extension
+    #[derive(Clone)]
+    struct Echo;
+
+    impl tower_service::Service<hyper::Request<()>> for Echo {
+        type Response = hyper::Response<String>;
+        type Error = std::convert::Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(
+            &mut self,
+            _: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<Result<(), Self::Error>> {
+            std::task::Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: hyper::Request<()>) -> Self::Future {
+            let user = req.extensions().get::<crate::AuthenticatedUser>().unwrap();
+            std::future::ready(Ok(hyper::Response::new(format!("{:?}", user.user_id()))))
+        }
+    }
+
+    #[tokio::test]
+    async fn layer_injects_user() {
+        use tower_layer::Layer;
+        use tower_service::Service;
+
+        let verifier = crate::Verifier::new(crate::KeySource::Secret(b"secret".to_vec()));
+        let mut service = super::AuthLayer::new(verifier).layer(Echo);
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512),
+            &serde_json::json!({"sub": "42", "exp": jsonwebtoken::get_current_timestamp() + 60}),
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        let req = hyper::Request::builder()
+            .header(hyper::header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(())
+            .unwrap();
+        let response = service.call(req).await.unwrap();
+        assert_eq!(response.body(), "Some(42)");
+
+        let response = service.call(hyper::Request::new(())).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+        assert!(response
+            .headers()
+            .contains_key(hyper::header::WWW_AUTHENTICATE));
+    }
+}
@@ -0,0 +1,163 @@
+// Note: verification of the tokens issued by the auth service, for the services consuming them.
+// Tokens are verified with the shared secret (HS512 tokens) or with the JWKS of the service (RS256
+// tokens), optionally asking `/oauth/introspect` whether they are revoked. Use `Verifier::verify`
+// in the handlers, or `AuthLayer` with the tower based servers which puts the `AuthenticatedUser`
+// in the request extensions
+mod introspection;
+mod jwks;
+mod layer;
+mod verifier;
+
+pub use introspection::Introspection;
+pub use layer::{AuthLayer, AuthService};
+pub use verifier::{KeySource, Verifier};
+
+#[derive(thiserror::Error, Debug)]
+pub enum VerifyError {
+    #[error("TokenNotFound")]
+    TokenNotFound,
+    #[error("InvalidToken: {}", _0)]
+    InvalidToken(#[from] jsonwebtoken::errors::Error),
+    #[error("UnknownKey: {}", _0)]
+    UnknownKey(String),
+    #[error("InvalidSubject")]
+    InvalidSubject,
+    #[error("InvalidTokenType")]
+    InvalidTokenType,
+    #[error("Revoked")]
+    Revoked,
+    #[error("JwksError: {}", _0)]
+    Jwks(String),
+    #[error("IntrospectionError: {}", _0)]
+    Introspection(String),
+}
+
+impl VerifyError {
+    // Note: false if the auth service could not be asked, the caller is not to be blamed for it
+    pub fn is_unauthorized(&self) -> bool {
+        !matches!(self, VerifyError::Jwks(_) | VerifyError::Introspection(_))
+    }
+}
+
+// who the token is issued to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    User(i64),
+    // machine client of the client credentials grant
+    Client(String),
+    // access token the user granted to a third-party oidc client, the services accept it only if
+    // they trust the client
+    Delegated { user_id: i64, client_id: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedUser {
+    pub principal: Principal,
+    // oidc client the token is issued to, none for the session tokens
+    pub client_id: Option<String>,
+    pub scopes: Vec<String>,
+    pub roles: Vec<String>,
+    pub expires_at: u64,
+}
+
+impl AuthenticatedUser {
+    // Note: the user of the session tokens only, see `Principal::Delegated`
+    pub fn user_id(&self) -> Option<i64> {
+        match self.principal {
+            Principal::User(user_id) => Some(user_id),
+            Principal::Client(_) | Principal::Delegated { .. } => None,
+        }
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s.eq(scope))
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r.eq(role))
+    }
+}
+
+// claims of the tokens issued by the auth service, see `auth::jwt::Claims`
+#[derive(Debug, serde::Deserialize)]
+struct Claims {
+    sub: String,
+    exp: u64,
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+    #[serde(default)]
+    gty: Option<String>,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(default)]
+    aud: Option<String>,
+}
+
+const CLIENT_CREDENTIALS_GTY: &str = "client-credentials";
+// `typ` header of the access tokens, see `auth::jwt::ACCESS_TOKEN_TYP`
+const ACCESS_TOKEN_TYP: &str = "at+jwt";
+
+impl Claims {
+    // Note: access tokens must have their `typ` and the client as `aud`, the session tokens
+    // neither, so that one is never taken for the other
+    fn check_type(&self, typ: Option<&str>) -> Result<(), VerifyError> {
+        let valid = match self.client_id {
+            Some(ref client_id) => {
+                typ == Some(ACCESS_TOKEN_TYP) && self.aud.as_deref() == Some(client_id.as_str())
+            }
+            None => typ != Some(ACCESS_TOKEN_TYP) && self.aud.is_none(),
+        };
+        match valid {
+            true => Ok(()),
+            false => Err(VerifyError::InvalidTokenType),
+        }
+    }
+}
+
+impl TryFrom<Claims> for AuthenticatedUser {
+    type Error = VerifyError;
+
+    fn try_from(claims: Claims) -> Result<Self, Self::Error> {
+        let principal = match (claims.gty.as_deref(), claims.client_id.as_ref()) {
+            (Some(CLIENT_CREDENTIALS_GTY), _) => Principal::Client(claims.sub),
+            (_, client_id) => {
+                let user_id = claims
+                    .sub
+                    .parse::<i64>()
+                    .map_err(|_| VerifyError::InvalidSubject)?;
+                match client_id {
+                    Some(client_id) => Principal::Delegated {
+                        user_id,
+                        client_id: client_id.clone(),
+                    },
+                    None => Principal::User(user_id),
+                }
+            }
+        };
+        Ok(AuthenticatedUser {
+            principal,
+            client_id: claims.client_id,
+            scopes: claims
+                .scope
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            roles: claims.roles,
+            expires_at: claims.exp,
+        })
+    }
+}
+
+// bearer token of the authorization header
+pub fn token_from_headers(headers: &hyper::HeaderMap<hyper::header::HeaderValue>) -> Option<&str> {
+    headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+}
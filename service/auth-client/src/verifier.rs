@@ -0,0 +1,223 @@
+pub enum KeySource {
+    // HS512 secret shared with the auth service
+    Secret(Vec<u8>),
+    // `/oauth/jwks` of the auth service for the RS256 tokens, keys are cached for the ttl. The
+    // tokens must have the `audience` as their `aud` (the client id of the service), the ID tokens
+    // issued to any other client are rejected
+    Jwks {
+        url: String,
+        ttl: std::time::Duration,
+        audience: String,
+    },
+}
+
+enum Keys {
+    Secret(jsonwebtoken::DecodingKey),
+    Jwks {
+        jwks: crate::jwks::Jwks,
+        audience: String,
+    },
+}
+
+// Note: cheap to clone, the clones share the key and the introspection caches
+#[derive(Clone)]
+pub struct Verifier {
+    inner: std::sync::Arc<Inner>,
+}
+
+struct Inner {
+    keys: Keys,
+    issuer: Option<String>,
+    audience: Option<String>,
+    introspection: Option<crate::Introspection>,
+}
+
+impl Verifier {
+    pub fn new(source: KeySource) -> Verifier {
+        let keys = match source {
+            KeySource::Secret(secret) => {
+                Keys::Secret(jsonwebtoken::DecodingKey::from_secret(secret.as_slice()))
+            }
+            KeySource::Jwks { url, ttl, audience } => Keys::Jwks {
+                jwks: crate::jwks::Jwks::new(url, ttl),
+                audience,
+            },
+        };
+        Verifier {
+            inner: std::sync::Arc::new(Inner {
+                keys,
+                issuer: None,
+                audience: None,
+                introspection: None,
+            }),
+        }
+    }
+
+    // Note: the `with_` methods are for the setup, they panic once the verifier is cloned
+    pub fn with_issuer(mut self, issuer: &str) -> Verifier {
+        self.inner_mut().issuer = Some(issuer.to_string());
+        self
+    }
+
+    // Note: for `KeySource::Secret`, the jwks tokens are checked with the audience of the key source
+    pub fn with_audience(mut self, audience: &str) -> Verifier {
+        self.inner_mut().audience = Some(audience.to_string());
+        self
+    }
+
+    // every verified token is checked with the introspection endpoint as well, to catch the
+    // revoked tokens before they expire
+    pub fn with_introspection(mut self, introspection: crate::Introspection) -> Verifier {
+        self.inner_mut().introspection = Some(introspection);
+        self
+    }
+
+    fn inner_mut(&mut self) -> &mut Inner {
+        std::sync::Arc::get_mut(&mut self.inner).expect("verifier is already shared")
+    }
+
+    pub async fn verify(
+        &self,
+        token: &str,
+    ) -> Result<crate::AuthenticatedUser, crate::VerifyError> {
+        let token = token.strip_prefix("Bearer ").unwrap_or(token);
+        let claims = match self.inner.keys {
+            Keys::Secret(ref key) => {
+                let decoded = jsonwebtoken::decode::<crate::Claims>(
+                    token,
+                    key,
+                    &self.validation(jsonwebtoken::Algorithm::HS512),
+                )?;
+                // Note: the session and the access tokens share the secret, see `check_type`
+                decoded.claims.check_type(decoded.header.typ.as_deref())?;
+                decoded.claims
+            }
+            Keys::Jwks {
+                ref jwks,
+                ref audience,
+            } => {
+                let kid = jsonwebtoken::decode_header(token)?
+                    .kid
+                    .ok_or_else(|| crate::VerifyError::UnknownKey("kid not found".to_string()))?;
+                let key = jwks.key(kid.as_str()).await?;
+                let mut validation = self.validation(jsonwebtoken::Algorithm::RS256);
+                validation.set_audience(&[audience]);
+                validation.set_required_spec_claims(&["exp", "aud"]);
+                jsonwebtoken::decode::<crate::Claims>(token, &key, &validation)?.claims
+            }
+        };
+        let user = crate::AuthenticatedUser::try_from(claims)?;
+        if let Some(ref introspection) = self.inner.introspection {
+            if !introspection.is_active(token).await? {
+                return Err(crate::VerifyError::Revoked);
+            }
+        }
+        Ok(user)
+    }
+
+    pub async fn verify_headers(
+        &self,
+        headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+    ) -> Result<crate::AuthenticatedUser, crate::VerifyError> {
+        let token = crate::token_from_headers(headers).ok_or(crate::VerifyError::TokenNotFound)?;
+        self.verify(token).await
+    }
+
+    fn validation(&self, algorithm: jsonwebtoken::Algorithm) -> jsonwebtoken::Validation {
+        let mut validation = jsonwebtoken::Validation::new(algorithm);
+        if let Some(ref issuer) = self.inner.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        if let Some(ref audience) = self.inner.audience {
+            validation.set_audience(&[audience]);
+        }
+        validation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn token(claims: serde_json::Value, secret: &[u8]) -> String {
+        // Note: the tokens issued to the clients are access tokens, see `Claims::check_type`
+        let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512);
+        if claims.get("client_id").is_some() {
+            header.typ = Some(crate::ACCESS_TOKEN_TYP.to_string());
+        }
+        jsonwebtoken::encode(
+            &header,
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(secret),
+        )
+        .unwrap()
+    }
+
+    fn exp() -> u64 {
+        jsonwebtoken::get_current_timestamp() + 60
+    }
+
+    #[tokio::test]
+    async fn verify_with_secret() {
+        let verifier = super::Verifier::new(super::KeySource::Secret(b"secret".to_vec()));
+        let user = verifier
+            .verify(&token(
+                serde_json::json!({"sub": "42", "exp": exp(), "scope": "ai:use", "roles": ["admin"]}),
+                b"secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(user.user_id(), Some(42));
+        assert!(user.has_scope("ai:use") && user.has_role("admin"));
+
+        let client = verifier
+            .verify(&format!(
+                "Bearer {}",
+                token(
+                    serde_json::json!({"sub": "job", "exp": exp(), "gty": "client-credentials", "client_id": "job", "aud": "job"}),
+                    b"secret"
+                )
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            client.principal,
+            crate::Principal::Client("job".to_string())
+        );
+
+        let delegated = verifier
+            .verify(&token(
+                serde_json::json!({"sub": "42", "exp": exp(), "client_id": "app", "aud": "app"}),
+                b"secret",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(delegated.user_id(), None);
+        assert_eq!(
+            delegated.principal,
+            crate::Principal::Delegated {
+                user_id: 42,
+                client_id: "app".to_string()
+            }
+        );
+
+        // an access token of the client presented with the type of the session tokens
+        let retyped = jsonwebtoken::encode(
+            &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512),
+            &serde_json::json!({"sub": "42", "exp": exp(), "client_id": "app", "aud": "app"}),
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(matches!(
+            verifier.verify(&retyped).await,
+            Err(crate::VerifyError::InvalidTokenType)
+        ));
+
+        let forged = token(serde_json::json!({"sub": "42", "exp": exp()}), b"other");
+        assert!(verifier
+            .verify(&forged)
+            .await
+            .unwrap_err()
+            .is_unauthorized());
+        let expired = token(serde_json::json!({"sub": "42", "exp": 1}), b"secret");
+        assert!(verifier.verify(&expired).await.is_err());
+    }
+}
@@ -0,0 +1,299 @@
+// Note: the jwks and the introspection modes against a local stand-in of the auth service, it
+// answers the routes with the bodies set by the test and counts the requests of each route
+const KEY_A: &str = include_str!("rsa-a.pem");
+const KEY_B: &str = include_str!("rsa-b.pem");
+
+type Routes = std::sync::Arc<std::sync::Mutex<std::collections::HashMap<String, (u16, String)>>>;
+type Hits = std::sync::Arc<std::sync::Mutex<Vec<(String, Option<String>)>>>;
+
+struct Server {
+    url: String,
+    routes: Routes,
+    // path and the authorization header of the requests
+    hits: Hits,
+}
+
+impl Server {
+    async fn start() -> Server {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (routes, hits) = (Routes::default(), Hits::default());
+        let (r, h) = (routes.clone(), hits.clone());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(answer(stream, r.clone(), h.clone()));
+            }
+        });
+        Server { url, routes, hits }
+    }
+
+    fn route(&self, path: &str, status: u16, body: String) {
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), (status, body));
+    }
+
+    fn hits(&self, path: &str) -> usize {
+        self.hits
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(p, _)| p.eq(path))
+            .count()
+    }
+}
+
+async fn answer(mut stream: tokio::net::TcpStream, routes: Routes, hits: Hits) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let mut request = vec![];
+    let mut buf = [0u8; 4096];
+    // Note: the bodies of the requests here are small, the head and the body are read together
+    let head = loop {
+        let n = stream.read(&mut buf).await.unwrap();
+        request.extend_from_slice(&buf[..n]);
+        if let Some(end) = request.windows(4).position(|w| w.eq(b"\r\n\r\n")) {
+            break String::from_utf8_lossy(&request[..end]).to_string();
+        }
+        if n == 0 {
+            return;
+        }
+    };
+    let path = head
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+    let authorization = head
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+        .map(|(_, value)| value.trim().to_string());
+    hits.lock().unwrap().push((path.clone(), authorization));
+
+    let (status, body) = routes
+        .lock()
+        .unwrap()
+        .get(path.as_str())
+        .cloned()
+        .unwrap_or((404, "{}".to_string()));
+    let response = format!(
+        "HTTP/1.1 {} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await.unwrap();
+}
+
+fn base64_url(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn jwk(pem: &str, kid: &str) -> serde_json::Value {
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::traits::PublicKeyParts;
+
+    let key = rsa::RsaPrivateKey::from_pkcs8_pem(pem).unwrap();
+    serde_json::json!({
+        "kty": "RSA",
+        "use": "sig",
+        "alg": "RS256",
+        "kid": kid,
+        "n": base64_url(&key.n().to_bytes_be()),
+        "e": base64_url(&key.e().to_bytes_be()),
+    })
+}
+
+fn id_token(pem: &str, kid: &str, aud: &str) -> String {
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some(kid.to_string());
+    jsonwebtoken::encode(
+        &header,
+        &serde_json::json!({
+            "sub": "42",
+            "aud": aud,
+            "exp": jsonwebtoken::get_current_timestamp() + 60,
+        }),
+        &jsonwebtoken::EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap(),
+    )
+    .unwrap()
+}
+
+fn jwks_verifier(server: &Server, ttl: std::time::Duration) -> auth_client::Verifier {
+    auth_client::Verifier::new(auth_client::KeySource::Jwks {
+        url: format!("{}/oauth/jwks", server.url),
+        ttl,
+        audience: "reports".to_string(),
+    })
+}
+
+#[tokio::test]
+async fn jwks_are_fetched_once_in_the_ttl() {
+    let server = Server::start().await;
+    server.route(
+        "/oauth/jwks",
+        200,
+        serde_json::json!({"keys": [jwk(KEY_A, "a")]}).to_string(),
+    );
+    let verifier = jwks_verifier(&server, std::time::Duration::from_millis(300));
+
+    let token = id_token(KEY_A, "a", "reports");
+    for _ in 0..3 {
+        let user = verifier.verify(token.as_str()).await.unwrap();
+        assert_eq!(user.user_id(), Some(42));
+    }
+    assert_eq!(server.hits("/oauth/jwks"), 1);
+
+    tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+    verifier.verify(token.as_str()).await.unwrap();
+    assert_eq!(server.hits("/oauth/jwks"), 2);
+}
+
+#[tokio::test]
+async fn rotated_key_is_taken_after_refetch() {
+    let server = Server::start().await;
+    server.route(
+        "/oauth/jwks",
+        200,
+        serde_json::json!({"keys": [jwk(KEY_A, "a")]}).to_string(),
+    );
+    let verifier = jwks_verifier(&server, std::time::Duration::from_millis(300));
+    verifier
+        .verify(id_token(KEY_A, "a", "reports").as_str())
+        .await
+        .unwrap();
+
+    server.route(
+        "/oauth/jwks",
+        200,
+        serde_json::json!({"keys": [jwk(KEY_B, "b")]}).to_string(),
+    );
+    let rotated = id_token(KEY_B, "b", "reports");
+    // Note: the keys were just fetched, an unknown kid does not make the verifier ask again
+    assert!(matches!(
+        verifier.verify(rotated.as_str()).await,
+        Err(auth_client::VerifyError::UnknownKey(_))
+    ));
+    assert_eq!(server.hits("/oauth/jwks"), 1);
+
+    tokio::time::sleep(std::time::Duration::from_millis(400)).await;
+    verifier.verify(rotated.as_str()).await.unwrap();
+    assert_eq!(server.hits("/oauth/jwks"), 2);
+    // the old key is gone with the refetch
+    assert!(verifier
+        .verify(id_token(KEY_A, "a", "reports").as_str())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn jwks_tokens_need_the_audience() {
+    let server = Server::start().await;
+    server.route(
+        "/oauth/jwks",
+        200,
+        serde_json::json!({"keys": [jwk(KEY_A, "a")]}).to_string(),
+    );
+    let verifier = jwks_verifier(&server, std::time::Duration::from_secs(60));
+    let other = verifier
+        .verify(id_token(KEY_A, "a", "other-client").as_str())
+        .await
+        .unwrap_err();
+    assert!(other.is_unauthorized());
+
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some("a".to_string());
+    let without = jsonwebtoken::encode(
+        &header,
+        &serde_json::json!({"sub": "42", "exp": jsonwebtoken::get_current_timestamp() + 60}),
+        &jsonwebtoken::EncodingKey::from_rsa_pem(KEY_A.as_bytes()).unwrap(),
+    )
+    .unwrap();
+    assert!(verifier.verify(without.as_str()).await.is_err());
+}
+
+#[tokio::test]
+async fn unreachable_jwks_is_not_the_callers_fault() {
+    let server = Server::start().await;
+    server.route("/oauth/jwks", 500, "{}".to_string());
+    let err = jwks_verifier(&server, std::time::Duration::from_secs(60))
+        .verify(id_token(KEY_A, "a", "reports").as_str())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, auth_client::VerifyError::Jwks(_)));
+    assert!(!err.is_unauthorized());
+}
+
+fn session_token(sub: &str) -> String {
+    jsonwebtoken::encode(
+        &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512),
+        &serde_json::json!({"sub": sub, "exp": jsonwebtoken::get_current_timestamp() + 60}),
+        &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap()
+}
+
+fn introspecting_verifier(server: &Server) -> auth_client::Verifier {
+    auth_client::Verifier::new(auth_client::KeySource::Secret(b"secret".to_vec()))
+        .with_introspection(auth_client::Introspection::new(
+            format!("{}/oauth/introspect", server.url).as_str(),
+            "reports",
+            "s3cret",
+            std::time::Duration::from_secs(60),
+        ))
+}
+
+#[tokio::test]
+async fn introspection_answers_are_cached() {
+    let server = Server::start().await;
+    server.route("/oauth/introspect", 200, r#"{"active": true}"#.to_string());
+    let verifier = introspecting_verifier(&server);
+
+    let token = session_token("42");
+    for _ in 0..2 {
+        assert_eq!(
+            verifier.verify(token.as_str()).await.unwrap().user_id(),
+            Some(42)
+        );
+    }
+    assert_eq!(server.hits("/oauth/introspect"), 1);
+    // asked with the basic auth of the client
+    let (_, authorization) = server.hits.lock().unwrap()[0].clone();
+    assert_eq!(
+        authorization.as_deref(),
+        Some(format!("Basic {}", {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.encode("reports:s3cret")
+        }))
+        .as_deref()
+    );
+}
+
+#[tokio::test]
+async fn introspected_revoked_token_is_rejected() {
+    let server = Server::start().await;
+    server.route("/oauth/introspect", 200, r#"{"active": false}"#.to_string());
+    let verifier = introspecting_verifier(&server);
+    let err = verifier
+        .verify(session_token("42").as_str())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, auth_client::VerifyError::Revoked));
+    assert!(err.is_unauthorized());
+
+    // Note: an invalid token is rejected before the auth service is asked
+    assert!(verifier.verify("not.a.jwt").await.is_err());
+    assert_eq!(server.hits("/oauth/introspect"), 1);
+
+    server.route("/oauth/introspect", 503, "{}".to_string());
+    let err = verifier
+        .verify(session_token("43").as_str())
+        .await
+        .unwrap_err();
+    assert!(matches!(err, auth_client::VerifyError::Introspection(_)));
+    assert!(!err.is_unauthorized());
+}
@@ -0,0 +1,202 @@
+// Note: typed client of the auth http api for our rust services, the request and response types are
+// the ones of the server from `auth-types`. Session calls send the user's session token as the
+// bearer token, set it with `with_session`
+pub use auth_types as types;
+
+use auth_types::{api_key, identities, otp, user};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SdkError {
+    #[error("HttpError: {}", _0)]
+    Http(#[from] reqwest::Error),
+    #[error("ApiError: {} {}", status, message)]
+    Api { status: u16, message: String },
+    #[error("DecodeError: {}", _0)]
+    Decode(#[from] serde_json::Error),
+    #[error("SessionRequired")]
+    SessionRequired,
+}
+
+#[derive(Clone)]
+pub struct AuthClient {
+    base_url: String,
+    http: reqwest::Client,
+    session: Option<String>,
+}
+
+impl AuthClient {
+    // `base_url` of the auth service, e.g. `https://auth.example.com`
+    pub fn new(base_url: &str) -> AuthClient {
+        AuthClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            session: None,
+        }
+    }
+
+    pub fn with_session(mut self, token: &str) -> AuthClient {
+        self.session = Some(token.to_string());
+        self
+    }
+
+    pub async fn send_otp(&self, req: &otp::SendOtpReq) -> Result<otp::SendOtpRes, SdkError> {
+        self.post("/v1/api/auth/send-otp/", req).await
+    }
+
+    pub async fn resend_otp(&self, req: &otp::SendOtpReq) -> Result<otp::SendOtpRes, SdkError> {
+        self.post("/v1/api/auth/resend-otp/", req).await
+    }
+
+    // Note: the returned `user_token` is the session token for `with_session`, it is empty if the
+    // server is in the cookie session mode
+    pub async fn verify_otp(&self, req: &otp::VerifyOtpReq) -> Result<otp::VerifyOtpRes, SdkError> {
+        self.post("/v1/api/auth/verify-otp/", req).await
+    }
+
+    // Note: `Undetermined` status is returned as it is, the server answers it with 503
+    pub async fn get_identities(
+        &self,
+        req: &identities::GetIdsRequest,
+    ) -> Result<identities::GetIdsResponse, SdkError> {
+        let response = self
+            .http
+            .post(self.url("/auth/get-identities/"))
+            .json(req)
+            .send()
+            .await?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await?;
+        if status == 503 {
+            if let Ok(data) =
+                serde_json::from_slice::<auth_types::ApiSuccess<identities::GetIdsResponse>>(&body)
+            {
+                return Ok(data.data);
+            }
+        }
+        decode(status, &body)
+    }
+
+    pub async fn profile(&self) -> Result<user::Profile, SdkError> {
+        self.get("/v1/api/auth/profile/").await
+    }
+
+    pub async fn update_profile(
+        &self,
+        req: &user::UpdateProfileReq,
+    ) -> Result<user::Profile, SdkError> {
+        self.post("/v1/api/auth/profile/", req).await
+    }
+
+    pub async fn sessions(&self) -> Result<Vec<user::Session>, SdkError> {
+        self.get("/v1/api/auth/sessions/").await
+    }
+
+    pub async fn revoke_session(&self, id: i64) -> Result<(), SdkError> {
+        self.post::<serde_json::Value>(
+            "/v1/api/auth/sessions/revoke/",
+            &user::RevokeSessionReq { id },
+        )
+        .await
+        .map(|_| ())
+    }
+
+    pub async fn logout(&self) -> Result<(), SdkError> {
+        self.post::<serde_json::Value>("/v1/api/auth/logout/", &serde_json::json!({}))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn api_keys(&self) -> Result<Vec<api_key::ApiKey>, SdkError> {
+        self.get("/v1/api/auth/api-keys/").await
+    }
+
+    pub async fn create_api_key(
+        &self,
+        req: &api_key::CreateApiKeyReq,
+    ) -> Result<api_key::CreateApiKeyRes, SdkError> {
+        self.post("/v1/api/auth/api-keys/", req).await
+    }
+
+    pub async fn revoke_api_key(&self, id: i64) -> Result<(), SdkError> {
+        self.post::<serde_json::Value>(
+            "/v1/api/auth/api-keys/revoke/",
+            &api_key::RevokeApiKeyReq { id },
+        )
+        .await
+        .map(|_| ())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    fn session(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.session {
+            Some(ref token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, SdkError> {
+        if self.session.is_none() {
+            return Err(SdkError::SessionRequired);
+        }
+        self.send(self.session(self.http.get(self.url(path)))).await
+    }
+
+    async fn post<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl serde::Serialize,
+    ) -> Result<T, SdkError> {
+        self.send(self.session(self.http.post(self.url(path)).json(body)))
+            .await
+    }
+
+    async fn send<T: serde::de::DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<T, SdkError> {
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await?;
+        decode(status, &body)
+    }
+}
+
+// Note: the successful responses are `{"data": .., "success": true}`, the errors are
+// `{"message": .., "success": false}` or a plain text for the unknown routes
+fn decode<T: serde::de::DeserializeOwned>(status: u16, body: &[u8]) -> Result<T, SdkError> {
+    if (200..300).contains(&status) {
+        return Ok(serde_json::from_slice::<auth_types::ApiSuccess<T>>(body)?.data);
+    }
+    let message = match serde_json::from_slice::<auth_types::ApiError>(body) {
+        Ok(error) => error.message,
+        Err(_) => String::from_utf8_lossy(body).into_owned(),
+    };
+    Err(SdkError::Api { status, message })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn decode_responses() {
+        let res: super::otp::VerifyOtpRes =
+            super::decode(200, br#"{"data": {"user_token": "jwt"}, "success": true}"#).unwrap();
+        assert_eq!(res.user_token, "jwt");
+
+        match super::decode::<super::user::Profile>(
+            401,
+            br#"{"message": "unauthorized", "success": false}"#,
+        ) {
+            Err(super::SdkError::Api { status, message }) => {
+                assert_eq!((status, message.as_str()), (401, "unauthorized"))
+            }
+            _ => panic!("expected the api error"),
+        }
+        assert!(matches!(
+            super::decode::<super::user::Profile>(404, b"Not Found"),
+            Err(super::SdkError::Api { status: 404, .. })
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CreateApiKeyReq {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    // never expires if not given, at most 3650
+    pub expires_in_days: Option<u32>,
+}
+
+// times are RFC 3339
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub active: bool,
+    pub created_on: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CreateApiKeyRes {
+    pub id: i64,
+    // Note: shown only this time
+    pub key: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RevokeApiKeyReq {
+    pub id: i64,
+}
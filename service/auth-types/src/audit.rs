@@ -0,0 +1,22 @@
+// times are RFC 3339
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub created_on: String,
+    pub event: String,
+    // `success` or `failure`
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub user_id: Option<i64>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct AuditEventsRes {
+    pub events: Vec<AuditEvent>,
+    // `before_id` of the next page, none on the last page
+    pub next_before_id: Option<i64>,
+}
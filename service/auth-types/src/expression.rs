@@ -0,0 +1,426 @@
+// Note: identity requirements as boolean expression over the identity predicates, accepted either as
+// json ast, `{"and": [{"key": "github-org-member", "value": "fastn-stack"}, {"or": [...]}]}`,
+// or as text, `org:fastn-stack AND (starred:a/b OR starred:a/c)`
+// Precedence is NOT > AND > OR, operators are case-insensitive
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    #[serde(untagged)]
+    Identity(crate::Identity),
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+#[serde(untagged)]
+pub enum Requirement {
+    Text(String),
+    Ast(Expr),
+}
+
+impl Requirement {
+    pub fn into_expr(self) -> Result<Expr, ExpressionError> {
+        match self {
+            Requirement::Text(text) => parse(text.as_str()),
+            Requirement::Ast(expr) => {
+                expr.check_size()?;
+                Ok(expr)
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ExpressionError {
+    #[error("UnexpectedEnd")]
+    UnexpectedEnd,
+    #[error("UnexpectedToken: {}", _0)]
+    UnexpectedToken(String),
+    #[error("InvalidPredicate: {}", _0)]
+    InvalidPredicate(String),
+    #[error("TooDeep: more than {} levels of nesting", MAX_DEPTH)]
+    TooDeep,
+    #[error("TooLong: more than {} tokens", MAX_TOKENS)]
+    TooLong,
+}
+
+// Note: the parser, the evaluation and the drop of `Expr` recurse once per level, the limits keep a
+// request from overflowing the stack. The json ast is deserialized within serde_json's own limit
+// of 128 levels before it is checked
+pub const MAX_DEPTH: usize = 32;
+pub const MAX_TOKENS: usize = 1000;
+
+// short names of the predicates accepted in the text expression
+const ALIASES: &[(&str, &str)] = &[
+    ("starred", "github-starred"),
+    ("org", "github-org-member"),
+    ("team", "github-team-member"),
+    ("collaborator", "github-repo-collaborator"),
+    ("sponsor", "github-sponsor"),
+    ("follower", "github-follower"),
+];
+
+impl Expr {
+    // Note: walks with its own stack instead of recursing, it is the check before the recursion
+    pub fn check_size(&self) -> Result<(), ExpressionError> {
+        let mut stack = vec![(self, 1)];
+        let mut nodes = 0;
+        while let Some((expr, depth)) = stack.pop() {
+            nodes += 1;
+            if nodes > MAX_TOKENS {
+                return Err(ExpressionError::TooLong);
+            }
+            if depth > MAX_DEPTH {
+                return Err(ExpressionError::TooDeep);
+            }
+            match expr {
+                Expr::And(exprs) | Expr::Or(exprs) => {
+                    stack.extend(exprs.iter().map(|e| (e, depth + 1)))
+                }
+                Expr::Not(expr) => stack.push((expr, depth + 1)),
+                Expr::Identity(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // all the distinct identities used in the expression, in order of appearance
+    pub fn identities(&self) -> Vec<&crate::Identity> {
+        fn walk<'a>(expr: &'a Expr, out: &mut Vec<&'a crate::Identity>) {
+            match expr {
+                Expr::And(exprs) | Expr::Or(exprs) => exprs.iter().for_each(|e| walk(e, out)),
+                Expr::Not(expr) => walk(expr, out),
+                Expr::Identity(identity) => {
+                    if !out.contains(&identity) {
+                        out.push(identity)
+                    }
+                }
+            }
+        }
+        let mut out = vec![];
+        walk(self, &mut out);
+        out
+    }
+
+    // Note: three valued evaluation, `None` is for the identities which could not be checked, the
+    // result is `None` only if the unknown identities can change it.
+    // Empty `and` is true and empty `or` is false
+    pub fn eval<F: Fn(&crate::Identity) -> Option<bool> + Copy>(&self, matched: F) -> Option<bool> {
+        self.eval_with(|identity| match matched(identity) {
+            Some(true) => Leaf::Matched,
+            Some(false) => Leaf::NotMatched,
+            None => Leaf::Unknown,
+        })
+    }
+
+    // same as `eval`, with the identities which can not be evaluated for the user at all
+    pub fn eval_with<F: Fn(&crate::Identity) -> Leaf + Copy>(&self, leaf: F) -> Option<bool> {
+        self.eval_in(false, leaf)
+    }
+
+    // `negated` is true under an odd number of NOTs
+    fn eval_in<F: Fn(&crate::Identity) -> Leaf + Copy>(
+        &self,
+        negated: bool,
+        leaf: F,
+    ) -> Option<bool> {
+        match self {
+            Expr::And(exprs) => {
+                let mut unknown = false;
+                for expr in exprs {
+                    match expr.eval_in(negated, leaf) {
+                        Some(false) => return Some(false),
+                        Some(true) => {}
+                        None => unknown = true,
+                    }
+                }
+                (!unknown).then_some(true)
+            }
+            Expr::Or(exprs) => {
+                let mut unknown = false;
+                for expr in exprs {
+                    match expr.eval_in(negated, leaf) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => unknown = true,
+                    }
+                }
+                (!unknown).then_some(false)
+            }
+            Expr::Not(expr) => expr.eval_in(!negated, leaf).map(|v| !v),
+            Expr::Identity(identity) => match leaf(identity) {
+                Leaf::Matched => Some(true),
+                Leaf::NotMatched => Some(false),
+                Leaf::Unknown => None,
+                // false once the NOTs above it are applied
+                Leaf::Unavailable => Some(negated),
+            },
+        }
+    }
+}
+
+// value of an identity for `Expr::eval_with`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Leaf {
+    Matched,
+    NotMatched,
+    // could not be checked now (provider outage), the result is unknown if it depends on it
+    Unknown,
+    // Note: can not be checked for the user at all (no linked account), it never satisfies the
+    // expression, neither as it is nor under NOT
+    Unavailable,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Predicate(String),
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut word = String::new();
+    let flush = |word: &mut String, tokens: &mut Vec<Token>| {
+        if word.is_empty() {
+            return;
+        }
+        tokens.push(match word.to_uppercase().as_str() {
+            "AND" => Token::And,
+            "OR" => Token::Or,
+            "NOT" => Token::Not,
+            _ => Token::Predicate(word.clone()),
+        });
+        word.clear();
+    };
+    for c in text.chars() {
+        match c {
+            '(' | ')' => {
+                flush(&mut word, &mut tokens);
+                tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut tokens),
+            c => word.push(c),
+        }
+    }
+    flush(&mut word, &mut tokens);
+    tokens
+}
+
+pub fn parse(text: &str) -> Result<Expr, ExpressionError> {
+    let tokens = tokenize(text);
+    if tokens.len() > MAX_TOKENS {
+        return Err(ExpressionError::TooLong);
+    }
+    let mut pos = 0;
+    let expr = parse_or(&tokens, &mut pos, 1)?;
+    match tokens.get(pos) {
+        None => Ok(expr),
+        Some(token) => Err(ExpressionError::UnexpectedToken(format!("{:?}", token))),
+    }
+}
+
+// `depth` is the nesting of the parentheses and NOTs, it is checked before going a level deeper
+fn parse_or(tokens: &[Token], pos: &mut usize, depth: usize) -> Result<Expr, ExpressionError> {
+    let mut exprs = vec![parse_and(tokens, pos, depth)?];
+    while tokens.get(*pos) == Some(&Token::Or) {
+        *pos += 1;
+        exprs.push(parse_and(tokens, pos, depth)?);
+    }
+    Ok(if exprs.len() == 1 {
+        exprs.remove(0)
+    } else {
+        Expr::Or(exprs)
+    })
+}
+
+fn parse_and(tokens: &[Token], pos: &mut usize, depth: usize) -> Result<Expr, ExpressionError> {
+    let mut exprs = vec![parse_not(tokens, pos, depth)?];
+    while tokens.get(*pos) == Some(&Token::And) {
+        *pos += 1;
+        exprs.push(parse_not(tokens, pos, depth)?);
+    }
+    Ok(if exprs.len() == 1 {
+        exprs.remove(0)
+    } else {
+        Expr::And(exprs)
+    })
+}
+
+fn parse_not(tokens: &[Token], pos: &mut usize, depth: usize) -> Result<Expr, ExpressionError> {
+    if depth > MAX_DEPTH {
+        return Err(ExpressionError::TooDeep);
+    }
+    match tokens.get(*pos) {
+        Some(Token::Not) => {
+            *pos += 1;
+            Ok(Expr::Not(Box::new(parse_not(tokens, pos, depth + 1)?)))
+        }
+        Some(Token::Open) => {
+            *pos += 1;
+            let expr = parse_or(tokens, pos, depth + 1)?;
+            match tokens.get(*pos) {
+                Some(Token::Close) => {
+                    *pos += 1;
+                    Ok(expr)
+                }
+                Some(token) => Err(ExpressionError::UnexpectedToken(format!("{:?}", token))),
+                None => Err(ExpressionError::UnexpectedEnd),
+            }
+        }
+        Some(Token::Predicate(predicate)) => {
+            *pos += 1;
+            Ok(Expr::Identity(parse_predicate(predicate)?))
+        }
+        Some(token) => Err(ExpressionError::UnexpectedToken(format!("{:?}", token))),
+        None => Err(ExpressionError::UnexpectedEnd),
+    }
+}
+
+fn parse_predicate(predicate: &str) -> Result<crate::Identity, ExpressionError> {
+    let (key, value) = match predicate.split_once(':') {
+        Some((key, value)) if !key.is_empty() && !value.is_empty() => (key, value),
+        _ => return Err(ExpressionError::InvalidPredicate(predicate.to_string())),
+    };
+    let key = ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq(&key))
+        .map(|(_, key)| *key)
+        .unwrap_or(key);
+    Ok(crate::Identity {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Expr, ExpressionError};
+
+    fn id(key: &str, value: &str) -> Expr {
+        Expr::Identity(crate::Identity {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_precedence() {
+        assert_eq!(
+            parse("org:fastn-stack AND (starred:a/b OR starred:a/c) or NOT follower:x").unwrap(),
+            Expr::Or(vec![
+                Expr::And(vec![
+                    id("github-org-member", "fastn-stack"),
+                    Expr::Or(vec![
+                        id("github-starred", "a/b"),
+                        id("github-starred", "a/c")
+                    ]),
+                ]),
+                Expr::Not(Box::new(id("github-follower", "x"))),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse("org:a AND"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(parse("(org:a"), Err(ExpressionError::UnexpectedEnd));
+        assert_eq!(
+            parse("org"),
+            Err(ExpressionError::InvalidPredicate("org".to_string()))
+        );
+        assert!(matches!(
+            parse("org:a org:b"),
+            Err(ExpressionError::UnexpectedToken(_))
+        ));
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let deep = format!("{}org:a{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_eq!(parse(deep.as_str()), Err(ExpressionError::TooLong));
+        let deep = format!("{}org:a{}", "(".repeat(40), ")".repeat(40));
+        assert_eq!(parse(deep.as_str()), Err(ExpressionError::TooDeep));
+        assert_eq!(
+            parse(format!("{}org:a", "NOT ".repeat(40)).as_str()),
+            Err(ExpressionError::TooDeep)
+        );
+        let nested = format!("{}org:a{}", "(".repeat(20), ")".repeat(20));
+        assert!(parse(nested.as_str()).is_ok());
+        let wide = vec!["org:a"; 600].join(" OR ");
+        assert_eq!(parse(wide.as_str()), Err(ExpressionError::TooLong));
+
+        let mut expr = id("github-org-member", "a");
+        for _ in 0..40 {
+            expr = Expr::Not(Box::new(expr));
+        }
+        assert_eq!(expr.check_size(), Err(ExpressionError::TooDeep));
+        let requirement = super::Requirement::Ast(expr);
+        assert_eq!(requirement.into_expr(), Err(ExpressionError::TooDeep));
+        let wide = Expr::Or(vec![id("github-org-member", "a"); 1000]);
+        assert_eq!(wide.check_size(), Err(ExpressionError::TooLong));
+        assert!(parse("NOT (org:a OR starred:a/b)")
+            .unwrap()
+            .check_size()
+            .is_ok());
+    }
+
+    #[test]
+    fn json_ast_and_eval() {
+        let expr: Expr = serde_json::from_str(
+            r#"{"and": [{"key": "github-org-member", "value": "fastn-stack"}, {"not": {"key": "github-starred", "value": "a/b"}}]}"#,
+        )
+        .unwrap();
+        assert_eq!(expr.identities().len(), 2);
+        assert_eq!(
+            expr.eval(|i| Some(i.key == "github-org-member")),
+            Some(true)
+        );
+        assert_eq!(expr.eval(|_| Some(true)), Some(false));
+    }
+
+    #[test]
+    fn eval_unknown() {
+        let expr = parse("org:a AND (starred:a/b OR starred:a/c)").unwrap();
+        let org_unknown = |i: &crate::Identity| (i.key != "github-org-member").then_some(true);
+        assert_eq!(expr.eval(org_unknown), None);
+        // org is not a member, unknown star can not change the result
+        let star_unknown = |i: &crate::Identity| match i.value.as_str() {
+            "a/b" => None,
+            _ => Some(i.key == "github-starred"),
+        };
+        assert_eq!(expr.eval(star_unknown), Some(false));
+        // other star is satisfied, unknown star can not change the result
+        let star_unknown = |i: &crate::Identity| match i.value.as_str() {
+            "a/b" => None,
+            _ => Some(true),
+        };
+        assert_eq!(expr.eval(star_unknown), Some(true));
+        assert_eq!(parse("NOT org:a").unwrap().eval(|_| None), None);
+    }
+
+    #[test]
+    fn unavailable_never_satisfies() {
+        let unavailable = |_: &crate::Identity| super::Leaf::Unavailable;
+        for text in [
+            "org:a",
+            "NOT org:a",
+            "NOT NOT org:a",
+            "NOT (org:a OR starred:a/b)",
+        ] {
+            assert_eq!(parse(text).unwrap().eval_with(unavailable), Some(false));
+        }
+        // only the other identity decides
+        let org_unavailable = |i: &crate::Identity| match i.key.as_str() {
+            "github-org-member" => super::Leaf::Unavailable,
+            _ => super::Leaf::NotMatched,
+        };
+        let expr = parse("NOT (org:a AND starred:a/b)").unwrap();
+        assert_eq!(expr.eval_with(org_unavailable), Some(true));
+    }
+}
@@ -0,0 +1,85 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Token {
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Identity {
+    pub key: String,
+    pub value: String,
+}
+
+impl std::fmt::Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.key, self.value)
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct GetIdsRequest {
+    // Note: only our session cookie is read from the tokens, provider tokens are looked up
+    // server side with the session's user
+    pub tokens: Vec<Token>,
+    // Note: identities are checked as OR, `expression` takes precedence over them if given
+    #[serde(default)]
+    pub identities: Vec<Identity>,
+    #[serde(default)]
+    pub expression: Option<crate::expression::Requirement>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct FailedClause {
+    pub clause: String,
+    pub reason: String,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GetIdsStatus {
+    Granted,
+    Denied,
+    // some identities could not be checked (provider outage, rate limits), and they decide the
+    // result, the calling service should not take it as denied
+    Undetermined,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct GetIdsResponse {
+    // list of expired tokens so that main service can clear them, a revoked or expired provider
+    // token is not one of them, it is reported as not linked and the session stays
+    pub expired_token_cookies: Vec<String>,
+    // result of the whole expression, same as `status` is granted
+    pub success: bool,
+    pub status: GetIdsStatus,
+    // identities of the expression which are satisfied by the user, as `key:value`
+    pub matched: Vec<String>,
+    // identities of the expression which are not satisfied, with the reason
+    pub failed: Vec<FailedClause>,
+    // identities of the expression which could not be checked, with the reason
+    pub undetermined: Vec<FailedClause>,
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn get_ids_request_round_trip() {
+        let req: super::GetIdsRequest = serde_json::from_value(serde_json::json!({
+            "tokens": [{"key": "auth-session", "value": "jwt"}],
+            "expression": "org:fastn-stack AND NOT starred:a/b",
+        }))
+        .unwrap();
+        let req: super::GetIdsRequest =
+            serde_json::from_str(serde_json::to_string(&req).unwrap().as_str()).unwrap();
+        assert!(req.identities.is_empty());
+        assert_eq!(
+            req.expression
+                .unwrap()
+                .into_expr()
+                .unwrap()
+                .identities()
+                .len(),
+            2
+        );
+    }
+}
@@ -0,0 +1,24 @@
+// Note: request and response types of the auth http api, shared by the server (`auth`) and the
+// client (`auth-sdk`) so that the two never drift
+pub mod api_key;
+pub mod audit;
+pub mod expression;
+pub mod identities;
+pub mod otp;
+pub mod user;
+
+pub use identities::Identity;
+
+// body of the successful responses, and of `get-identities` with 503
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ApiSuccess<T> {
+    pub data: T,
+    pub success: bool,
+}
+
+// body of the error responses
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ApiError {
+    pub message: String,
+    pub success: bool,
+}
@@ -0,0 +1,28 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SendOtpReq {
+    pub email: String,
+    pub phone: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SendOtpRes {
+    pub email: String,
+    pub message: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct VerifyOtpReq {
+    pub email: String,
+    pub phone: Option<String>,
+    pub otp: u32,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct VerifyOtpRes {
+    // session token, empty in the cookie session mode where it is only in the `auth-session` cookie
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub user_token: String,
+    // for the `X-CSRF-Token` header of the cookie session requests
+    #[serde(default)]
+    pub csrf_token: String,
+}
@@ -0,0 +1,37 @@
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    pub id: i64,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    // granted to the user, see `auth::authorization`
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct UpdateProfileReq {
+    pub name: String,
+}
+
+// active login session, or access token issued to an oidc client for the user
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub id: i64,
+    // none for the login sessions
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub created_on: String,
+    // the session of the request
+    pub current: bool,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CsrfRes {
+    pub csrf_token: String,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct RevokeSessionReq {
+    pub id: i64,
+}
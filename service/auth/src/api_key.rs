@@ -0,0 +1,227 @@
+// Note: personal api keys of the users for scripting against our apis, the key is shown only once
+// on create and only its sha256 is stored. Keys are sent as `Authorization: Bearer ak_...` or as
+// `X-Api-Key: ak_...`
+pub const KEY_PREFIX: &str = "ak_";
+pub const API_KEY_HEADER: &str = "x-api-key";
+// characters of the key kept to tell the keys apart in the list
+const DISPLAY_PREFIX_LEN: usize = 11;
+const MAX_KEYS_PER_USER: usize = 50;
+// keys live at most ten years
+const MAX_EXPIRES_IN_DAYS: u32 = 3650;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ApiKeyError {
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+    #[error("InvalidRequest: {}", _0)]
+    InvalidRequest(String),
+    #[error("NotFound")]
+    NotFound,
+}
+
+impl ApiKeyError {
+    // reason of the failed audit events
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            ApiKeyError::DB(_) => "db",
+            ApiKeyError::InvalidRequest(_) => "invalid_request",
+            ApiKeyError::NotFound => "not_found",
+        }
+    }
+}
+
+pub use auth_types::api_key::{ApiKey, CreateApiKeyReq, CreateApiKeyRes, RevokeApiKeyReq};
+
+fn api_key(key: db::api_key::ApiKeyDB) -> ApiKey {
+    ApiKey {
+        id: key.id,
+        name: key.name,
+        key_prefix: key.key_prefix,
+        scopes: key.scopes.split_whitespace().map(String::from).collect(),
+        expires_at: key.expires_at.map(|t| t.to_rfc3339()),
+        last_used_at: key.last_used_at.map(|t| t.to_rfc3339()),
+        active: key.active,
+        created_on: key.created_on.to_rfc3339(),
+    }
+}
+
+pub async fn create(
+    user_id: i64,
+    req: CreateApiKeyReq,
+    db_pool: &db::pg::DbPool,
+) -> Result<CreateApiKeyRes, ApiKeyError> {
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > 127 {
+        return Err(ApiKeyError::InvalidRequest(
+            "name is required, at most 127 characters".to_string(),
+        ));
+    }
+    if req
+        .scopes
+        .iter()
+        .any(|s| s.is_empty() || s.contains(char::is_whitespace))
+    {
+        return Err(ApiKeyError::InvalidRequest("invalid scope".to_string()));
+    }
+    let expires_at = expires_at(req.expires_in_days, chrono::Utc::now())?;
+    // Note: a key can not do more than its user
+    let permissions = crate::authorization::permissions(user_id, db_pool).await?;
+    if let Some(scope) = req.scopes.iter().find(|s| !permissions.has_scope(s)) {
+        return Err(ApiKeyError::InvalidRequest(format!(
+            "scope {} is not granted to the user",
+            scope
+        )));
+    }
+    let scopes = req.scopes.join(" ");
+    if scopes.len() > 255 {
+        return Err(ApiKeyError::InvalidRequest("too many scopes".to_string()));
+    }
+    let active_keys = db::api_key::list(user_id, db_pool)
+        .await?
+        .iter()
+        .filter(|k| k.active)
+        .count();
+    if active_keys >= MAX_KEYS_PER_USER {
+        return Err(ApiKeyError::InvalidRequest(format!(
+            "at most {} active keys are allowed, revoke the unused keys",
+            MAX_KEYS_PER_USER
+        )));
+    }
+
+    let key = format!("{}{}", KEY_PREFIX, crate::crypto::random_token(32));
+    let key_prefix = &key[..DISPLAY_PREFIX_LEN];
+    let id = db::api_key::create(
+        db::api_key::NewApiKey {
+            user_id,
+            name: name.to_string(),
+            key_prefix: key_prefix.to_string(),
+            key_hash: crate::crypto::hash(key.as_str()),
+            scopes,
+            expires_at,
+        },
+        db_pool,
+    )
+    .await?;
+    tracing::info!(message = "api key created", user_id = user_id, key_id = id);
+    Ok(CreateApiKeyRes {
+        id,
+        name: name.to_string(),
+        key_prefix: key_prefix.to_string(),
+        key,
+        scopes: req.scopes,
+        expires_at: expires_at.map(|t| t.to_rfc3339()),
+    })
+}
+
+// Note: `None` is a key that never expires
+fn expires_at(
+    expires_in_days: Option<u32>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, ApiKeyError> {
+    let days = match expires_in_days {
+        Some(days) => days,
+        None => return Ok(None),
+    };
+    if !(1..=MAX_EXPIRES_IN_DAYS).contains(&days) {
+        return Err(ApiKeyError::InvalidRequest(format!(
+            "expires_in_days must be from 1 to {}",
+            MAX_EXPIRES_IN_DAYS
+        )));
+    }
+    now.checked_add_signed(chrono::Duration::days(days as i64))
+        .map(Some)
+        .ok_or_else(|| ApiKeyError::InvalidRequest("expires_in_days is too large".to_string()))
+}
+
+pub async fn list(user_id: i64, db_pool: &db::pg::DbPool) -> Result<Vec<ApiKey>, ApiKeyError> {
+    Ok(db::api_key::list(user_id, db_pool)
+        .await?
+        .into_iter()
+        .map(api_key)
+        .collect())
+}
+
+pub async fn revoke(
+    user_id: i64,
+    req: RevokeApiKeyReq,
+    db_pool: &db::pg::DbPool,
+) -> Result<(), ApiKeyError> {
+    if !db::api_key::revoke(req.id, user_id, db_pool).await? {
+        return Err(ApiKeyError::NotFound);
+    }
+    tracing::info!(
+        message = "api key revoked",
+        user_id = user_id,
+        key_id = req.id
+    );
+    Ok(())
+}
+
+// api key from the `X-Api-Key` header, or from the bearer token if it is an api key
+pub fn from_headers(headers: &hyper::HeaderMap<hyper::header::HeaderValue>) -> Option<String> {
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .or_else(|| {
+            headers
+                .get(hyper::header::AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .filter(|token| token.starts_with(KEY_PREFIX))
+                .map(|token| token.trim().to_string())
+        })
+}
+
+// Note: active and not expired key, last used time of the key is tracked
+pub async fn verify(
+    key: &str,
+    db_pool: &db::pg::DbPool,
+) -> Result<Option<db::api_key::ApiKeyDB>, ApiKeyError> {
+    if !key.starts_with(KEY_PREFIX) {
+        return Ok(None);
+    }
+    Ok(db::api_key::verify(crate::crypto::hash(key).as_str(), db_pool).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn api_key_from_headers() {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(
+            hyper::header::AUTHORIZATION,
+            hyper::header::HeaderValue::from_static("Bearer eyJhbGciOi"),
+        );
+        assert_eq!(super::from_headers(&headers), None);
+        headers.insert(
+            hyper::header::AUTHORIZATION,
+            hyper::header::HeaderValue::from_static("Bearer ak_abc"),
+        );
+        assert_eq!(super::from_headers(&headers).as_deref(), Some("ak_abc"));
+        headers.insert(
+            super::API_KEY_HEADER,
+            hyper::header::HeaderValue::from_static("ak_xyz"),
+        );
+        assert_eq!(super::from_headers(&headers).as_deref(), Some("ak_xyz"));
+    }
+
+    #[test]
+    fn expiry_of_keys() {
+        let now = chrono::Utc::now();
+        assert!(super::expires_at(None, now).unwrap().is_none());
+        assert_eq!(
+            super::expires_at(Some(90), now).unwrap(),
+            Some(now + chrono::Duration::days(90))
+        );
+        assert!(super::expires_at(Some(3650), now).unwrap().is_some());
+        for days in [0, 3651, u32::MAX] {
+            assert!(matches!(
+                super::expires_at(Some(days), now),
+                Err(super::ApiKeyError::InvalidRequest(_))
+            ));
+        }
+        // an error instead of the overflow at the end of the calendar
+        assert!(super::expires_at(Some(3650), chrono::DateTime::<chrono::Utc>::MAX_UTC).is_err());
+    }
+}
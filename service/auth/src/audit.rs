@@ -0,0 +1,396 @@
+// Note: security audit log of the authentication events, who logged in as a user, from where and
+// when. Events are appended to `authapp_audit_event` (the table rejects the updates and the
+// deletes) with the client of the request. A failed write is logged, the request is not failed
+// for it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    OtpSend,
+    OtpResend,
+    OtpVerify,
+    GithubLogin,
+    GithubUnlink,
+    Logout,
+    SessionRevoke,
+    ApiKeyRevoke,
+    OauthRevoke,
+    ProfileUpdate,
+}
+
+impl Event {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::OtpSend => "otp-send",
+            Event::OtpResend => "otp-resend",
+            Event::OtpVerify => "otp-verify",
+            Event::GithubLogin => "github-login",
+            Event::GithubUnlink => "github-unlink",
+            Event::Logout => "logout",
+            Event::SessionRevoke => "session-revoke",
+            Event::ApiKeyRevoke => "api-key-revoke",
+            Event::OauthRevoke => "oauth-revoke",
+            Event::ProfileUpdate => "profile-update",
+        }
+    }
+}
+
+// Note: failures are recorded with a short reason, never the error message, it can have the
+// emails and the tokens
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome<'a> {
+    Success,
+    Failure(&'a str),
+}
+
+// client of the request, taken before the body is read
+#[derive(Clone, Debug, Default)]
+pub struct Client {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl Client {
+    pub fn from_request<B>(req: &hyper::Request<B>) -> Client {
+        let header = |name: hyper::header::HeaderName, max: usize| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.chars().take(max).collect::<String>())
+        };
+        Client {
+            ip: client_ip(
+                req.extensions()
+                    .get::<router::RemoteAddr>()
+                    .map(|remote| remote.0.ip()),
+                req.headers(),
+                TRUSTED_PROXIES.as_slice(),
+            )
+            .map(|ip| ip.to_string()),
+            user_agent: header(hyper::header::USER_AGENT, 512),
+            request_id: req
+                .extensions()
+                .get::<router::RequestId>()
+                .map(|id| id.0.clone()),
+        }
+    }
+}
+
+// Note: comma separated CIDRs of the reverse proxies in env `TRUSTED_PROXIES`, e.g.
+// `10.0.0.0/8,fd00::/8`, none by default. The service does not start with an invalid one
+static TRUSTED_PROXIES: once_cell::sync::Lazy<Vec<ipnet::IpNet>> =
+    once_cell::sync::Lazy::new(|| match std::env::var("TRUSTED_PROXIES") {
+        Ok(proxies) => parse_proxies(proxies.as_str()).unwrap_or_else(|e| panic!("{}", e)),
+        Err(_) => vec![],
+    });
+
+fn parse_proxies(proxies: &str) -> Result<Vec<ipnet::IpNet>, String> {
+    proxies
+        .split(',')
+        .map(|proxy| proxy.trim())
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| {
+            // Note: a single address is the network of it alone
+            proxy
+                .parse::<ipnet::IpNet>()
+                .or_else(|_| proxy.parse::<std::net::IpAddr>().map(ipnet::IpNet::from))
+                .map_err(|_| format!("TRUSTED_PROXIES has an invalid CIDR {}", proxy))
+        })
+        .collect()
+}
+
+// Note: called at the startup, see `TRUSTED_PROXIES`
+pub fn check_trusted_proxies() -> Result<(), String> {
+    match std::env::var("TRUSTED_PROXIES") {
+        Ok(proxies) => parse_proxies(proxies.as_str()).map(|_| ()),
+        Err(_) => Ok(()),
+    }
+}
+
+// Note: the peer is the client unless it is a trusted proxy, then the client is the last address of
+// `X-Forwarded-For` which is not a trusted proxy (the ones before it are sent by the client and can
+// be anything). IPv4 peers mapped into IPv6 are matched as IPv4
+fn client_ip(
+    remote: Option<std::net::IpAddr>,
+    headers: &hyper::HeaderMap,
+    trusted: &[ipnet::IpNet],
+) -> Option<std::net::IpAddr> {
+    let is_trusted = |ip: &std::net::IpAddr| {
+        let ip = ip.to_canonical();
+        trusted.iter().any(|net| net.contains(&ip))
+    };
+    let remote = remote.map(|ip| ip.to_canonical());
+    if !remote.as_ref().is_some_and(is_trusted) {
+        return remote;
+    }
+    let forwarded: Vec<Option<std::net::IpAddr>> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|ip| ip.trim().parse::<std::net::IpAddr>().ok())
+        .collect();
+    // Note: walked from the proxy, an address which is not an ip ends it at the last proxy
+    let mut client = remote;
+    for ip in forwarded.into_iter().rev() {
+        match ip.map(|ip| ip.to_canonical()) {
+            Some(ip) if is_trusted(&ip) => client = Some(ip),
+            Some(ip) => return Some(ip),
+            None => break,
+        }
+    }
+    client
+}
+
+pub struct Record<'a> {
+    pub event: Event,
+    pub outcome: Outcome<'a>,
+    pub user_id: Option<i64>,
+    pub email: Option<&'a str>,
+}
+
+pub async fn record(record: Record<'_>, client: &Client, db_pool: &db::pg::DbPool) {
+    let (outcome, reason) = match record.outcome {
+        Outcome::Success => ("success", None),
+        Outcome::Failure(reason) => ("failure", Some(reason.to_string())),
+    };
+    let result = db::audit::insert(
+        db::audit::NewAuditEvent {
+            event: record.event.as_str().to_string(),
+            outcome: outcome.to_string(),
+            reason,
+            user_id: record.user_id,
+            email: record.email.map(String::from),
+            ip: client.ip.clone(),
+            user_agent: client.user_agent.clone(),
+            request_id: client.request_id.clone(),
+        },
+        db_pool,
+    )
+    .await;
+    if let Err(e) = result {
+        tracing::error!(
+            message = "audit event is not recorded",
+            event = record.event.as_str(),
+            error = e.to_string()
+        );
+    }
+}
+
+// `Success` if the result is ok, otherwise `Failure` with the reason of the error
+pub fn outcome<T, E>(
+    result: &Result<T, E>,
+    reason: impl Fn(&E) -> &'static str,
+) -> Outcome<'static> {
+    match result {
+        Ok(_) => Outcome::Success,
+        Err(err) => Outcome::Failure(reason(err)),
+    }
+}
+
+// Note: the admin query of the events, `audit:read` scope is granted with a role in the django
+// admin
+pub const AUDIT_SCOPE: &str = "audit:read";
+const MAX_LIMIT: i64 = 100;
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuditError {
+    #[error("InvalidRequest: {}", _0)]
+    InvalidRequest(String),
+    #[error("Forbidden: {}", _0)]
+    Forbidden(#[from] crate::authorization::AuthorizationError),
+    #[error("DBError: {}", _0)]
+    DBError(#[from] db::DBError),
+}
+
+// events of the query string for the user, who must have the `AUDIT_SCOPE`
+pub async fn list(
+    user_id: i64,
+    query: &str,
+    db_pool: &db::pg::DbPool,
+) -> Result<auth_types::audit::AuditEventsRes, AuditError> {
+    if !crate::authorization::permissions(user_id, db_pool)
+        .await?
+        .has_scope(AUDIT_SCOPE)
+    {
+        return Err(crate::authorization::AuthorizationError::MissingScope(
+            AUDIT_SCOPE.to_string(),
+        )
+        .into());
+    }
+    let (filter, limit) = parse_query(query)?;
+    let events = db::audit::list(filter, limit, db_pool).await?;
+    // Note: a full page may have more events, the next page is the events before its last one
+    let next_before_id = match events.len() as i64 == limit {
+        true => events.last().map(|event| event.id),
+        false => None,
+    };
+    Ok(auth_types::audit::AuditEventsRes {
+        events: events
+            .into_iter()
+            .map(|event| auth_types::audit::AuditEvent {
+                id: event.id,
+                created_on: event.created_on.to_rfc3339(),
+                event: event.event,
+                outcome: event.outcome,
+                reason: event.reason,
+                user_id: event.user_id,
+                email: event.email,
+                ip: event.ip,
+                user_agent: event.user_agent,
+                request_id: event.request_id,
+            })
+            .collect(),
+        next_before_id,
+    })
+}
+
+// filter and the page size of the query string, the empty params are left out
+fn parse_query(query: &str) -> Result<(db::audit::AuditFilter, i64), AuditError> {
+    let query: std::collections::HashMap<String, String> =
+        url::form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .filter(|(_, value)| !value.is_empty())
+            .collect();
+    let get = |name: &str| query.get(name).cloned();
+    let number = |name: &str| -> Result<Option<i64>, AuditError> {
+        get(name)
+            .map(|value| {
+                value
+                    .parse::<i64>()
+                    .map_err(|_| AuditError::InvalidRequest(format!("{} must be a number", name)))
+            })
+            .transpose()
+    };
+    let time = |name: &str| -> Result<Option<chrono::DateTime<chrono::Utc>>, AuditError> {
+        get(name)
+            .map(|value| {
+                chrono::DateTime::parse_from_rfc3339(value.as_str())
+                    .map(|time| time.with_timezone(&chrono::Utc))
+                    .map_err(|_| {
+                        AuditError::InvalidRequest(format!("{} must be a RFC 3339 time", name))
+                    })
+            })
+            .transpose()
+    };
+    let limit = number("limit")?.unwrap_or(50);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AuditError::InvalidRequest(format!(
+            "limit must be from 1 to {}",
+            MAX_LIMIT
+        )));
+    }
+    Ok((
+        db::audit::AuditFilter {
+            user_id: number("user_id")?,
+            email: get("email"),
+            event: get("event"),
+            outcome: get("outcome"),
+            ip: get("ip"),
+            since: time("since")?,
+            until: time("until")?,
+            before_id: number("before_id")?,
+        },
+        limit,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn query_of_events() {
+        let (filter, limit) = super::parse_query(
+            "user_id=42&email=a%40example.com&event=otp-verify&outcome=&since=2026-10-01T00:00:00Z&before_id=9",
+        )
+        .unwrap();
+        assert_eq!(limit, 50);
+        assert_eq!(filter.user_id, Some(42));
+        assert_eq!(filter.email.as_deref(), Some("a@example.com"));
+        assert_eq!(filter.event.as_deref(), Some("otp-verify"));
+        // empty params are not filtered on
+        assert_eq!(filter.outcome, None);
+        assert_eq!(
+            filter.since.map(|since| since.to_rfc3339()).as_deref(),
+            Some("2026-10-01T00:00:00+00:00")
+        );
+        assert_eq!(filter.until, None);
+        assert_eq!(filter.before_id, Some(9));
+
+        for query in [
+            "user_id=me",
+            "before_id=1.5",
+            "since=yesterday",
+            "until=2026-10-01",
+        ] {
+            assert!(matches!(
+                super::parse_query(query),
+                Err(super::AuditError::InvalidRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn limit_of_events() {
+        for (query, limit) in [("limit=1", 1), ("limit=100", 100), ("", 50)] {
+            assert_eq!(super::parse_query(query).unwrap().1, limit);
+        }
+        for query in ["limit=0", "limit=101", "limit=-1", "limit=ten"] {
+            assert!(matches!(
+                super::parse_query(query),
+                Err(super::AuditError::InvalidRequest(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn client_ip_of_proxy() {
+        let trusted = super::parse_proxies("10.0.0.0/8, fd00::/8, 192.0.2.1").unwrap();
+        let mut headers = hyper::HeaderMap::new();
+        headers.append("x-forwarded-for", "1.1.1.1, 10.0.0.9".parse().unwrap());
+        headers.append("x-forwarded-for", "203.0.113.7".parse().unwrap());
+
+        // the proxy on the private network appended the client
+        let proxy = Some("10.0.0.2".parse().unwrap());
+        assert_eq!(
+            super::client_ip(proxy, &headers, &trusted),
+            Some("203.0.113.7".parse().unwrap())
+        );
+        // a public peer is the client, its forwarded header is not trusted
+        let client = Some("198.51.100.4".parse().unwrap());
+        assert_eq!(super::client_ip(client, &headers, &trusted), client);
+        assert_eq!(
+            super::client_ip(proxy, &hyper::HeaderMap::new(), &trusted),
+            proxy
+        );
+        // no proxy is trusted by default, not even the private networks
+        assert_eq!(super::client_ip(proxy, &headers, &[]), proxy);
+    }
+
+    #[test]
+    fn client_ip_behind_proxies_of_ipv6() {
+        let trusted = super::parse_proxies("10.0.0.0/8,fd00::/8").unwrap();
+        let mut headers = hyper::HeaderMap::new();
+        // the client, then the edge proxy of the unique local network which the last one trusts
+        headers.append("x-forwarded-for", "2001:db8::7, fd00::2".parse().unwrap());
+        assert_eq!(
+            super::client_ip(Some("fd00::1".parse().unwrap()), &headers, &trusted),
+            Some("2001:db8::7".parse().unwrap())
+        );
+        // ipv4 proxy seen as mapped into ipv6
+        assert_eq!(
+            super::client_ip(Some("::ffff:10.0.0.2".parse().unwrap()), &headers, &trusted),
+            Some("2001:db8::7".parse().unwrap())
+        );
+        // the ula outside the trusted network is the client
+        let other = Some("fc00::1".parse().unwrap());
+        assert_eq!(super::client_ip(other, &headers, &trusted), other);
+        assert!(super::parse_proxies("10.0.0.0/33").is_err());
+
+        // a forged entry of the client does not hide the one the proxy appended
+        let mut headers = hyper::HeaderMap::new();
+        headers.append("x-forwarded-for", "forged, 203.0.113.7".parse().unwrap());
+        assert_eq!(
+            super::client_ip(Some("10.0.0.2".parse().unwrap()), &headers, &trusted),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+}
@@ -0,0 +1,91 @@
+// Note: verification of the caller of our apis, used by the resource handlers like `ai::apis`.
+// Callers send the user's jwt, the client credentials jwt or the user's api key
+#[derive(thiserror::Error, Debug)]
+pub enum AuthenticateError {
+    #[error("JWTError: {}", _0)]
+    Jwt(#[from] crate::jwt::JWTError),
+    #[error("InvalidApiKey")]
+    InvalidApiKey,
+    #[error("RevokedToken")]
+    RevokedToken,
+    #[error("InactiveClient")]
+    InactiveClient,
+    #[error("ApiKeyError: {}", _0)]
+    ApiKey(#[from] crate::api_key::ApiKeyError),
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+}
+
+impl AuthenticateError {
+    // Note: false for the server errors, the caller is not to be blamed for them
+    pub fn is_unauthorized(&self) -> bool {
+        !matches!(
+            self,
+            AuthenticateError::ApiKey(_) | AuthenticateError::DB(_)
+        )
+    }
+}
+
+// Note: roles and scopes of the jwt callers are the ones in their tokens. Api keys carry no roles
+// and their scopes are checked against the user's current scopes, so the revoked grants are
+// revoked for the keys right away. Tokens of the users are recorded in `authapp_user_token` and must
+// be active, the logged out and the revoked ones are rejected
+pub async fn authenticate(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+    db_pool: &db::pg::DbPool,
+) -> Result<crate::authorization::Caller, AuthenticateError> {
+    if let Some(key) = crate::api_key::from_headers(headers) {
+        let key = crate::api_key::verify(key.as_str(), db_pool)
+            .await?
+            .ok_or(AuthenticateError::InvalidApiKey)?;
+        let permissions = crate::authorization::permissions(key.user_id, db_pool).await?;
+        return Ok(crate::authorization::Caller {
+            scopes: key
+                .scopes
+                .split_whitespace()
+                .filter(|s| permissions.has_scope(s))
+                .map(String::from)
+                .collect(),
+            roles: vec![],
+            principal: crate::jwt::Principal::ApiKey {
+                user_id: key.user_id,
+                key_id: key.id,
+                scope: key.scopes,
+            },
+        });
+    }
+    let token = crate::jwt::token_from_headers(headers)?;
+    let caller =
+        crate::authorization::Caller::from_claims(crate::jwt::decode_claims(token.as_str())?)?;
+    let token = token.strip_prefix("Bearer ").unwrap_or(token.as_str());
+    check_active(&caller.principal, token, db_pool).await?;
+    Ok(caller)
+}
+
+// Note: for the jwts verified without the database as well, e.g. by the auth client. Tokens of the
+// users are recorded, the logged out and the revoked ones are rejected. Tokens of the machine
+// clients are not recorded, they are valid while the client is active, like `oidc::introspect`
+pub async fn check_active(
+    principal: &crate::jwt::Principal,
+    token: &str,
+    db_pool: &db::pg::DbPool,
+) -> Result<(), AuthenticateError> {
+    match principal {
+        crate::jwt::Principal::User(_) | crate::jwt::Principal::Delegated { .. } => {
+            if !db::user::is_token_active(token, db_pool).await? {
+                return Err(AuthenticateError::RevokedToken);
+            }
+        }
+        crate::jwt::Principal::Client(client_id) => {
+            if db::machine_client::get(client_id.as_str(), db_pool)
+                .await?
+                .is_none()
+            {
+                return Err(AuthenticateError::InactiveClient);
+            }
+        }
+        // Note: api keys are not jwts, they are checked with the database by `authenticate`
+        crate::jwt::Principal::ApiKey { .. } => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+// Note: roles and scopes of the users are granted in the django admin, a user has the scopes of
+// its roles and the scopes granted directly. They are put in the issued tokens, so the changes
+// reach the session tokens on the next login, api keys see them on every request
+use crate::jwt::Principal;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum AuthorizationError {
+    #[error("scope `{}` is required", _0)]
+    MissingScope(String),
+    #[error("role `{}` is required", _0)]
+    MissingRole(String),
+}
+
+impl AuthorizationError {
+    pub fn response(&self) -> hyper::Response<Vec<u8>> {
+        crate::controller::response(
+            serde_json::json!({"message": self.to_string(), "success": false}).to_string(),
+            hyper::StatusCode::FORBIDDEN,
+        )
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Permissions {
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+impl Permissions {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s.eq(scope))
+    }
+}
+
+pub async fn permissions(
+    user_id: i64,
+    db_pool: &db::pg::DbPool,
+) -> Result<Permissions, db::DBError> {
+    let roles = db::role::user_roles(user_id, db_pool).await?;
+    let mut scopes = db::role::user_scopes(user_id, db_pool).await?;
+    scopes.extend(
+        roles
+            .iter()
+            .flat_map(|(_, scopes)| scopes.split_whitespace().map(String::from)),
+    );
+    scopes.sort();
+    scopes.dedup();
+    Ok(Permissions {
+        roles: roles.into_iter().map(|(name, _)| name).collect(),
+        scopes,
+    })
+}
+
+// authenticated caller of the api with what it is allowed to do
+#[derive(Debug, Clone, PartialEq)]
+pub struct Caller {
+    pub principal: Principal,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+impl Caller {
+    pub fn from_claims(claims: crate::jwt::Claims) -> Result<Caller, crate::jwt::JWTError> {
+        Ok(Caller {
+            principal: claims.principal()?,
+            scopes: claims
+                .scope
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .map(String::from)
+                .collect(),
+            roles: claims.roles,
+        })
+    }
+
+    pub fn require(&self, scope: &str) -> Result<(), AuthorizationError> {
+        if self.scopes.iter().any(|s| s.eq(scope)) {
+            return Ok(());
+        }
+        Err(AuthorizationError::MissingScope(scope.to_string()))
+    }
+
+    pub fn require_role(&self, role: &str) -> Result<(), AuthorizationError> {
+        if self.roles.iter().any(|r| r.eq(role)) {
+            return Ok(());
+        }
+        Err(AuthorizationError::MissingRole(role.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn require_scope_and_role() {
+        let token = crate::jwt::create_jwt(
+            "42".to_string(),
+            &super::Permissions {
+                roles: vec!["admin".to_string()],
+                scopes: vec!["ai:use".to_string(), "reports:read".to_string()],
+            },
+        )
+        .unwrap();
+        let caller =
+            super::Caller::from_claims(crate::jwt::decode_claims(token.as_str()).unwrap()).unwrap();
+        assert_eq!(caller.principal, crate::jwt::Principal::User(42));
+        assert!(caller.require("ai:use").is_ok());
+        assert!(caller.require_role("admin").is_ok());
+        assert_eq!(
+            caller.require("ai"),
+            Err(super::AuthorizationError::MissingScope("ai".to_string()))
+        );
+        assert_eq!(
+            caller.require_role("support"),
+            Err(super::AuthorizationError::MissingRole(
+                "support".to_string()
+            ))
+        );
+
+        // Note: users without any grant get the tokens without roles and scope
+        let token = crate::jwt::create_jwt("7".to_string(), &Default::default()).unwrap();
+        let caller =
+            super::Caller::from_claims(crate::jwt::decode_claims(token.as_str()).unwrap()).unwrap();
+        assert!(caller.roles.is_empty() && caller.scopes.is_empty());
+        assert_eq!(
+            super::AuthorizationError::MissingScope("ai:use".to_string())
+                .response()
+                .status(),
+            hyper::StatusCode::FORBIDDEN
+        );
+    }
+}
@@ -28,6 +28,13 @@ pub struct SendEmailReq {
     pub tags: Vec<String>,
 }
 
+// Note: only the message id of the answer is kept, the rest may echo the recipients
+#[derive(serde::Deserialize)]
+pub struct SendEmailRes {
+    #[serde(rename = "messageId")]
+    pub message_id: Option<String>,
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum SendMailError {
     #[error("InvalidHeaderValueError: {}", _0)]
@@ -38,6 +45,18 @@ pub enum SendMailError {
     Serde(#[from] serde_json::Error),
 }
 
+// Note: readiness check of the email provider config, the api key is read from the env instead of
+// `BREVO_API_KEY` so that a missing key is reported instead of panicking
+pub fn check_config() -> Result<(), String> {
+    match std::env::var("BREVO_API_KEY") {
+        Ok(key) if key.trim().is_empty() => Err("BREVO_API_KEY is empty".to_string()),
+        Ok(key) => hyper::header::HeaderValue::try_from(key)
+            .map(|_| ())
+            .map_err(|e| format!("BREVO_API_KEY is not a valid header value: {}", e)),
+        Err(e) => Err(format!("BREVO_API_KEY not found in env {}", e)),
+    }
+}
+
 pub async fn send_email(otp: u32, to_email: &str) -> Result<(), SendMailError> {
     let mut headers = hyper::HeaderMap::new();
     headers.insert(
@@ -75,21 +94,41 @@ pub async fn send_email(otp: u32, to_email: &str) -> Result<(), SendMailError> {
     };
 
     let client = reqwest::Client::new();
-    let payload = serde_json::to_string(&request)?;
-    println!("payload: {}", payload);
-    let response = client
+    let started = std::time::Instant::now();
+    let response = match client
         .post("https://api.brevo.com/v3/smtp/email")
         .headers(headers)
         .json(&request)
         .send()
-        .await?;
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            crate::metrics::email(started.elapsed(), Some("request"));
+            return Err(e.into());
+        }
+    };
 
     let status = response.status();
-    let body = response.text().await?;
+    crate::metrics::email(
+        started.elapsed(),
+        match status.is_success() {
+            true => None,
+            false => Some("status"),
+        },
+    );
     if status.is_success() {
-        tracing::info!("Send Email Response Success Body: {}", body);
+        let message_id = match response.json::<SendEmailRes>().await {
+            Ok(res) => res.message_id,
+            Err(_) => None,
+        };
+        tracing::info!(
+            message = "email sent",
+            status = status.as_u16(),
+            message_id = message_id.as_deref().unwrap_or("")
+        );
     } else {
-        tracing::error!("Send Email Error: status: {}, body: {}", status, body);
+        tracing::error!(message = "email send failed", status = status.as_u16());
     }
 
     Ok(())
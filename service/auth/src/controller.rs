@@ -1,14 +1,76 @@
-use http_body_util::BodyExt;
 use hyper::body::Incoming;
 
+// Note: browser apps on the other origins calling the `/v1/api/auth/` routes, comma separated
+// origins or `*`, there is no CORS if it is not set. Credentials are needed for the session cookie.
+// The service does not start with an invalid config, see `check_cors`
+static CORS: once_cell::sync::Lazy<router::Cors> = once_cell::sync::Lazy::new(|| {
+    cors(|name| std::env::var(name).ok()).unwrap_or_else(|e| panic!("{}", e))
+});
+
+// Note: called at the startup, see `CORS`
+pub fn check_cors() -> Result<(), String> {
+    cors(|name| std::env::var(name).ok()).map(|_| ())
+}
+
+fn cors(var: impl Fn(&str) -> Option<String>) -> Result<router::Cors, String> {
+    let origins = match var("CORS_ALLOWED_ORIGINS") {
+        Some(val) => val
+            .split(',')
+            .map(|origin| origin.trim().trim_end_matches('/'))
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| match origin == "*" || is_origin(origin) {
+                true => Ok(origin.to_string()),
+                false => Err(format!(
+                    "CORS_ALLOWED_ORIGINS has an invalid origin {}",
+                    origin
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => vec![],
+    };
+    let credentials = match var("CORS_ALLOW_CREDENTIALS") {
+        Some(val) => match val.to_lowercase().as_str() {
+            "true" => true,
+            "false" => false,
+            _ => {
+                return Err(format!(
+                    "CORS_ALLOW_CREDENTIALS must be true or false, found {}",
+                    val
+                ))
+            }
+        },
+        None => false,
+    };
+    let max_age = match var("CORS_MAX_AGE_SECS") {
+        Some(val) => val
+            .trim()
+            .parse()
+            .map_err(|_| format!("CORS_MAX_AGE_SECS is not a number, found {}", val))?,
+        None => 10 * 60,
+    };
+    Ok(router::Cors::new(origins)
+        .allow_credentials(credentials)
+        .max_age(std::time::Duration::from_secs(max_age)))
+}
+
+// `scheme://host[:port]` as the browsers send it in `Origin`
+fn is_origin(origin: &str) -> bool {
+    match url::Url::parse(origin) {
+        Ok(url) => {
+            matches!(url.scheme(), "http" | "https")
+                && url.host_str().is_some()
+                && url.origin().ascii_serialization() == origin
+        }
+        Err(_) => false,
+    }
+}
+
+// Note: the body is read up to the body limit of the route, see `router::body`
 async fn from_body<T: serde::de::DeserializeOwned>(
-    req: Incoming,
+    req: hyper::Request<Incoming>,
 ) -> Result<T, crate::error::AuthError> {
-    let collected_body = req
-        .collect()
-        .await
-        .map_err(|e| crate::error::AuthError::ReadBody(format!("{e}")))?
-        .to_bytes();
+    let (parts, body) = req.into_parts();
+    let collected_body = router::body(&parts.extensions, body).await?;
 
     Ok(serde_json::from_slice(&collected_body)?)
 }
@@ -16,19 +78,21 @@ async fn from_body<T: serde::de::DeserializeOwned>(
 fn success(
     data: impl serde::Serialize,
 ) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
-    #[derive(serde::Serialize)]
-    struct ApiSuccess<T: serde::Serialize> {
-        data: T,
-        success: bool,
-    }
+    respond(data, hyper::StatusCode::OK)
+}
 
-    let resp = serde_json::to_vec(&ApiSuccess {
+// Note: `success` is false for the non 2xx status, data is sent as it is
+fn respond(
+    data: impl serde::Serialize,
+    status: hyper::StatusCode,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let resp = serde_json::to_vec(&auth_types::ApiSuccess {
         data,
-        success: true,
+        success: status.is_success(),
     })?;
 
     let mut response = hyper::Response::new(resp);
-    *response.status_mut() = hyper::StatusCode::OK;
+    *response.status_mut() = status;
     response.headers_mut().append(
         hyper::header::CONTENT_TYPE,
         hyper::http::HeaderValue::from_str("application/json").unwrap(), // TODO: Remove unwrap
@@ -40,13 +104,7 @@ fn error(
     message: String,
     status: hyper::StatusCode,
 ) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
-    #[derive(serde::Serialize)]
-    struct ApiError {
-        message: String,
-        success: bool,
-    }
-
-    let resp = serde_json::to_vec(&ApiError {
+    let resp = serde_json::to_vec(&auth_types::ApiError {
         success: false,
         message,
     })?;
@@ -55,92 +113,497 @@ fn error(
     Ok(response)
 }
 
-pub async fn api_handler(
+// Note: the `/v1/api/auth/` routes of the session are behind `require_session`, other services
+// call `/auth/verify` and `/auth/get-identities/`, the oidc provider registers its own routes
+pub fn register(router: &mut router::Router<db::pg::DbPool>) {
+    // Note: otp routes are public, their bodies are an email or a phone and the otp
+    router
+        .group("/v1/api/auth")
+        .cors(CORS.clone())
+        .body_limit(4 * 1024)
+        .post("/send-otp/", send_otp)
+        .post("/resend-otp/", resend_otp)
+        .post("/verify-otp/", verify_otp);
+    router
+        .group("/v1/api/auth")
+        .cors(CORS.clone())
+        .layer(require_session)
+        .get("/api-keys/", api_keys)
+        .post("/api-keys/", create_api_key)
+        .post("/api-keys/revoke/", revoke_api_key)
+        .get("/profile/", profile)
+        .post("/profile/", update_profile)
+        .get("/sessions/", sessions)
+        .post("/sessions/revoke/", revoke_session)
+        .post("/logout/", logout)
+        .get("/csrf/", csrf)
+        // Note: needs the `audit:read` scope as well
+        .get("/admin/audit-events/", audit_events);
+
+    router
+        .group("/auth")
+        .get("/login/", login_page)
+        .get("/github/login/", github_login)
+        .get("/github/callback/", github_callback)
+        // Note: forward auth subrequest of the reverse proxies, see `forward_auth`
+        .any("/verify/", forward_auth)
+        .post("/get-identities/", get_identities);
+    router
+        .group("/auth")
+        .layer(require_session)
+        .post("/github/unlink/", github_unlink);
+
+    crate::oidc::register(router);
+}
+
+// logged in user of the routes behind `require_session`
+// Note: api keys are managed with the login session only, an api key can not create other keys
+#[derive(Clone)]
+struct Session {
+    user_id: i64,
+    token: String,
+}
+
+async fn require_session(
+    mut req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+    next: router::Next<db::pg::DbPool, Incoming>,
+) -> Result<hyper::Response<Vec<u8>>, router::BoxError> {
+    if !crate::session::verify_csrf(req.method(), req.headers()) {
+        return Ok(error(
+            "invalid csrf token".to_string(),
+            hyper::StatusCode::FORBIDDEN,
+        )?);
+    }
+    match session_token(req.headers(), &db_pool).await? {
+        Some((user_id, token)) => {
+            use tracing::Instrument;
+
+            req.extensions_mut().insert(Session { user_id, token });
+            // Note: nested in the request span, the logs of the handler have the user
+            next.run(req, db_pool)
+                .instrument(tracing::info_span!("user", user_id = user_id))
+                .await
+        }
+        None => Ok(error(
+            "unauthorized".to_string(),
+            hyper::StatusCode::UNAUTHORIZED,
+        )?),
+    }
+}
+
+fn session(req: &hyper::Request<Incoming>) -> Session {
+    req.extensions()
+        .get::<Session>()
+        .cloned()
+        .expect("route is not behind require_session")
+}
+
+// event of the logged in user, `outcome` is taken from the result of the handler
+async fn audit<T, E>(
+    client: &crate::audit::Client,
+    event: crate::audit::Event,
+    user_id: i64,
+    result: &Result<T, E>,
+    reason: impl Fn(&E) -> &'static str,
+    db_pool: &db::pg::DbPool,
+) {
+    crate::audit::record(
+        crate::audit::Record {
+            event,
+            outcome: crate::audit::outcome(result, reason),
+            user_id: Some(user_id),
+            email: None,
+        },
+        client,
+        db_pool,
+    )
+    .await;
+}
+
+async fn send_otp(
     req: hyper::Request<Incoming>,
     db_pool: db::pg::DbPool,
 ) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
-    let (p, b) = req.into_parts();
-    let _start = std::time::Instant::now();
-    match (&p.method, p.uri.path()) {
-        (&hyper::Method::POST, "/v1/api/auth/send-otp/") => {
-            match crate::otp::send_otp(from_body(b).await?, db_pool).await {
-                Ok(response) => success(response),
-                Err(err) => {
-                    tracing::error!(mesage = "err:send_otp", error = err.to_string());
-                    error(
-                        "server error".to_string(),
-                        hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                    )
-                }
-            }
+    let client = crate::audit::Client::from_request(&req);
+    match crate::otp::send_otp(from_body(req).await?, &client, db_pool).await {
+        Ok(response) => success(response),
+        Err(err) => {
+            tracing::error!(mesage = "err:send_otp", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
         }
-        (&hyper::Method::POST, "/v1/api/auth/resend-otp/") => {
-            match crate::otp::resend_otp(from_body(b).await?, db_pool).await {
-                Ok(response) => success(response),
-                Err(err) => {
-                    tracing::error!(message = "err:re_send_otp", error = err.to_string());
-                    error(
-                        "server error".to_string(),
-                        hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                    )
-                }
-            }
+    }
+}
+
+async fn resend_otp(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let client = crate::audit::Client::from_request(&req);
+    match crate::otp::resend_otp(from_body(req).await?, &client, db_pool).await {
+        Ok(response) => success(response),
+        Err(err) => {
+            tracing::error!(message = "err:re_send_otp", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
         }
-        (&hyper::Method::POST, "/v1/api/auth/verify-otp/") => {
-            match crate::otp::verify_otp(from_body(b).await?, db_pool).await {
-                Ok(response) => success(response),
-                Err(err) => {
-                    tracing::error!(message = "err:re_send_otp", error = err.to_string());
-                    error(
-                        "server error".to_string(),
-                        hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                    )
-                }
+    }
+}
+
+async fn verify_otp(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let host = req
+        .headers()
+        .get(hyper::header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .map(String::from);
+    let client = crate::audit::Client::from_request(&req);
+    match crate::otp::verify_otp(from_body(req).await?, &client, db_pool).await {
+        // Note: browser logins (login page of the oidc authorize) get the session cookie, in the
+        // cookie session mode the token is not sent in the body
+        Ok(mut response) => {
+            let cookies =
+                host.map(|host| crate::session::login_cookies(&response.user_token, &host));
+            if *crate::session::SESSION_MODE == crate::session::SessionMode::Cookie {
+                response.user_token = String::new();
             }
+            let mut resp = success(response)?;
+            set_cookies(&mut resp, cookies.into_iter().flatten());
+            Ok(resp)
+        }
+        Err(err) => {
+            tracing::error!(message = "err:verify_otp", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
+
+async fn api_keys(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    api_key_response(crate::api_key::list(session(&req).user_id, &db_pool).await)
+}
+
+async fn create_api_key(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let user_id = session(&req).user_id;
+    api_key_response(crate::api_key::create(user_id, from_body(req).await?, &db_pool).await)
+}
+
+async fn revoke_api_key(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let user_id = session(&req).user_id;
+    let client = crate::audit::Client::from_request(&req);
+    let result = crate::api_key::revoke(user_id, from_body(req).await?, &db_pool).await;
+    audit(
+        &client,
+        crate::audit::Event::ApiKeyRevoke,
+        user_id,
+        &result,
+        crate::api_key::ApiKeyError::reason,
+        &db_pool,
+    )
+    .await;
+    api_key_response(result.map(|()| serde_json::json!({"message": "api key revoked"})))
+}
+
+async fn profile(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    user_response(crate::user::profile(session(&req).user_id, &db_pool).await)
+}
+
+async fn update_profile(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let user_id = session(&req).user_id;
+    let client = crate::audit::Client::from_request(&req);
+    let result = crate::user::update_profile(user_id, from_body(req).await?, &db_pool).await;
+    audit(
+        &client,
+        crate::audit::Event::ProfileUpdate,
+        user_id,
+        &result,
+        crate::user::UserError::reason,
+        &db_pool,
+    )
+    .await;
+    user_response(result)
+}
+
+async fn sessions(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let Session { user_id, token } = session(&req);
+    user_response(crate::user::sessions(user_id, token.as_str(), &db_pool).await)
+}
+
+async fn revoke_session(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let user_id = session(&req).user_id;
+    let client = crate::audit::Client::from_request(&req);
+    let result = crate::user::revoke_session(user_id, from_body(req).await?, &db_pool).await;
+    audit(
+        &client,
+        crate::audit::Event::SessionRevoke,
+        user_id,
+        &result,
+        crate::user::UserError::reason,
+        &db_pool,
+    )
+    .await;
+    user_response(result.map(|()| serde_json::json!({"message": "session revoked"})))
+}
+
+async fn logout(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let Session { user_id, token } = session(&req);
+    let result = crate::user::logout(user_id, token.as_str(), &db_pool).await;
+    audit(
+        &crate::audit::Client::from_request(&req),
+        crate::audit::Event::Logout,
+        user_id,
+        &result,
+        crate::user::UserError::reason,
+        &db_pool,
+    )
+    .await;
+    let mut resp = user_response(result.map(|()| serde_json::json!({"message": "logged out"})))?;
+    if let Some(host) = req
+        .headers()
+        .get(hyper::header::HOST)
+        .and_then(|host| host.to_str().ok())
+    {
+        set_cookies(&mut resp, crate::session::clear_cookies(host));
+    }
+    Ok(resp)
+}
+
+fn set_cookies(resp: &mut hyper::Response<Vec<u8>>, cookies: impl IntoIterator<Item = String>) {
+    for cookie in cookies {
+        if let Ok(cookie) = hyper::header::HeaderValue::from_str(cookie.as_str()) {
+            resp.headers_mut().append(hyper::header::SET_COOKIE, cookie);
         }
-        _ => Ok(crate::not_found!(serde_json::json!(
-                {"message": format!("route not found: {}", p.uri.path()),"success": false})
-        .to_string())),
     }
 }
 
+// Note: events of all the users, newest first, `before_id` of the next page is `next_before_id`
+async fn audit_events(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let query = req.uri().query().unwrap_or_default();
+    match crate::audit::list(session(&req).user_id, query, &db_pool).await {
+        Ok(response) => success(response),
+        Err(crate::audit::AuditError::InvalidRequest(message)) => {
+            error(message, hyper::StatusCode::BAD_REQUEST)
+        }
+        Err(crate::audit::AuditError::Forbidden(err)) => Ok(err.response()),
+        Err(err) => {
+            tracing::error!(message = "err:audit_events", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
+
+// csrf token of the session for the browser apps on the other origins, they can not read the
+// `auth-csrf` cookie of this host
+async fn csrf(
+    req: hyper::Request<Incoming>,
+    _db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    success(auth_types::user::CsrfRes {
+        csrf_token: crate::session::csrf_token(session(&req).token.as_str()),
+    })
+}
+
+async fn login_page(
+    _req: hyper::Request<Incoming>,
+    _db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    let bytes = tokio::fs::read("service/auth/login.html").await?;
+    Ok(router::html(bytes))
+}
+
 // todo: remove unwrap and add logging
-pub async fn routes(
+async fn github_login(
+    req: hyper::Request<Incoming>,
+    _db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    Ok(crate::github::login(&req).await.unwrap())
+}
+
+async fn github_callback(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    match crate::github::callback(&req, &db_pool).await {
+        Ok(response) => Ok(response),
+        Err(crate::github::CallbackError::InvalidState) => error(
+            "github login is not started in this browser, login again".to_string(),
+            hyper::StatusCode::BAD_REQUEST,
+        ),
+        Err(err) => {
+            tracing::error!(message = "err:github_callback", error = err.to_string());
+            error(
+                "github login failed".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
+
+async fn github_unlink(
     req: hyper::Request<Incoming>,
     db_pool: db::pg::DbPool,
 ) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
-    // Note: API handler
-    if req.uri().path().starts_with("/v1/api/auth/") {
-        return api_handler(req, db_pool).await;
+    let user_id = session(&req).user_id;
+    let result = crate::github::token::revoke(user_id, &db_pool).await;
+    audit(
+        &crate::audit::Client::from_request(&req),
+        crate::audit::Event::GithubUnlink,
+        user_id,
+        &result,
+        crate::github::token::TokenError::reason,
+        &db_pool,
+    )
+    .await;
+    match result {
+        Ok(()) => success(serde_json::json!({"message": "github account unlinked"})),
+        Err(err) => {
+            tracing::error!(message = "err:github_unlink", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
     }
+}
 
-    // OAuth handler
-    match req.uri().path() {
-        "/auth/github/login/" => Ok(crate::github::login(&req).await.unwrap()),
-        "/auth/github/callback/" => Ok(crate::github::callback(&req).await.unwrap()),
+async fn forward_auth(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    match crate::forward_auth::verify(req.uri(), req.headers(), &db_pool).await {
+        Ok(response) => Ok(response),
+        Err(err) => {
+            tracing::error!(message = "err:forward_auth", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
 
-        // Note: send the cookies starts with auth-
-        "/auth/get-identities/" => {
-            let (_p, b) = req.into_parts();
-            match crate::get_identities::get_identities(from_body(b).await?).await {
-                Ok(response) => success(response),
-                Err(err) => {
-                    println!("err:re_send_otp: {:?}", err);
-                    error(
-                        "server error".to_string(),
-                        hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                    )
-                }
-            }
+// Note: send the cookies starts with auth-
+async fn get_identities(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    match crate::get_identities::get_identities(from_body(req).await?, db_pool).await {
+        // Note: github could not be asked, calling service must not take it as denied
+        Ok(response) if response.status == crate::get_identities::GetIdsStatus::Undetermined => {
+            respond(response, hyper::StatusCode::SERVICE_UNAVAILABLE)
+        }
+        Ok(response) => success(response),
+        Err(crate::get_identities::GetIdsError::Expression(err)) => error(
+            format!("invalid expression: {}", err),
+            hyper::StatusCode::BAD_REQUEST,
+        ),
+        Err(crate::get_identities::GetIdsError::Unauthenticated(message)) => {
+            error(message, hyper::StatusCode::UNAUTHORIZED)
         }
-        // Some(p) if p.eq("gitlab") => Ok(hyper::Response::new(hyper::Body::empty())),
-        // Some(p) if p.eq("google") => Ok(hyper::Response::new(hyper::Body::empty())),
-        // Some(p) if p.eq("twitter") => Ok(hyper::Response::new(hyper::Body::empty())),
-        // Some(p) if p.eq("discord") => Ok(hyper::Response::new(hyper::Body::empty())),
-        // Some(p) if p.eq("linkedin") => Ok(hyper::Response::new(hyper::Body::empty())),
-        _ => {
-            let bytes = tokio::fs::read("service/auth/login.html").await?;
-            Ok(hyper::Response::new(bytes))
+        Err(err) => {
+            tracing::error!(message = "err:get_identities", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
+
+// user and the active session token of the request, without the `Bearer ` of the authorization
+// header
+async fn session_token(
+    headers: &hyper::HeaderMap,
+    db_pool: &db::pg::DbPool,
+) -> Result<Option<(i64, String)>, db::DBError> {
+    let token = match crate::session::from_headers(headers) {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    let token = token.strip_prefix("Bearer ").unwrap_or(token.as_str());
+    Ok(crate::session::active_user_id(token, db_pool)
+        .await?
+        .map(|user_id| (user_id, token.to_string())))
+}
+
+fn api_key_response(
+    result: Result<impl serde::Serialize, crate::api_key::ApiKeyError>,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    match result {
+        Ok(response) => success(response),
+        Err(crate::api_key::ApiKeyError::InvalidRequest(message)) => {
+            error(message, hyper::StatusCode::BAD_REQUEST)
+        }
+        Err(crate::api_key::ApiKeyError::NotFound) => error(
+            "api key not found".to_string(),
+            hyper::StatusCode::NOT_FOUND,
+        ),
+        Err(err) => {
+            tracing::error!(message = "err:api_key", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
+        }
+    }
+}
+
+fn user_response(
+    result: Result<impl serde::Serialize, crate::user::UserError>,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    match result {
+        Ok(response) => success(response),
+        Err(crate::user::UserError::InvalidRequest(message)) => {
+            error(message, hyper::StatusCode::BAD_REQUEST)
+        }
+        Err(crate::user::UserError::NotFound) => {
+            error("not found".to_string(), hyper::StatusCode::NOT_FOUND)
+        }
+        Err(err) => {
+            tracing::error!(message = "err:user", error = err.to_string());
+            error(
+                "server error".to_string(),
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+            )
         }
     }
 }
@@ -154,3 +617,38 @@ pub fn response(body: String, status: hyper::StatusCode) -> hyper::Response<Vec<
     );
     response
 }
+
+#[cfg(test)]
+mod tests {
+    fn cors(vars: &[(&str, &str)]) -> Result<router::Cors, String> {
+        super::cors(|name| {
+            vars.iter()
+                .find(|(var, _)| var.eq(&name))
+                .map(|(_, val)| val.to_string())
+        })
+    }
+
+    #[test]
+    fn cors_of_env() {
+        assert!(cors(&[]).is_ok());
+        assert!(cors(&[
+            (
+                "CORS_ALLOWED_ORIGINS",
+                "https://app.example.com, http://localhost:3000/,*"
+            ),
+            ("CORS_ALLOW_CREDENTIALS", "TRUE"),
+            ("CORS_MAX_AGE_SECS", "600"),
+        ])
+        .is_ok());
+        for vars in [
+            [("CORS_MAX_AGE_SECS", "ten minutes")],
+            [("CORS_MAX_AGE_SECS", "-1")],
+            [("CORS_ALLOW_CREDENTIALS", "yes")],
+            [("CORS_ALLOWED_ORIGINS", "app.example.com")],
+            [("CORS_ALLOWED_ORIGINS", "https://app.example.com/login")],
+            [("CORS_ALLOWED_ORIGINS", "ftp://app.example.com")],
+        ] {
+            assert!(cors(&vars).is_err(), "{:?}", vars);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// Note: provider access tokens are stored encrypted at rest with AES-256-GCM,
+// the 12 byte nonce is prepended to the cipher text
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+
+const NONCE_LEN: usize = 12;
+
+// base64 encoded 32 bytes key, generate with `openssl rand -base64 32`
+static PROVIDER_TOKEN_KEY: once_cell::sync::Lazy<aes_gcm::Aes256Gcm> = {
+    once_cell::sync::Lazy::new(|| {
+        use base64::Engine;
+        let key = match std::env::var("PROVIDER_TOKEN_KEY") {
+            Ok(val) => val,
+            Err(e) => panic!("{}{}", "PROVIDER_TOKEN_KEY not found in env ", e),
+        };
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(key.trim())
+            .expect("PROVIDER_TOKEN_KEY must be base64 encoded");
+        aes_gcm::Aes256Gcm::new_from_slice(key.as_slice())
+            .expect("PROVIDER_TOKEN_KEY must be of 32 bytes")
+    })
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum CryptoError {
+    #[error("EncryptError")]
+    Encrypt,
+    #[error("DecryptError")]
+    Decrypt,
+    #[error("Utf8Error: {}", _0)]
+    Utf8(#[from] std::string::FromUtf8Error),
+}
+
+pub fn encrypt(plain_text: &str) -> Result<Vec<u8>, CryptoError> {
+    encrypt_with(&PROVIDER_TOKEN_KEY, plain_text)
+}
+
+pub fn decrypt(cipher_text: &[u8]) -> Result<String, CryptoError> {
+    decrypt_with(&PROVIDER_TOKEN_KEY, cipher_text)
+}
+
+fn encrypt_with(cipher: &aes_gcm::Aes256Gcm, plain_text: &str) -> Result<Vec<u8>, CryptoError> {
+    let nonce = aes_gcm::Aes256Gcm::generate_nonce(&mut OsRng);
+    let encrypted = cipher
+        .encrypt(&nonce, plain_text.as_bytes())
+        .map_err(|_| CryptoError::Encrypt)?;
+    let mut out = Vec::with_capacity(NONCE_LEN + encrypted.len());
+    out.extend_from_slice(nonce.as_slice());
+    out.extend_from_slice(encrypted.as_slice());
+    Ok(out)
+}
+
+fn decrypt_with(cipher: &aes_gcm::Aes256Gcm, cipher_text: &[u8]) -> Result<String, CryptoError> {
+    if cipher_text.len() < NONCE_LEN {
+        return Err(CryptoError::Decrypt);
+    }
+    let (nonce, encrypted) = cipher_text.split_at(NONCE_LEN);
+    let plain_text = cipher
+        .decrypt(aes_gcm::Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| CryptoError::Decrypt)?;
+    Ok(String::from_utf8(plain_text)?)
+}
+
+// sha256 hex, tokens handed out to the clients (authorization codes, client secrets) are stored
+// only as their hash
+pub fn hash(value: &str) -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Note: compared in constant time, the time taken must not tell how much of the hash matched
+pub fn verify_hash(value: &str, hash: &str) -> bool {
+    use subtle::ConstantTimeEq;
+    self::hash(value).as_bytes().ct_eq(hash.as_bytes()).into()
+}
+
+// url safe random token of `bytes` random bytes
+pub fn random_token(bytes: usize) -> String {
+    use base64::Engine;
+    use rand::RngCore;
+    let mut buf = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(buf.as_mut_slice());
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use aes_gcm::aead::KeyInit;
+
+    #[test]
+    fn encrypt_decrypt_round_trip() {
+        let cipher = aes_gcm::Aes256Gcm::new_from_slice(&[7u8; 32]).unwrap();
+        let encrypted = super::encrypt_with(&cipher, "gho_token").unwrap();
+        assert_ne!(&encrypted[super::NONCE_LEN..], b"gho_token");
+        assert_eq!(
+            super::decrypt_with(&cipher, &encrypted).unwrap(),
+            "gho_token"
+        );
+    }
+
+    #[test]
+    fn verify_hash_of_value() {
+        let hash = super::hash("secret");
+        assert!(super::verify_hash("secret", hash.as_str()));
+        assert!(!super::verify_hash("secreT", hash.as_str()));
+        assert!(!super::verify_hash("secret", &hash[1..]));
+    }
+
+    #[test]
+    fn decrypt_rejects_tampered_cipher_text() {
+        let cipher = aes_gcm::Aes256Gcm::new_from_slice(&[7u8; 32]).unwrap();
+        let mut encrypted = super::encrypt_with(&cipher, "gho_token").unwrap();
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+        assert!(super::decrypt_with(&cipher, &encrypted).is_err());
+    }
+}
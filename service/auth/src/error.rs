@@ -1,7 +1,7 @@
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
-    #[error("BodyReadError: {0}")]
-    ReadBody(String),
+    #[error("BodyError: {0}")]
+    Body(#[from] router::BodyError),
     #[error("FileReadError: {0}")]
     FileReadError(#[from] std::io::Error),
     #[error("JsonParseError: {0}")]
@@ -0,0 +1,203 @@
+// Note: `/auth/verify` for the reverse proxies, nginx `auth_request` and traefik `forwardAuth`,
+// protecting the apps which know nothing about this service. The caller is authenticated with the
+// session cookie or the bearer token, identities are optionally checked with the query params
+// `expression=org:fastn-stack AND starred:a/b` or `identity=github-org-member:fastn-stack` (OR of
+// all of them). Allowed requests get 200 with the user headers, the proxy passes them to the app.
+// nginx can only take 401 and 403 from the subrequest, traefik returns our response to the browser
+// as it is, so the browsers are redirected to the login page only when the request has the
+// `X-Forwarded-Uri` of traefik
+pub const USER_ID_HEADER: &str = "x-auth-user-id";
+pub const EMAIL_HEADER: &str = "x-auth-email";
+const FORWARDED_URI_HEADER: &str = "x-forwarded-uri";
+
+#[derive(thiserror::Error, Debug)]
+pub enum ForwardAuthError {
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+    #[error("AuthenticateError: {}", _0)]
+    Authenticate(#[from] crate::authenticate::AuthenticateError),
+    #[error("GetIdsError: {}", _0)]
+    GetIds(#[from] crate::get_identities::GetIdsError),
+}
+
+pub async fn verify(
+    uri: &hyper::Uri,
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, ForwardAuthError> {
+    // Note: a broken expression is the mistake of the proxy config, it is reported before the
+    // caller is looked at
+    let expr = match expression(uri.query().unwrap_or_default()) {
+        Ok(expr) => expr,
+        Err(err) => {
+            return Ok(reply(
+                format!("invalid expression: {}", err),
+                hyper::StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let user_id = match user_id(headers, db_pool).await? {
+        Some(user_id) => user_id,
+        None => return Ok(unauthorized(headers)),
+    };
+
+    if let Some(expr) = expr {
+        let response = crate::get_identities::for_user(user_id, expr, db_pool).await?;
+        match response.status {
+            crate::get_identities::GetIdsStatus::Granted => {}
+            crate::get_identities::GetIdsStatus::Denied => {
+                tracing::info!(message = "forward auth denied", user_id = user_id);
+                return Ok(reply("forbidden".to_string(), hyper::StatusCode::FORBIDDEN));
+            }
+            // Note: github could not be asked, the proxy must not take it as denied
+            crate::get_identities::GetIdsStatus::Undetermined => {
+                return Ok(reply(
+                    "identities could not be checked".to_string(),
+                    hyper::StatusCode::SERVICE_UNAVAILABLE,
+                ))
+            }
+        }
+    }
+
+    let mut response = reply("ok".to_string(), hyper::StatusCode::OK);
+    response
+        .headers_mut()
+        .insert(USER_ID_HEADER, hyper::header::HeaderValue::from(user_id));
+    if let Some(email) = db::user::get(user_id, db_pool)
+        .await?
+        .and_then(|user| user.email)
+        .and_then(|email| hyper::header::HeaderValue::from_str(email.as_str()).ok())
+    {
+        response.headers_mut().insert(EMAIL_HEADER, email);
+    }
+    Ok(response)
+}
+
+// Note: `None` if the query has no identities, everyone logged in is allowed then
+fn expression(
+    query: &str,
+) -> Result<Option<crate::expression::Expr>, crate::expression::ExpressionError> {
+    let mut identities = vec![];
+    for (name, value) in url::form_urlencoded::parse(query.as_bytes()) {
+        match name.as_ref() {
+            "expression" => {
+                let expr = crate::expression::parse(value.as_ref())?;
+                crate::get_identities::validate(&expr)?;
+                return Ok(Some(expr));
+            }
+            "identity" => match value.split_once(':') {
+                Some((key, value)) => {
+                    identities.push(crate::expression::Expr::Identity(crate::Identity {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                    }))
+                }
+                None => {
+                    return Err(crate::expression::ExpressionError::InvalidPredicate(
+                        value.into_owned(),
+                    ))
+                }
+            },
+            _ => {}
+        }
+    }
+    if identities.is_empty() {
+        return Ok(None);
+    }
+    let expr = crate::expression::Expr::Or(identities);
+    crate::get_identities::validate(&expr)?;
+    Ok(Some(expr))
+}
+
+// Note: the session token first, then the bearer session token and the api keys of the users.
+// Machine clients have no user and the access tokens of the oidc clients are not the user's own,
+// they are not let through
+async fn user_id(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+    db_pool: &db::pg::DbPool,
+) -> Result<Option<i64>, ForwardAuthError> {
+    if let Some(token) = crate::session::from_headers(headers) {
+        if let Some(user_id) = crate::session::active_user_id(token.as_str(), db_pool).await? {
+            return Ok(Some(user_id));
+        }
+    }
+    if !headers.contains_key(hyper::header::AUTHORIZATION)
+        && !headers.contains_key(crate::api_key::API_KEY_HEADER)
+    {
+        return Ok(None);
+    }
+    // Note: the machine clients are not let through, they are turned away before their client is
+    // looked up
+    let client = crate::jwt::token_from_headers(headers)
+        .and_then(|token| crate::jwt::decode_claims(token.as_str()))
+        .and_then(|claims| claims.principal());
+    if let Ok(crate::jwt::Principal::Client(_)) = client {
+        return Ok(None);
+    }
+    match crate::authenticate::authenticate(headers, db_pool).await {
+        Ok(caller) => Ok(match caller.principal {
+            crate::jwt::Principal::User(user_id)
+            | crate::jwt::Principal::ApiKey { user_id, .. } => Some(user_id),
+            crate::jwt::Principal::Client(_) | crate::jwt::Principal::Delegated { .. } => None,
+        }),
+        Err(err) if err.is_unauthorized() => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn unauthorized(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+) -> hyper::Response<Vec<u8>> {
+    let accepts_html = headers
+        .get(hyper::header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("text/html"));
+    let next = headers
+        .get(FORWARDED_URI_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(crate::session::safe_next);
+    match (accepts_html, next) {
+        (true, Some(next)) => {
+            let mut response = hyper::Response::new(vec![]);
+            *response.status_mut() = hyper::StatusCode::FOUND;
+            if let Ok(location) =
+                hyper::header::HeaderValue::from_str(crate::session::login_url(next).as_str())
+            {
+                response
+                    .headers_mut()
+                    .insert(hyper::header::LOCATION, location);
+            }
+            response
+        }
+        _ => reply("unauthorized".to_string(), hyper::StatusCode::UNAUTHORIZED),
+    }
+}
+
+fn reply(message: String, status: hyper::StatusCode) -> hyper::Response<Vec<u8>> {
+    crate::controller::response(
+        serde_json::json!({"message": message, "success": status.is_success()}).to_string(),
+        status,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn expression_from_query() {
+        assert_eq!(super::expression("").unwrap(), None);
+        assert_eq!(
+            super::expression("identity=github-org-member:fastn-stack&identity=github-starred:a/b")
+                .unwrap()
+                .unwrap()
+                .identities()
+                .len(),
+            2
+        );
+        assert!(
+            super::expression("expression=org%3Afastn-stack%20AND%20starred%3Aa%2Fb")
+                .unwrap()
+                .is_some()
+        );
+        assert!(super::expression("identity=fastn-stack").is_err());
+    }
+}
@@ -1,60 +1,204 @@
-#[derive(Debug, serde::Deserialize)]
-pub struct Token {
-    pub key: String,
-    pub value: String,
+use futures::StreamExt;
+
+pub use auth_types::identities::{
+    FailedClause, GetIdsRequest, GetIdsResponse, GetIdsStatus, Identity, Token,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetIdsError {
+    #[error("ExpressionError: {}", _0)]
+    Expression(#[from] crate::expression::ExpressionError),
+    #[error("GithubTokenError: {}", _0)]
+    GithubToken(#[from] crate::github::token::TokenError),
+    // Note: there is nothing to check without the user, `NOT` of anything would be granted
+    #[error("Unauthenticated: {}", _0)]
+    Unauthenticated(String),
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
-pub struct Identity {
-    pub key: String,
-    pub value: String,
+#[derive(Clone)]
+enum Outcome {
+    Matched,
+    Failed(String),
+    Undetermined(String),
+    // can not be checked for the user, e.g. the github account is not linked, it never satisfies
+    // the expression, not even under `NOT`
+    Unavailable(String),
+    // provider token is revoked by the user or expired
+    Revoked,
 }
 
-#[derive(Debug, serde::Deserialize)]
-pub struct GetIdsRequest {
-    pub tokens: Vec<Token>,
-    pub identities: Vec<Identity>,
+// Note: unknown providers and identities, and the values which are not github names, are the
+// mistake of the caller, they are rejected instead of being evaluated as not satisfied
+pub fn validate(expr: &crate::expression::Expr) -> Result<(), crate::expression::ExpressionError> {
+    expr.check_size()?;
+    for identity in expr.identities() {
+        if !crate::github::apis::IDENTITY_KEYS.contains(&identity.key.as_str()) {
+            return Err(crate::expression::ExpressionError::InvalidPredicate(
+                format!("unknown identity {}", identity.key),
+            ));
+        }
+        if !crate::github::apis::is_valid_value(identity.key.as_str(), identity.value.as_str()) {
+            return Err(crate::expression::ExpressionError::InvalidPredicate(
+                format!("invalid value of {}", identity),
+            ));
+        }
+    }
+    Ok(())
 }
 
-#[derive(Debug, serde::Serialize)]
-pub struct GetIdsResponse {
-    // list of expired tokens so that main service can
-    // todo: is there any way to refresh token, or need to check when can this token would be expire
-    expired_token_cookies: Vec<String>,
-    // send only one success, because we are checking as OR
-    // or no success
-    // note: telling which identity got success
-    success: bool,
+// Note: `github_token` is `Err` with the outcome of all the github identities, if they can not be
+// checked
+async fn check<'a>(
+    identity: &'a Identity,
+    github_token: &Result<String, Outcome>,
+) -> (&'a Identity, Outcome) {
+    let access_token = match github_token {
+        Ok(access_token) => access_token,
+        Err(outcome) => return (identity, outcome.clone()),
+    };
+    let outcome = match crate::github::apis::check_identity(access_token, identity).await {
+        Ok(true) => Outcome::Matched,
+        Ok(false) => Outcome::Failed("not satisfied".to_string()),
+        Err(crate::github::apis::GithubApiError::Unauthorized) => Outcome::Revoked,
+        Err(err) => {
+            tracing::error!(
+                message = "github identity check failed",
+                identity = identity.to_string(),
+                error = err.to_string()
+            );
+            Outcome::Undetermined(err.to_string())
+        }
+    };
+    (identity, outcome)
 }
 
-#[derive(Debug, serde::Serialize)]
-pub struct GetIdsError {}
+pub async fn get_identities(
+    req: GetIdsRequest,
+    db_pool: db::pg::DbPool,
+) -> Result<GetIdsResponse, GetIdsError> {
+    // Note: nothing of the request is parsed for the callers without a session
+    let session = req
+        .tokens
+        .iter()
+        .find(|t| t.key.eq(crate::session::SESSION_COOKIE))
+        .ok_or_else(|| GetIdsError::Unauthenticated("session not found".to_string()))?;
+    let user_id = crate::session::active_user_id(session.value.as_str(), &db_pool)
+        .await?
+        .ok_or_else(|| GetIdsError::Unauthenticated("invalid session".to_string()))?;
 
-pub async fn get_identities(req: GetIdsRequest) -> Result<GetIdsResponse, GetIdsError> {
-    if let Some(github_token) = req.tokens.iter().find(|t| t.key.eq("auth-gt-token")) {
-        return match crate::github::apis::get_identities(
-            github_token.value.as_str(),
+    let expr = match req.expression {
+        Some(requirement) => requirement.into_expr()?,
+        None => crate::expression::Expr::Or(
             req.identities
-                .iter()
-                .filter(|id| id.key.starts_with("github"))
+                .into_iter()
+                .map(crate::expression::Expr::Identity)
                 .collect(),
-        )
-        .await
-        {
-            Ok(flag) => Ok(GetIdsResponse {
-                expired_token_cookies: vec![],
-                success: flag,
-            }),
-            Err(_err) => Ok(GetIdsResponse {
-                expired_token_cookies: vec![],
-                success: false,
-            }),
+        ),
+    };
+    validate(&expr)?;
+    evaluate(expr, user_id, &db_pool).await
+}
+
+// identities of the expression checked for the already authenticated user, see `forward_auth`
+pub async fn for_user(
+    user_id: i64,
+    expr: crate::expression::Expr,
+    db_pool: &db::pg::DbPool,
+) -> Result<GetIdsResponse, GetIdsError> {
+    validate(&expr)?;
+    evaluate(expr, user_id, db_pool).await
+}
+
+async fn evaluate(
+    expr: crate::expression::Expr,
+    user_id: i64,
+    db_pool: &db::pg::DbPool,
+) -> Result<GetIdsResponse, GetIdsError> {
+    let identities = expr.identities();
+    let github_token = match identities.iter().any(|id| id.key.starts_with("github-")) {
+        true => {
+            match crate::github::token::access_token(user_id, db_pool).await {
+                Ok(Some(access_token)) => Ok(access_token),
+                Ok(None) => Err(Outcome::Unavailable(
+                    "github account not linked".to_string(),
+                )),
+                // Note: the session stays, only the github account has to be linked again
+                Err(crate::github::token::TokenError::Expired) => {
+                    crate::github::token::forget(user_id, db_pool).await?;
+                    Err(Outcome::Unavailable(
+                        "github account not linked".to_string(),
+                    ))
+                }
+                // Note: github is not reachable to refresh the token
+                Err(crate::github::token::TokenError::Refresh(err)) => Err(Outcome::Undetermined(
+                    format!("github token refresh: {}", err),
+                )),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        false => Err(Outcome::Unavailable(
+            "github account not linked".to_string(),
+        )),
+    };
+
+    // Note: identities are checked concurrently, cached results are served by the github module
+    let checks: Vec<_> = identities
+        .into_iter()
+        .map(|identity| check(identity, &github_token))
+        .collect();
+    let outcomes: Vec<(&Identity, Outcome)> = futures::stream::iter(checks)
+        .buffered(crate::github::apis::MAX_CONCURRENT_CHECKS)
+        .collect()
+        .await;
+
+    // Note: github token is revoked by the user or expired, the user logs in with github again to
+    // link the account, our session is not touched
+    if outcomes.iter().any(|(_, o)| matches!(o, Outcome::Revoked)) {
+        tracing::info!(message = "github token revoked", user_id = user_id);
+        crate::github::token::forget(user_id, db_pool).await?;
+    }
+
+    let status =
+        match expr.eval_with(
+            |identity| match outcomes.iter().find(|(i, _)| identity.eq(*i)) {
+                Some((_, Outcome::Matched)) => crate::expression::Leaf::Matched,
+                Some((_, Outcome::Failed(_))) => crate::expression::Leaf::NotMatched,
+                Some((_, Outcome::Undetermined(_))) => crate::expression::Leaf::Unknown,
+                Some((_, Outcome::Unavailable(_) | Outcome::Revoked)) | None => {
+                    crate::expression::Leaf::Unavailable
+                }
+            },
+        ) {
+            Some(true) => GetIdsStatus::Granted,
+            Some(false) => GetIdsStatus::Denied,
+            None => GetIdsStatus::Undetermined,
         };
+    let mut matched = vec![];
+    let mut failed = vec![];
+    let mut undetermined = vec![];
+    for (identity, outcome) in outcomes {
+        let clause = identity.to_string();
+        match outcome {
+            Outcome::Matched => matched.push(clause),
+            Outcome::Failed(reason) | Outcome::Unavailable(reason) => {
+                failed.push(FailedClause { clause, reason })
+            }
+            Outcome::Revoked => failed.push(FailedClause {
+                clause,
+                reason: "github account not linked".to_string(),
+            }),
+            Outcome::Undetermined(reason) => undetermined.push(FailedClause { clause, reason }),
+        }
     }
 
     Ok(GetIdsResponse {
-        // todo: do it later
         expired_token_cookies: vec![],
-        success: false,
+        success: status == GetIdsStatus::Granted,
+        status,
+        matched,
+        failed,
+        undetermined,
     })
 }
@@ -1,62 +1,472 @@
 #[derive(thiserror::Error, Debug)]
-pub enum GetIdentitiesError {
-    #[error("IsRepoStarred: {}", _0)]
-    IsRepoStarred(#[from] UserStaredRepoError),
+pub enum GithubApiError {
+    // Note: token is revoked by the user or expired
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("NotFound")]
+    NotFound,
+    #[error("RateLimited: retry after {:?} secs", _0)]
+    RateLimited(Option<u64>),
+    #[error("UpstreamError: status: {}, body: {}", status, body)]
+    Upstream { status: u16, body: String },
+    // Note: network errors, timeouts and unexpected response bodies
+    #[error("ReqwestError: {}", _0)]
+    Reqwest(#[from] reqwest::Error),
 }
 
-pub async fn get_identities(
+// Supported identities, the value format is given against each key
+// - github-starred: `owner/repo` is starred by the user
+// - github-org-member: `org` has the user as an active member
+// - github-team-member: `org/team-slug` has the user as an active member
+// - github-repo-collaborator: `owner/repo` has the user as collaborator with push access
+// - github-sponsor: `account` (user or org) is sponsored by the user
+// - github-follower: `account` is followed by the user
+pub const IDENTITY_KEYS: &[&str] = &[
+    "github-starred",
+    "github-org-member",
+    "github-team-member",
+    "github-repo-collaborator",
+    "github-sponsor",
+    "github-follower",
+];
+
+// Note: values go into the path of the github urls, so they must be the names github allows, a
+// `/`, `..` or `?` in them would call another api of github with the user's token
+// Docs: https://docs.github.com/en/repositories/creating-and-managing-repositories/repository-limits
+pub fn is_valid_value(key: &str, value: &str) -> bool {
+    match key {
+        "github-starred" | "github-repo-collaborator" => match value.split_once('/') {
+            Some((owner, repo)) => is_login(owner) && is_repo_name(repo),
+            None => false,
+        },
+        "github-team-member" => match value.split_once('/') {
+            Some((org, team_slug)) => is_login(org) && is_team_slug(team_slug),
+            None => false,
+        },
+        "github-org-member" | "github-sponsor" | "github-follower" => is_login(value),
+        _ => false,
+    }
+}
+
+// user and org logins are alphanumeric with hyphens, not starting with a hyphen, at most 39
+fn is_login(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 39
+        && !value.starts_with('-')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn is_repo_name(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 100
+        && value != "."
+        && value != ".."
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn is_team_slug(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 255
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+// identities of a request are checked concurrently, at most these many github calls at a time
+pub const MAX_CONCURRENT_CHECKS: usize = 8;
+
+// rate limited calls are retried these many times, if github asks to wait no longer than
+// `MAX_BACKOFF`, otherwise the rate limited response is returned as it is
+const MAX_RETRIES: u32 = 3;
+const MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(10);
+
+// Note: github's limits are shared by the calls of all the users (the secondary limits are per app
+// and ip), once github asks to wait no call is sent till then. The calls wait if it is not longer
+// than `MAX_BACKOFF`, otherwise they fail as rate limited without asking github
+static BACKOFF: once_cell::sync::Lazy<Backoff> = once_cell::sync::Lazy::new(Backoff::default);
+
+#[derive(Default)]
+struct Backoff {
+    until: std::sync::Mutex<Option<std::time::Instant>>,
+}
+
+impl Backoff {
+    fn remaining(&self, now: std::time::Instant) -> Option<std::time::Duration> {
+        let until = self.until.lock().unwrap_or_else(|e| e.into_inner());
+        until
+            .and_then(|until| until.checked_duration_since(now))
+            .filter(|wait| !wait.is_zero())
+    }
+
+    // later of the current and the asked wait
+    fn wait(&self, now: std::time::Instant, wait: std::time::Duration) {
+        let mut until = self.until.lock().unwrap_or_else(|e| e.into_inner());
+        *until = Some(until.map_or(now + wait, |until| until.max(now + wait)));
+    }
+}
+
+// Note: one client for all the github calls, so that the connections are reused
+static HTTP_CLIENT: once_cell::sync::Lazy<reqwest::Client> = once_cell::sync::Lazy::new(|| {
+    reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .expect("failed to build the github http client")
+});
+
+// Note: `Ok(false)` only when github says the relation does not exist, every other failure is an
+// error, so that a github outage is not taken as "not satisfied". Errors are not cached
+pub async fn check_identity(
     access_token: &str,
-    identities: Vec<&crate::Identity>,
-) -> Result<bool, GetIdentitiesError> {
-    if identities.is_empty() {
+    identity: &crate::Identity,
+) -> Result<bool, GithubApiError> {
+    if !is_valid_value(identity.key.as_str(), identity.value.as_str()) {
+        tracing::info!(
+            message = "invalid github identity",
+            identity = identity.to_string()
+        );
         return Ok(false);
     }
+    let cache_key = super::cache::identity_key(access_token, identity);
+    if let Some(matched) = super::cache::IDENTITIES.get(cache_key.as_str()) {
+        return Ok(matched);
+    }
 
-    let identities_group_by = crate::utils::group_by(identities, |item| item.key.as_str());
-    for (key, values) in identities_group_by {
-        match key {
-            "github-starred" => {
-                let is_user_starred_repos = is_user_starred_any(
-                    access_token,
-                    values.iter().map(|i| i.value.as_str()).collect(),
-                )
-                .await?;
-                println!("is_user_starred_any_repo: {:?}", is_user_starred_repos);
-                return Ok(is_user_starred_repos);
-            }
-            _ => {}
+    let value = identity.value.as_str();
+    let matched = match identity.key.as_str() {
+        "github-starred" => is_repo_starred(access_token, value).await?,
+        "github-org-member" => is_org_member(access_token, value).await?,
+        "github-team-member" => {
+            // Note: team membership api needs the username
+            let login = user_login(access_token).await?;
+            is_team_member(access_token, login.as_str(), value).await?
         }
+        "github-repo-collaborator" => is_repo_collaborator(access_token, value).await?,
+        "github-sponsor" => is_sponsor(access_token, value).await?,
+        "github-follower" => is_follower(access_token, value).await?,
+        _ => false,
+    };
+
+    let ttl = if matched {
+        super::cache::POSITIVE_TTL
+    } else {
+        super::cache::NEGATIVE_TTL
+    };
+    super::cache::IDENTITIES.insert(cache_key, matched, ttl);
+    Ok(matched)
+}
+
+async fn user_login(access_token: &str) -> Result<String, GithubApiError> {
+    let token_hash = super::cache::token_hash(access_token);
+    if let Some(login) = super::cache::USER_LOGINS.get(token_hash.as_str()) {
+        return Ok(login);
     }
-    Ok(false)
+    let login = call(get(access_token, "https://api.github.com/user"))
+        .await?
+        .json::<GithubUser>()
+        .await?
+        .login;
+    super::cache::USER_LOGINS.insert(token_hash, login.clone(), super::cache::POSITIVE_TTL);
+    Ok(login)
 }
 
-// Note: this function receives AbrarNitk/bookrafter or AbrarNitk/auth is starred or not the logged in user
+async fn call(request: reqwest::RequestBuilder) -> Result<reqwest::Response, GithubApiError> {
+    let result = respond(request).await;
+    crate::metrics::github(
+        "rest",
+        match &result {
+            Ok(_) => "success",
+            Err(GithubApiError::Unauthorized) => "unauthorized",
+            Err(GithubApiError::NotFound) => "not_found",
+            Err(GithubApiError::RateLimited(_)) => "rate_limited",
+            Err(GithubApiError::Upstream { .. }) => "upstream_error",
+            Err(GithubApiError::Reqwest(_)) => "network_error",
+        },
+    );
+    result
+}
 
-#[derive(thiserror::Error, Debug)]
-pub enum UserStaredRepoError {}
+// Note: sends the request and maps the failed responses to the typed errors
+async fn respond(request: reqwest::RequestBuilder) -> Result<reqwest::Response, GithubApiError> {
+    if let Some(wait) = BACKOFF.remaining(std::time::Instant::now()) {
+        if wait > MAX_BACKOFF {
+            return Err(GithubApiError::RateLimited(Some(wait.as_secs())));
+        }
+        tokio::time::sleep(wait).await;
+    }
+    let response = send(request).await?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(GithubApiError::Unauthorized);
+    }
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Err(GithubApiError::NotFound);
+    }
+    if let Some(wait) = rate_limit_wait(&response, 0) {
+        return Err(GithubApiError::RateLimited(Some(wait.as_secs())));
+    }
+    Err(GithubApiError::Upstream {
+        status: status.as_u16(),
+        body: response.text().await.unwrap_or_default(),
+    })
+}
 
-pub async fn is_user_starred_any(
-    access_token: &str,
-    resource_ids: Vec<&str>,
-) -> Result<bool, UserStaredRepoError> {
-    for resource in resource_ids.into_iter() {
-        if is_repo_starred(access_token, resource).await? {
-            return Ok(true);
-        } else {
-            eprintln!("not starred: {}", resource)
+// Note: github answers with 404 if the relation does not exist, like diesel's `optional`
+fn optional<T>(result: Result<T, GithubApiError>) -> Result<Option<T>, GithubApiError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(GithubApiError::NotFound) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+// Note: sends the request, waiting and retrying if github says the rate limit is exceeded
+// Docs: https://docs.github.com/en/rest/using-the-rest-api/best-practices-for-using-the-rest-api?apiVersion=2022-11-28#handle-rate-limit-errors-appropriately
+async fn send(mut request: reqwest::RequestBuilder) -> Result<reqwest::Response, reqwest::Error> {
+    let mut attempt = 0;
+    loop {
+        let retry = request.try_clone();
+        let response = request.send().await?;
+        let wait = rate_limit_wait(&response, attempt);
+        if let Some(wait) = wait {
+            BACKOFF.wait(std::time::Instant::now(), wait);
         }
+        let (wait, retry) = match (wait, retry) {
+            (Some(wait), Some(retry)) if attempt < MAX_RETRIES && wait <= MAX_BACKOFF => {
+                (wait, retry)
+            }
+            _ => return Ok(response),
+        };
+        tracing::warn!(
+            message = "github rate limited",
+            url = response.url().as_str(),
+            status = response.status().as_u16(),
+            wait_secs = wait.as_secs()
+        );
+        tokio::time::sleep(wait).await;
+        request = retry;
+        attempt += 1;
+    }
+}
+
+// Note: secondary rate limits send `retry-after`, primary rate limit sends the remaining calls as
+// zero with the reset time, 429 without any of the headers waits exponentially. 403 without the
+// headers is forbidden and not a rate limit
+fn rate_limit_wait(response: &reqwest::Response, attempt: u32) -> Option<std::time::Duration> {
+    let status = response.status();
+    if status != reqwest::StatusCode::TOO_MANY_REQUESTS && status != reqwest::StatusCode::FORBIDDEN
+    {
+        return None;
+    }
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    if let Some(secs) = header("retry-after") {
+        return Some(std::time::Duration::from_secs(secs));
     }
-    Ok(false)
+    if header("x-ratelimit-remaining") == Some(0) {
+        let reset = header("x-ratelimit-reset")?;
+        let now = chrono::Utc::now().timestamp() as u64;
+        return Some(std::time::Duration::from_secs(
+            reset.saturating_sub(now).max(1),
+        ));
+    }
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Some(std::time::Duration::from_secs(1 << attempt));
+    }
+    None
 }
 
+// Note: this function receives AbrarNitk/bookrafter or AbrarNitk/auth is starred or not the logged in user
 // API Docs: https://docs.github.com/en/rest/activity/starring?apiVersion=2022-11-28#check-if-a-repository-is-starred-by-the-authenticated-user
-pub async fn is_repo_starred(
+pub async fn is_repo_starred(access_token: &str, repo_name: &str) -> Result<bool, GithubApiError> {
+    let url = format!("https://api.github.com/user/starred/{}", repo_name);
+    check_no_content(access_token, url.as_str()).await
+}
+
+// Note: gives the body of the success response, `None` if github says there is no such relation
+async fn get_json<T: serde::de::DeserializeOwned>(
+    request: reqwest::RequestBuilder,
+) -> Result<Option<T>, GithubApiError> {
+    match optional(call(request).await)? {
+        Some(response) => Ok(Some(response.json::<T>().await?)),
+        None => Ok(None),
+    }
+}
+
+// Note: github answers the "check" apis with 204 if the relation exists and 404 if not
+async fn check_no_content(access_token: &str, url: &str) -> Result<bool, GithubApiError> {
+    Ok(optional(call(get(access_token, url)).await)?.is_some())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Membership {
+    state: String,
+}
+
+// API Docs: https://docs.github.com/en/rest/orgs/members?apiVersion=2022-11-28#get-an-organization-membership-for-the-authenticated-user
+pub async fn is_org_member(access_token: &str, org: &str) -> Result<bool, GithubApiError> {
+    let url = format!("https://api.github.com/user/memberships/orgs/{}", org);
+    Ok(get_json::<Membership>(get(access_token, url.as_str()))
+        .await?
+        .map(|m| m.state.eq("active"))
+        .unwrap_or(false))
+}
+
+// team is `org/team-slug`
+// API Docs: https://docs.github.com/en/rest/teams/members?apiVersion=2022-11-28#get-team-membership-for-a-user
+pub async fn is_team_member(
+    access_token: &str,
+    username: &str,
+    team: &str,
+) -> Result<bool, GithubApiError> {
+    let (org, team_slug) = match team.split_once('/') {
+        Some(v) => v,
+        None => return Ok(false),
+    };
+    let url = format!(
+        "https://api.github.com/orgs/{}/teams/{}/memberships/{}",
+        org, team_slug, username
+    );
+    Ok(get_json::<Membership>(get(access_token, url.as_str()))
+        .await?
+        .map(|m| m.state.eq("active"))
+        .unwrap_or(false))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RepoPermissions {
+    #[serde(default)]
+    admin: bool,
+    #[serde(default)]
+    maintain: bool,
+    #[serde(default)]
+    push: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct Repo {
+    permissions: Option<RepoPermissions>,
+}
+
+// Note: collaborators api needs push access to the repo itself, so reading the permissions of the
+// authenticated user on the repo instead
+// API Docs: https://docs.github.com/en/rest/repos/repos?apiVersion=2022-11-28#get-a-repository
+pub async fn is_repo_collaborator(
     access_token: &str,
     repo_name: &str,
-) -> Result<bool, UserStaredRepoError> {
-    let url = format!("https://api.github.com/user/starred/{}", repo_name);
+) -> Result<bool, GithubApiError> {
+    let url = format!("https://api.github.com/repos/{}", repo_name);
+    Ok(get_json::<Repo>(get(access_token, url.as_str()))
+        .await?
+        .and_then(|r| r.permissions)
+        .map(|p| p.admin || p.maintain || p.push)
+        .unwrap_or(false))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SponsorResponse {
+    data: Option<SponsorData>,
+    errors: Option<Vec<GraphqlError>>,
+}
 
-    let response = reqwest::Client::new()
+#[derive(Debug, serde::Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SponsorData {
+    repository_owner: Option<SponsorOwner>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SponsorOwner {
+    viewer_is_sponsoring: Option<bool>,
+}
+
+// Note: sponsorships are only available in graphql api, account can be a user or an organization
+// API Docs: https://docs.github.com/en/graphql/reference/interfaces#sponsorable
+pub async fn is_sponsor(access_token: &str, account: &str) -> Result<bool, GithubApiError> {
+    let query = serde_json::json!({
+        "query": "query($login: String!) { repositoryOwner(login: $login) { ... on Sponsorable { viewerIsSponsoring } } }",
+        "variables": { "login": account },
+    });
+    let request = HTTP_CLIENT
+        .post("https://api.github.com/graphql")
+        .header(
+            reqwest::header::AUTHORIZATION,
+            format!("{}{}", "Bearer ", access_token),
+        )
+        .header(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static("bookrafter"),
+        )
+        .json(&query);
+    is_sponsoring(get_json::<SponsorResponse>(request).await?)
+}
+
+// Note: graphql answers its errors with 200, a missing `viewerIsSponsoring` next to them is not
+// known instead of not sponsoring
+fn is_sponsoring(response: Option<SponsorResponse>) -> Result<bool, GithubApiError> {
+    if let Some(errors) = response.as_ref().and_then(|r| r.errors.as_ref()) {
+        if !errors.is_empty() {
+            return Err(GithubApiError::Upstream {
+                status: 200,
+                body: errors
+                    .iter()
+                    .map(|e| e.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            });
+        }
+    }
+    Ok(response
+        .and_then(|r| r.data)
+        .and_then(|d| d.repository_owner)
+        .and_then(|o| o.viewer_is_sponsoring)
+        .unwrap_or(false))
+}
+
+// API Docs: https://docs.github.com/en/rest/users/followers?apiVersion=2022-11-28#check-if-a-person-is-followed-by-the-authenticated-user
+pub async fn is_follower(access_token: &str, account: &str) -> Result<bool, GithubApiError> {
+    let url = format!("https://api.github.com/user/following/{}", account);
+    check_no_content(access_token, url.as_str()).await
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GithubUserError {
+    #[error("GithubApiError: {}", _0)]
+    Api(#[from] GithubApiError),
+    #[error("PrimaryEmailNotFound")]
+    PrimaryEmailNotFound,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct GithubUser {
+    pub id: i64,
+    pub login: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GithubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+fn get(access_token: &str, url: &str) -> reqwest::RequestBuilder {
+    HTTP_CLIENT
         .get(url)
         .header(
             reqwest::header::AUTHORIZATION,
@@ -70,29 +480,56 @@ pub async fn is_repo_starred(
             reqwest::header::ACCEPT,
             reqwest::header::HeaderValue::from_static("application/vnd.github+json"),
         )
-        .send()
-        .await;
-
-    // todo: return appropriate error response
-    match response {
-        Ok(r) => {
-            if r.status() == reqwest::StatusCode::NO_CONTENT {
-                Ok(true)
-            } else {
-                eprintln!(
-                    "api error repo_name:{},  status {}, body: {:?} ",
-                    repo_name,
-                    r.status(),
-                    r.text().await
-                );
-                Ok(false)
-            }
-        }
-        Err(e) => {
-            eprintln!("api error: {}", e);
-            Ok(false)
-        }
-    }
+}
+
+// API Docs: https://docs.github.com/en/rest/users/users?apiVersion=2022-11-28#get-the-authenticated-user
+pub async fn get_user(access_token: &str) -> Result<GithubUser, GithubUserError> {
+    Ok(call(get(access_token, "https://api.github.com/user"))
+        .await?
+        .json::<GithubUser>()
+        .await
+        .map_err(GithubApiError::from)?)
+}
+
+// API Docs: https://docs.github.com/en/rest/users/emails?apiVersion=2022-11-28#list-email-addresses-for-the-authenticated-user
+pub async fn get_primary_email(access_token: &str) -> Result<String, GithubUserError> {
+    call(get(access_token, "https://api.github.com/user/emails"))
+        .await?
+        .json::<Vec<GithubEmail>>()
+        .await
+        .map_err(GithubApiError::from)?
+        .into_iter()
+        .find(|e| e.primary && e.verified)
+        .map(|e| e.email)
+        .ok_or(GithubUserError::PrimaryEmailNotFound)
+}
+
+// Note: revokes the grant, all the tokens of the app for the user, used when user unlinks github
+// API Docs: https://docs.github.com/en/rest/apps/oauth-applications?apiVersion=2022-11-28#delete-an-app-authorization
+pub async fn delete_grant(
+    client_id: &str,
+    client_secret: &str,
+    access_token: &str,
+) -> Result<(), GithubUserError> {
+    let request = HTTP_CLIENT
+        .delete(format!(
+            "https://api.github.com/applications/{}/grant",
+            client_id
+        ))
+        .basic_auth(client_id, Some(client_secret))
+        .header(
+            reqwest::header::USER_AGENT,
+            reqwest::header::HeaderValue::from_static("bookrafter"),
+        )
+        .header(
+            reqwest::header::ACCEPT,
+            reqwest::header::HeaderValue::from_static("application/vnd.github+json"),
+        )
+        .json(&serde_json::json!({ "access_token": access_token }));
+
+    // Note: grant is already revoked by the user from github settings
+    optional(call(request).await)?;
+    Ok(())
 }
 
 // pub async fn user_starred_repo(access_token: &str) {}
@@ -106,3 +543,113 @@ Note: Which API is better to use for the stars check
 API for giving the star
 - https://docs.github.com/en/rest/activity/starring?apiVersion=2022-11-28#star-a-repository-for-the-authenticated-user
  */
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn backoff_keeps_the_latest_wait() {
+        let backoff = super::Backoff::default();
+        let now = std::time::Instant::now();
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(backoff.remaining(now), None);
+        backoff.wait(now, secs(30));
+        assert_eq!(backoff.remaining(now + secs(10)), Some(secs(20)));
+        // Note: a shorter wait asked later does not cut the current one
+        backoff.wait(now + secs(10), secs(5));
+        assert_eq!(backoff.remaining(now + secs(10)), Some(secs(20)));
+        backoff.wait(now + secs(10), secs(60));
+        assert_eq!(backoff.remaining(now + secs(10)), Some(secs(60)));
+        assert_eq!(backoff.remaining(now + secs(70)), None);
+    }
+
+    #[test]
+    fn sponsoring_of_graphql_answers() {
+        let answer = |json: serde_json::Value| {
+            super::is_sponsoring(Some(serde_json::from_value(json).unwrap()))
+        };
+        let sponsoring = serde_json::json!({
+            "data": {"repositoryOwner": {"viewerIsSponsoring": true}},
+        });
+        assert!(answer(sponsoring).unwrap());
+        let owner = serde_json::json!({"data": {"repositoryOwner": null}});
+        assert!(!answer(owner).unwrap());
+        assert!(!super::is_sponsoring(None).unwrap());
+        let errors = serde_json::json!({
+            "data": null,
+            "errors": [{"type": "FORBIDDEN", "message": "Resource not accessible by integration"}],
+        });
+        assert!(matches!(
+            answer(errors),
+            Err(super::GithubApiError::Upstream { status: 200, body })
+                if body == "Resource not accessible by integration"
+        ));
+    }
+
+    #[test]
+    fn starred_and_collaborator_values() {
+        for key in ["github-starred", "github-repo-collaborator"] {
+            for value in [
+                "fastn-stack/fastn",
+                "AbrarNitk/auth.rs",
+                "a/.github",
+                "a/b_c-d",
+            ] {
+                assert!(super::is_valid_value(key, value), "{} {}", key, value);
+            }
+            for value in [
+                "fastn-stack",
+                "a/b/c",
+                "a/..",
+                "a/.",
+                "../user",
+                "a/b?per_page=1",
+                "a/b#c",
+                "-a/b",
+                "a/",
+                "/b",
+                "a%2Fb/c",
+            ] {
+                assert!(!super::is_valid_value(key, value), "{} {}", key, value);
+            }
+        }
+    }
+
+    #[test]
+    fn team_member_values() {
+        let key = "github-team-member";
+        for value in ["fastn-stack/core", "a/b_c-1"] {
+            assert!(super::is_valid_value(key, value), "{}", value);
+        }
+        for value in ["fastn-stack", "a/b/c", "a/b.c", "a/..", "a/", "a/b?x=1"] {
+            assert!(!super::is_valid_value(key, value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn login_values() {
+        for key in ["github-org-member", "github-sponsor", "github-follower"] {
+            for value in ["fastn-stack", "AbrarNitk", "a1", &"a".repeat(39)] {
+                assert!(super::is_valid_value(key, value), "{} {}", key, value);
+            }
+            for value in [
+                "",
+                "-a",
+                "a/b",
+                "a.b",
+                "a_b",
+                "..",
+                "a?b",
+                "a b",
+                &"a".repeat(40),
+            ] {
+                assert!(!super::is_valid_value(key, value), "{} {}", key, value);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_key_is_invalid() {
+        assert!(!super::is_valid_value("github-unknown", "a"));
+        assert!(!super::is_valid_value("gitlab-group", "a"));
+    }
+}
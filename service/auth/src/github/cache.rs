@@ -0,0 +1,119 @@
+// Note: in memory ttl cache of the github checks, the calling service asks for the identities on
+// every page view. Keys are made with the sha256 of the access token, so raw tokens are never
+// kept in the cache
+
+// satisfied identities are cached longer than the unsatisfied, user may star the repo any time
+pub const POSITIVE_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+pub const NEGATIVE_TTL: std::time::Duration = std::time::Duration::from_secs(60);
+
+// expired entries are removed when the cache reaches this, if none of them is expired the ones
+// expiring first are evicted, so the cache never holds more
+const MAX_ENTRIES: usize = 10_000;
+
+pub struct TtlCache<V> {
+    entries: std::sync::Mutex<std::collections::HashMap<String, (V, std::time::Instant)>>,
+    max_entries: usize,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub fn new() -> Self {
+        Self::with_max_entries(MAX_ENTRIES)
+    }
+
+    pub fn with_max_entries(max_entries: usize) -> Self {
+        Self {
+            entries: std::sync::Mutex::new(std::collections::HashMap::new()),
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(key) {
+            Some((value, expires_at)) if *expires_at > std::time::Instant::now() => {
+                Some(value.clone())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, key: String, value: V, ttl: std::time::Duration) {
+        let now = std::time::Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.max_entries && !entries.contains_key(key.as_str()) {
+            entries.retain(|_, (_, expires_at)| *expires_at > now);
+            while entries.len() >= self.max_entries {
+                let first = entries
+                    .iter()
+                    .min_by_key(|(_, (_, expires_at))| *expires_at)
+                    .map(|(key, _)| key.clone());
+                match first {
+                    Some(first) => entries.remove(first.as_str()),
+                    None => break,
+                };
+            }
+        }
+        entries.insert(key, (value, now + ttl));
+    }
+}
+
+impl<V: Clone> Default for TtlCache<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// (token-hash, predicate) -> satisfied or not
+pub static IDENTITIES: once_cell::sync::Lazy<TtlCache<bool>> =
+    once_cell::sync::Lazy::new(TtlCache::new);
+
+// token-hash -> github login of the token's user
+pub static USER_LOGINS: once_cell::sync::Lazy<TtlCache<String>> =
+    once_cell::sync::Lazy::new(TtlCache::new);
+
+pub fn token_hash(access_token: &str) -> String {
+    crate::crypto::hash(access_token)
+}
+
+pub fn identity_key(access_token: &str, identity: &crate::Identity) -> String {
+    format!("{}|{}", token_hash(access_token), identity)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn expired_entries_are_not_returned() {
+        let cache = super::TtlCache::new();
+        cache.insert("a".to_string(), true, std::time::Duration::from_secs(60));
+        cache.insert("b".to_string(), false, std::time::Duration::ZERO);
+        assert_eq!(cache.get("a"), Some(true));
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("c"), None);
+    }
+
+    #[test]
+    fn cache_does_not_grow_beyond_max() {
+        let minute = std::time::Duration::from_secs(60);
+        let cache = super::TtlCache::with_max_entries(3);
+        cache.insert("a".to_string(), 1, minute);
+        cache.insert("b".to_string(), 2, minute * 2);
+        cache.insert("c".to_string(), 3, std::time::Duration::ZERO);
+        // Note: the expired entry goes first
+        cache.insert("d".to_string(), 4, minute * 3);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.entries.lock().unwrap().len(), 3);
+        // then the one expiring first
+        cache.insert("e".to_string(), 5, minute * 4);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(
+            ["b", "d", "e"].map(|key| cache.get(key)),
+            [Some(2), Some(4), Some(5)]
+        );
+        // updating an entry evicts nothing
+        cache.insert("b".to_string(), 6, minute);
+        assert_eq!(
+            ["b", "d", "e"].map(|key| cache.get(key)),
+            [Some(6), Some(4), Some(5)]
+        );
+    }
+}
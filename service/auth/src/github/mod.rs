@@ -1,10 +1,17 @@
 use hyper::body::Incoming;
 
 pub mod apis;
+pub mod cache;
+pub mod token;
 
 pub const CALLBACK_URL: &str = "/auth/github/callback/";
+pub const PROVIDER: &str = "github";
+// Note: the `state` sent to github at login, the callback is taken only with the same state, so a
+// callback of another browser's login (login CSRF) is rejected
+pub const STATE_COOKIE: &str = "auth-github-state";
+const STATE_COOKIE_MAX_AGE: u64 = 10 * 60;
 
-static CLIENT_ID: once_cell::sync::Lazy<oauth2::ClientId> = {
+pub(crate) static CLIENT_ID: once_cell::sync::Lazy<oauth2::ClientId> = {
     once_cell::sync::Lazy::new(|| {
         oauth2::ClientId::new(match std::env::var("GITHUB_CLIENT_ID") {
             Ok(val) => val,
@@ -13,7 +20,7 @@ static CLIENT_ID: once_cell::sync::Lazy<oauth2::ClientId> = {
     })
 };
 
-static CLIENT_SECRET: once_cell::sync::Lazy<oauth2::ClientSecret> = {
+pub(crate) static CLIENT_SECRET: once_cell::sync::Lazy<oauth2::ClientSecret> = {
     once_cell::sync::Lazy::new(|| {
         oauth2::ClientSecret::new(match std::env::var("GITHUB_CLIENT_SECRET") {
             Ok(val) => val,
@@ -22,8 +29,29 @@ static CLIENT_SECRET: once_cell::sync::Lazy<oauth2::ClientSecret> = {
     })
 };
 
-pub(crate) fn client() -> oauth2::basic::BasicClient {
-    oauth2::basic::BasicClient::new(
+// Note: GitHub App user-to-server tokens send `refresh_token_expires_in` along with the
+// `refresh_token`, OAuth App tokens have none of them and never expire
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct GithubTokenFields {
+    pub refresh_token_expires_in: Option<u64>,
+}
+
+impl oauth2::ExtraTokenFields for GithubTokenFields {}
+
+pub type GithubTokenResponse =
+    oauth2::StandardTokenResponse<GithubTokenFields, oauth2::basic::BasicTokenType>;
+
+pub(crate) type GithubClient = oauth2::Client<
+    oauth2::basic::BasicErrorResponse,
+    GithubTokenResponse,
+    oauth2::basic::BasicTokenType,
+    oauth2::basic::BasicTokenIntrospectionResponse,
+    oauth2::StandardRevocableToken,
+    oauth2::basic::BasicRevocationErrorResponse,
+>;
+
+pub(crate) fn client() -> GithubClient {
+    GithubClient::new(
         CLIENT_ID.to_owned(),
         Some(CLIENT_SECRET.to_owned()),
         oauth2::AuthUrl::new("https://github.com/login/oauth/authorize".to_string()).unwrap(),
@@ -35,29 +63,26 @@ pub(crate) fn client() -> oauth2::basic::BasicClient {
 }
 
 pub(crate) async fn login(req: &hyper::Request<Incoming>) -> Result<hyper::Response<Vec<u8>>, ()> {
-    // TODO: add the next url as query parameter and platform is github
-
     let host = req
         .headers()
         .get(hyper::header::HOST)
         .map(|x| x.to_str().unwrap().to_string())
         .unwrap();
-    println!("host: {}, uri: {:?}", host, req.uri());
 
     let scheme = match req.uri().scheme() {
         Some(scheme) => scheme.to_string(),
         None => "https".to_string(),
     };
 
-    println!("scheme {:?}", scheme);
     let callback_url = format!("{}://{}{}", scheme, host, CALLBACK_URL);
-    println!("callback url {}", callback_url);
+    tracing::debug!(
+        message = "github login",
+        callback_url = callback_url.as_str()
+    );
     let client = client().set_redirect_uri(oauth2::RedirectUrl::new(callback_url).unwrap());
 
-    let (mut authorize_url, _token) = client
-        // this is the state that we are passing, and will receive in the callback url
-        // todo: we must store it some where to validate the request, but for that we will need
-        // user tacker to implement
+    let (mut authorize_url, state) = client
+        // the state comes back in the callback url, it is kept in the state cookie till then
         .authorize_url(oauth2::CsrfToken::new_random)
         .add_scope(oauth2::Scope::new("user:email".to_string()))
         .add_scope(oauth2::Scope::new("read:user".to_string()))
@@ -75,82 +100,195 @@ pub(crate) async fn login(req: &hyper::Request<Incoming>) -> Result<hyper::Respo
     let location = hyper::header::HeaderValue::from_bytes(authorize_url.as_str().as_bytes())
         .expect("something went wrong");
     resp.headers_mut().insert(hyper::header::LOCATION, location);
-    *resp.status_mut() = hyper::StatusCode::PERMANENT_REDIRECT;
+    resp.headers_mut().append(
+        hyper::header::SET_COOKIE,
+        hyper::header::HeaderValue::from_str(&state_cookie(state.secret()))
+            .expect("failed to create the cookie header"),
+    );
+    // Note: page to go after the login, e.g. the oidc authorize url
+    let next = url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+        .find(|(k, _)| k.eq("next"))
+        .map(|(_, v)| v.into_owned());
+    if let Some(next) = next.as_deref().and_then(crate::session::safe_next) {
+        resp.headers_mut().append(
+            hyper::header::SET_COOKIE,
+            hyper::header::HeaderValue::from_str(&crate::session::next_cookie(next))
+                .expect("failed to create the cookie header"),
+        );
+    }
+    // Note: not permanent, browsers cache the permanent redirects and would skip the next cookie
+    *resp.status_mut() = hyper::StatusCode::TEMPORARY_REDIRECT;
     Ok(resp)
 }
 
-// TODO: remove the unwraps
+// Note: SameSite=Lax, the callback is the top level navigation from github and must carry it
+fn state_cookie(state: &str) -> String {
+    format!(
+        "{}={}; HttpOnly; Secure; SameSite=Lax; Path=/auth/github/; Max-Age={}",
+        STATE_COOKIE, state, STATE_COOKIE_MAX_AGE
+    )
+}
+
+fn clear_state_cookie() -> String {
+    format!(
+        "{}=; HttpOnly; Secure; SameSite=Lax; Path=/auth/github/; Max-Age=0",
+        STATE_COOKIE
+    )
+}
+
+// state of the callback url is the one of the login in this browser
+fn state_matches(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+    state: Option<&String>,
+) -> bool {
+    use subtle::ConstantTimeEq;
+    match (crate::session::cookie_value(headers, STATE_COOKIE), state) {
+        (Some(expected), Some(state)) if !expected.is_empty() => {
+            expected.as_bytes().ct_eq(state.as_bytes()).into()
+        }
+        _ => false,
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CallbackError {
+    #[error("HostHeaderNotFound")]
+    HostHeaderNotFound,
+    #[error("InvalidState")]
+    InvalidState,
+    #[error("CodeNotFound")]
+    CodeNotFound,
+    #[error("TokenExchangeError: {}", _0)]
+    TokenExchange(String),
+    #[error("GithubUserError: {}", _0)]
+    GithubUser(#[from] apis::GithubUserError),
+    #[error("TokenError: {}", _0)]
+    Token(#[from] token::TokenError),
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+    #[error("JWTError: {}", _0)]
+    Jwt(#[from] crate::jwt::JWTError),
+}
+
+impl CallbackError {
+    // reason of the failed audit events
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            CallbackError::HostHeaderNotFound => "host_not_found",
+            CallbackError::InvalidState => "invalid_state",
+            CallbackError::CodeNotFound => "code_not_found",
+            CallbackError::TokenExchange(_) => "token_exchange",
+            CallbackError::GithubUser(_) => "github",
+            CallbackError::Token(_) => "token",
+            CallbackError::DB(_) => "db",
+            CallbackError::Jwt(_) => "jwt",
+        }
+    }
+}
+
 pub(crate) async fn callback(
     req: &hyper::Request<Incoming>,
-) -> Result<hyper::Response<Vec<u8>>, ()> {
-    use oauth2::TokenResponse;
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, CallbackError> {
+    let result = link(req, db_pool).await;
+    crate::audit::record(
+        crate::audit::Record {
+            event: crate::audit::Event::GithubLogin,
+            outcome: crate::audit::outcome(&result, CallbackError::reason),
+            user_id: result.as_ref().ok().map(|(user_id, _, _)| *user_id),
+            email: result.as_ref().ok().map(|(_, email, _)| email.as_str()),
+        },
+        &crate::audit::Client::from_request(req),
+        db_pool,
+    )
+    .await;
+    result.map(|(_, _, response)| response)
+}
+
+// user and the email of the github account, with the login response
+// TODO: remove the unwraps
+async fn link(
+    req: &hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<(i64, String, hyper::Response<Vec<u8>>), CallbackError> {
     let host = req
         .headers()
         .get(hyper::header::HOST)
-        .map(|x| x.to_str().unwrap().to_string())
-        .unwrap();
+        .and_then(|x| x.to_str().ok())
+        .ok_or(CallbackError::HostHeaderNotFound)?
+        .to_string();
     let scheme = match req.uri().scheme() {
         Some(scheme) => scheme.to_string(),
         None => "https".to_string(),
     };
-    println!("scheme: {}, host: {:?}", scheme, host);
-    let query = url::form_urlencoded::parse(req.uri().query().unwrap().as_bytes())
+    let query = url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
         .into_owned()
         .collect::<std::collections::HashMap<String, String>>();
-    println!("callback params: {:?}", query);
-    let code = query.get("code").unwrap();
+    if !state_matches(req.headers(), query.get("state")) {
+        return Err(CallbackError::InvalidState);
+    }
+    let code = query.get("code").ok_or(CallbackError::CodeNotFound)?;
     let auth_url = format!("{}://{}{}", scheme, host, CALLBACK_URL);
     let client = client().set_redirect_uri(oauth2::RedirectUrl::new(auth_url).unwrap());
-    match client
+    let token = client
         .exchange_code(oauth2::AuthorizationCode::new(code.to_owned()))
         .request_async(oauth2::reqwest::async_http_client)
-        .await
-    {
-        Ok(token) => {
-            let t = token.access_token().secret();
-            println!("get the token: {}", t);
-            let cookie_value = format!(
-                "auth-gt-token={}; HttpOnly; Path=/; Domain={}",
-                t,
-                sanitize_port(host.as_str())
-            );
-            println!("cookie: {}", cookie_value);
-            let mut response = hyper::Response::builder()
-                .status(hyper::StatusCode::PERMANENT_REDIRECT)
-                .body(vec![])
-                .unwrap();
-
-            let cookie_header = hyper::header::HeaderValue::from_str(&cookie_value)
-                .expect("failed to create the cookie header");
-            response
-                .headers_mut()
-                .insert(hyper::header::SET_COOKIE, cookie_header);
-            response.headers_mut().insert(
-                hyper::header::LOCATION,
-                hyper::header::HeaderValue::from_str("/").unwrap(),
-            );
-            return Ok(response);
-            // creating the cookies to set in the response
-        }
-        Err(e) => {
-            println!("token request error: {}", e);
-            let mut resp = hyper::Response::new(vec![]);
-            *resp.status_mut() = hyper::StatusCode::PERMANENT_REDIRECT;
-            return Ok(resp);
-        }
-    }
+        .await;
+    crate::metrics::github(
+        "oauth_token",
+        match token {
+            Ok(_) => "success",
+            Err(_) => "error",
+        },
+    );
+    let token = token.map_err(|e| CallbackError::TokenExchange(e.to_string()))?;
+    let access_token = oauth2::TokenResponse::access_token(&token).secret();
 
-    // todo: set the callback url as redirect url, or else redirect it to the home page
-    // todo: check the state same as we send in redirect uri as query param
-    // todo: check the scope we asked for all the
-    // we will get the code here send call to the github in exchange with te access_token
-}
+    // Note: github login is an account as well, get or create the user with github primary email
+    let github_user = apis::get_user(access_token).await?;
+    let email = apis::get_primary_email(access_token).await?;
+    let user_id = db::user::upsert_with_email(email.as_str(), db_pool).await?;
+    token::save(
+        user_id,
+        github_user.id.to_string().as_str(),
+        &token,
+        db_pool,
+    )
+    .await?;
+    tracing::info!(message = "github account linked", user_id = user_id);
+
+    // Note: browser only gets our session, github token never leaves the server
+    let permissions = crate::authorization::permissions(user_id, db_pool).await?;
+    let jwt_token = crate::jwt::create_jwt(user_id.to_string(), &permissions)?;
+    db::user::create_token(user_id, jwt_token.as_str(), db_pool).await?;
 
-fn sanitize_port(host: &str) -> String {
-    match host.split_once(":") {
-        Some((domain, _port)) => domain.to_string(),
-        None => host.to_string(),
+    let next = crate::session::next_from_headers(req.headers())
+        .filter(|next| crate::session::safe_next(next).is_some())
+        .unwrap_or_else(|| "/".to_string());
+    let mut response = hyper::Response::new(vec![]);
+    *response.status_mut() = hyper::StatusCode::TEMPORARY_REDIRECT;
+    for cookie in crate::session::login_cookies(&jwt_token, &host) {
+        response.headers_mut().append(
+            hyper::header::SET_COOKIE,
+            hyper::header::HeaderValue::from_str(&cookie)
+                .expect("failed to create the cookie header"),
+        );
     }
+    for cookie in [crate::session::clear_next_cookie(), clear_state_cookie()] {
+        response.headers_mut().append(
+            hyper::header::SET_COOKIE,
+            hyper::header::HeaderValue::from_str(&cookie)
+                .expect("failed to create the cookie header"),
+        );
+    }
+    response.headers_mut().insert(
+        hyper::header::LOCATION,
+        hyper::header::HeaderValue::from_str(next.as_str())
+            .unwrap_or_else(|_| hyper::header::HeaderValue::from_static("/")),
+    );
+    Ok((user_id, email, response))
+
+    // todo: check the scope we asked for all the
 }
 
 // #[derive(serde::Deserialize)]
@@ -159,3 +297,33 @@ fn sanitize_port(host: &str) -> String {
 //     pub state: String,
 //     pub next: Option<String>,
 // }
+
+#[cfg(test)]
+mod tests {
+    fn headers(cookie: &str) -> hyper::HeaderMap {
+        let mut headers = hyper::HeaderMap::new();
+        headers.insert(hyper::header::COOKIE, cookie.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn state_of_the_callback() {
+        let state = "c3RhdGUgb2YgdGhlIGxvZ2lu".to_string();
+        let cookie = format!("auth-next=%2F; {}={}", super::STATE_COOKIE, state);
+        assert!(super::state_matches(&headers(&cookie), Some(&state)));
+        // Note: login of another browser, or a callback without the login
+        assert!(!super::state_matches(
+            &headers(&cookie),
+            Some(&"b3RoZXI".to_string())
+        ));
+        assert!(!super::state_matches(&headers(&cookie), None));
+        assert!(!super::state_matches(
+            &headers("auth-next=%2F"),
+            Some(&state)
+        ));
+        assert!(!super::state_matches(
+            &headers(&format!("{}=", super::STATE_COOKIE)),
+            Some(&String::new())
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+// Note: stored github tokens, OAuth App tokens never expire, GitHub App user-to-server tokens
+// expire in 8 hours and can be refreshed with the refresh token which itself lives for 6 months
+// Docs: https://docs.github.com/en/apps/creating-github-apps/authenticating-with-a-github-app/refreshing-user-access-tokens
+
+// refresh the access token a bit before it expires, so it does not expire in between the api calls
+const EXPIRY_LEEWAY_SECS: i64 = 60;
+
+// Note: github refresh tokens are used once, two requests of the same user refreshing at once would
+// have one of them rejected and the token forgotten. Refreshes of a user wait for each other on one
+// of these locks, picked by the user id
+const REFRESH_LOCKS: usize = 64;
+static REFRESH: once_cell::sync::Lazy<Vec<tokio::sync::Mutex<()>>> =
+    once_cell::sync::Lazy::new(|| (0..REFRESH_LOCKS).map(|_| Default::default()).collect());
+
+#[derive(thiserror::Error, Debug)]
+pub enum TokenError {
+    #[error("CryptoError: {}", _0)]
+    Crypto(#[from] crate::crypto::CryptoError),
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+    #[error("RefreshTokenError: {}", _0)]
+    Refresh(String),
+    #[error("RevokeTokenError: {}", _0)]
+    Revoke(#[from] super::apis::GithubUserError),
+    // access token is expired and can not be refreshed, user has to login with github again
+    #[error("TokenExpired")]
+    Expired,
+}
+
+impl TokenError {
+    // reason of the failed audit events
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            TokenError::Crypto(_) => "crypto",
+            TokenError::DB(_) => "db",
+            TokenError::Refresh(_) => "refresh",
+            TokenError::Revoke(_) => "github",
+            TokenError::Expired => "expired",
+        }
+    }
+}
+
+fn expires_at(secs: Option<u64>) -> Option<chrono::DateTime<chrono::Utc>> {
+    secs.map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs as i64))
+}
+
+pub(crate) async fn save(
+    user_id: i64,
+    github_user_id: &str,
+    token: &super::GithubTokenResponse,
+    db_pool: &db::pg::DbPool,
+) -> Result<(), TokenError> {
+    use oauth2::TokenResponse;
+    let access_token = crate::crypto::encrypt(token.access_token().secret())?;
+    let refresh_token = match token.refresh_token() {
+        Some(refresh_token) => Some(crate::crypto::encrypt(refresh_token.secret())?),
+        None => None,
+    };
+    db::provider_token::upsert(
+        user_id,
+        super::PROVIDER,
+        github_user_id,
+        db::provider_token::ProviderTokenUpsert {
+            access_token,
+            refresh_token,
+            expires_at: expires_at(token.expires_in().map(|d| d.as_secs())),
+            refresh_token_expires_at: expires_at(token.extra_fields().refresh_token_expires_in),
+        },
+        db_pool,
+    )
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Usable {
+    AccessToken,
+    Refresh,
+    Expired,
+}
+
+// what to do with the stored token at `now`, tokens without the expiry (OAuth App) never expire
+fn usable(
+    stored: &db::provider_token::ProviderTokenDB,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Usable {
+    match stored.expires_at {
+        Some(expires_at) if expires_at - chrono::Duration::seconds(EXPIRY_LEEWAY_SECS) <= now => {}
+        _ => return Usable::AccessToken,
+    }
+    match (&stored.refresh_token, stored.refresh_token_expires_at) {
+        (Some(_), Some(refresh_expires_at)) if refresh_expires_at <= now => Usable::Expired,
+        (Some(_), _) => Usable::Refresh,
+        (None, _) => Usable::Expired,
+    }
+}
+
+// Returns the usable access token of the user, refreshing it first if it is about to expire.
+// `Ok(None)` if the user has not linked the github account
+pub(crate) async fn access_token(
+    user_id: i64,
+    db_pool: &db::pg::DbPool,
+) -> Result<Option<String>, TokenError> {
+    let stored = match db::provider_token::get(user_id, super::PROVIDER, db_pool).await? {
+        Some(stored) => stored,
+        None => return Ok(None),
+    };
+    if usable(&stored, chrono::Utc::now()) == Usable::AccessToken {
+        return Ok(Some(crate::crypto::decrypt(&stored.access_token)?));
+    }
+
+    let _refreshing = REFRESH[user_id.rem_euclid(REFRESH_LOCKS as i64) as usize]
+        .lock()
+        .await;
+    // Note: read again, the token may be refreshed by the request which had the lock before
+    let stored = match db::provider_token::get(user_id, super::PROVIDER, db_pool).await? {
+        Some(stored) => stored,
+        None => return Ok(None),
+    };
+    let refresh_token = match (usable(&stored, chrono::Utc::now()), &stored.refresh_token) {
+        (Usable::AccessToken, _) => return Ok(Some(crate::crypto::decrypt(&stored.access_token)?)),
+        (Usable::Refresh, Some(refresh_token)) => crate::crypto::decrypt(refresh_token)?,
+        _ => return Err(TokenError::Expired),
+    };
+
+    let token = super::client()
+        .exchange_refresh_token(&oauth2::RefreshToken::new(refresh_token))
+        .request_async(oauth2::reqwest::async_http_client)
+        .await;
+    let token = match token {
+        Ok(token) => token,
+        // Note: github rejects the refresh token with an error body, sometimes with 200 status. The
+        // other instances of the service do not share the lock, one of them may have refreshed it
+        Err(
+            oauth2::RequestTokenError::ServerResponse(_) | oauth2::RequestTokenError::Parse(..),
+        ) => {
+            return match db::provider_token::get(user_id, super::PROVIDER, db_pool).await? {
+                Some(current) if current.updated_on > stored.updated_on => {
+                    Ok(Some(crate::crypto::decrypt(&current.access_token)?))
+                }
+                _ => Err(TokenError::Expired),
+            };
+        }
+        Err(e) => return Err(TokenError::Refresh(e.to_string())),
+    };
+    save(user_id, &stored.provider_user_id, &token, db_pool).await?;
+    tracing::info!(message = "github token refreshed", user_id = user_id);
+    Ok(Some(
+        oauth2::TokenResponse::access_token(&token)
+            .secret()
+            .to_owned(),
+    ))
+}
+
+// Note: forgetting the token, used when github says the token is revoked or expired
+pub(crate) async fn forget(user_id: i64, db_pool: &db::pg::DbPool) -> Result<(), TokenError> {
+    db::provider_token::delete(user_id, super::PROVIDER, db_pool).await?;
+    Ok(())
+}
+
+// Revokes the grant of the user on github, all the tokens of our app for the user become invalid,
+// and removes the stored token
+pub(crate) async fn revoke(user_id: i64, db_pool: &db::pg::DbPool) -> Result<(), TokenError> {
+    let stored = match db::provider_token::get(user_id, super::PROVIDER, db_pool).await? {
+        Some(stored) => stored,
+        None => return Ok(()),
+    };
+    let access_token = crate::crypto::decrypt(&stored.access_token)?;
+    super::apis::delete_grant(
+        super::CLIENT_ID.as_str(),
+        super::CLIENT_SECRET.secret(),
+        access_token.as_str(),
+    )
+    .await?;
+    forget(user_id, db_pool).await
+}
+
+#[cfg(test)]
+mod tests {
+    fn stored(
+        expires_in: Option<i64>,
+        refresh_token: bool,
+        refresh_expires_in: Option<i64>,
+    ) -> db::provider_token::ProviderTokenDB {
+        let now = chrono::Utc::now();
+        let at = |secs: i64| now + chrono::Duration::seconds(secs);
+        db::provider_token::ProviderTokenDB {
+            id: 1,
+            user_id: 1,
+            provider: "github".to_string(),
+            provider_user_id: "1".to_string(),
+            access_token: vec![],
+            refresh_token: refresh_token.then(Vec::new),
+            expires_at: expires_in.map(at),
+            refresh_token_expires_at: refresh_expires_in.map(at),
+            created_on: now,
+            updated_on: now,
+        }
+    }
+
+    #[test]
+    fn refresh_or_expiry_of_tokens() {
+        use super::Usable;
+        let now = chrono::Utc::now();
+        let cases = [
+            // OAuth App tokens never expire
+            (stored(None, false, None), Usable::AccessToken),
+            (stored(Some(3600), true, Some(86400)), Usable::AccessToken),
+            // refreshed a bit before it expires
+            (stored(Some(30), true, Some(86400)), Usable::Refresh),
+            (stored(Some(-10), true, Some(86400)), Usable::Refresh),
+            (stored(Some(-10), true, None), Usable::Refresh),
+            (stored(Some(-10), true, Some(-1)), Usable::Expired),
+            (stored(Some(-10), false, None), Usable::Expired),
+        ];
+        for (stored, expected) in cases {
+            assert_eq!(
+                super::usable(&stored, now),
+                expected,
+                "{:?} {:?}",
+                stored.expires_at,
+                stored.refresh_token_expires_at
+            );
+        }
+    }
+}
@@ -1,6 +1,33 @@
-// const BEARER: &str = "Bearer ";
-const JWT_EXPIRY: u64 = 30 * 24 * 60 * 60; // 30 days
-const JWT_SECRET: &[u8] = b"secret";
+const BEARER: &str = "Bearer ";
+pub(crate) const JWT_EXPIRY: u64 = 30 * 24 * 60 * 60; // 30 days
+const MIN_SECRET_LEN: usize = 32;
+
+// Note: HS512 secret of the session and access tokens in env `JWT_SECRET`, at least 32 bytes,
+// generate with `openssl rand -base64 48`. The services verifying our tokens with
+// `auth_client::KeySource::Secret` are given the same secret
+pub(crate) static JWT_SECRET: once_cell::sync::Lazy<Vec<u8>> =
+    once_cell::sync::Lazy::new(|| load_secret().unwrap_or_else(|e| panic!("{}", e)));
+
+fn load_secret() -> Result<Vec<u8>, String> {
+    let secret = match std::env::var("JWT_SECRET") {
+        Ok(secret) => secret,
+        Err(_) if cfg!(test) => return Ok(b"secret of the unit tests of the auth crate".to_vec()),
+        Err(e) => return Err(format!("JWT_SECRET not found in env {}", e)),
+    };
+    if secret.len() < MIN_SECRET_LEN {
+        return Err(format!(
+            "JWT_SECRET must be at least {} bytes",
+            MIN_SECRET_LEN
+        ));
+    }
+    Ok(secret.into_bytes())
+}
+
+// Note: called at the startup, the service does not start without the secret instead of failing
+// the first login
+pub fn check_secret() -> Result<(), String> {
+    load_secret().map(|_| ())
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum JWTError {
@@ -10,42 +37,383 @@ pub enum JWTError {
     TokenHeaderFormat,
     #[error("TokenHeaderNotFound")]
     TokenHeaderNotFound,
+    #[error("InvalidSubject")]
+    InvalidSubject,
+    #[error("NotSessionToken")]
+    NotSessionToken,
+    #[error("InvalidTokenType")]
+    InvalidTokenType,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct Claims {
-    sub: String,
-    iat: usize,
-    exp: usize,
+    pub sub: String,
+    pub iat: usize,
+    pub exp: usize,
+    // Note: set only in the access tokens issued to the oidc clients, session tokens have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    // Note: grant type, `client-credentials` for the tokens of the machine clients, their `sub` is
+    // the client id instead of the user id
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gty: Option<String>,
+    // roles of the user when the token is issued, see `authorization::Caller`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+    // Note: the client the access token is issued to, session tokens have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+}
+
+const CLIENT_CREDENTIALS_GTY: &str = "client-credentials";
+// Note: `typ` header of the access tokens (RFC 9068), session tokens have the default `JWT`, so
+// that one is never taken for the other though both are signed with the secret
+pub const ACCESS_TOKEN_TYP: &str = "at+jwt";
+
+// who the token is issued to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    User(i64),
+    Client(String),
+    // access token the user granted to a third-party oidc client, it is not the user's own session
+    // and the resources accept it only if they trust the client
+    Delegated {
+        user_id: i64,
+        client_id: String,
+    },
+    // user's personal api key, it is not a jwt, see `authenticate::authenticate`
+    ApiKey {
+        user_id: i64,
+        key_id: i64,
+        scope: String,
+    },
+}
+
+impl Claims {
+    pub fn principal(&self) -> Result<Principal, JWTError> {
+        if let Some(CLIENT_CREDENTIALS_GTY) = self.gty.as_deref() {
+            return Ok(Principal::Client(self.sub.clone()));
+        }
+        let user_id = self
+            .sub
+            .parse::<i64>()
+            .map_err(|_| JWTError::InvalidSubject)?;
+        Ok(match self.client_id {
+            Some(ref client_id) => Principal::Delegated {
+                user_id,
+                client_id: client_id.clone(),
+            },
+            None => Principal::User(user_id),
+        })
+    }
 }
 
-pub fn create_jwt(uid: String) -> Result<String, JWTError> {
+// Note: session token of the user, it carries the user's roles and scopes
+pub fn create_jwt(
+    uid: String,
+    permissions: &crate::authorization::Permissions,
+) -> Result<String, JWTError> {
     let now = jsonwebtoken::get_current_timestamp();
     let claims = Claims {
         sub: uid,
         iat: now as usize,
         exp: (now + JWT_EXPIRY) as usize,
+        client_id: None,
+        scope: Some(permissions.scopes.join(" ")).filter(|s| !s.is_empty()),
+        gty: None,
+        roles: permissions.roles.clone(),
+        aud: None,
     };
     let jwt = jsonwebtoken::encode(
         &jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512),
         &claims,
-        &jsonwebtoken::EncodingKey::from_secret(JWT_SECRET),
+        &jsonwebtoken::EncodingKey::from_secret(JWT_SECRET.as_slice()),
     )?;
     Ok(jwt)
 }
 
+// Note: user of the session or access token, or the machine client of the client credentials token
 pub fn decode_jwt(
     headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+) -> Result<Principal, JWTError> {
+    claims_from_headers(headers)?.principal()
+}
+
+// Note: the authorization header, or the session cookie of the browsers, their state changing
+// requests are to be checked with `session::verify_csrf`
+pub fn claims_from_headers(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+) -> Result<Claims, JWTError> {
+    decode_claims(token_from_headers(headers)?.as_str())
+}
+
+// token of the authorization header, with its `Bearer `, or of the session cookie
+pub fn token_from_headers(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
 ) -> Result<String, JWTError> {
     let header = match headers.get(hyper::header::AUTHORIZATION) {
         Some(h) => h,
-        None => return Err(JWTError::TokenHeaderNotFound),
+        None => {
+            return crate::session::cookie_value(headers, crate::session::SESSION_COOKIE)
+                .ok_or(JWTError::TokenHeaderNotFound)
+        }
     };
-    let token = header.to_str().map_err(|_| JWTError::TokenHeaderFormat)?; // Note: all the chars in the header token should be ascii
-    let decode_claims = jsonwebtoken::decode::<Claims>(
+    // Note: all the chars in the header token should be ascii
+    let token = header.to_str().map_err(|_| JWTError::TokenHeaderFormat)?;
+    Ok(token.to_string())
+}
+
+pub fn decode_token(token: &str) -> Result<String, JWTError> {
+    Ok(decode_claims(token)?.sub)
+}
+
+pub fn decode_claims(token: &str) -> Result<Claims, JWTError> {
+    let token = token.strip_prefix(BEARER).unwrap_or(token);
+    let decoded = jsonwebtoken::decode::<Claims>(
         token,
-        &jsonwebtoken::DecodingKey::from_secret(JWT_SECRET),
+        &jsonwebtoken::DecodingKey::from_secret(JWT_SECRET.as_slice()),
         &jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS512),
     )?;
-    Ok(decode_claims.claims.sub)
+    check_type(decoded.header.typ.as_deref(), &decoded.claims)?;
+    Ok(decoded.claims)
+}
+
+// Note: access tokens must have their `typ` and the client as `aud`, the session tokens neither
+fn check_type(typ: Option<&str>, claims: &Claims) -> Result<(), JWTError> {
+    let valid = match claims.client_id {
+        Some(ref client_id) => {
+            typ == Some(ACCESS_TOKEN_TYP) && claims.aud.as_deref() == Some(client_id.as_str())
+        }
+        None => typ != Some(ACCESS_TOKEN_TYP) && claims.aud.is_none(),
+    };
+    match valid {
+        true => Ok(()),
+        false => Err(JWTError::InvalidTokenType),
+    }
+}
+
+// Note: access token issued to an oidc client for the user, verified by `/oauth/userinfo`
+pub fn create_access_token(
+    uid: String,
+    client_id: &str,
+    scope: &str,
+    roles: &[String],
+    expiry: u64,
+) -> Result<String, JWTError> {
+    let now = jsonwebtoken::get_current_timestamp();
+    let claims = Claims {
+        sub: uid,
+        iat: now as usize,
+        exp: (now + expiry) as usize,
+        client_id: Some(client_id.to_string()),
+        scope: Some(scope.to_string()),
+        gty: None,
+        roles: roles.to_vec(),
+        aud: Some(client_id.to_string()),
+    };
+    encode_access_token(&claims)
+}
+
+// Note: token of the machine client issued with the client credentials grant, there is no user
+pub fn create_client_credentials_token(
+    client_id: &str,
+    scope: &str,
+    expiry: u64,
+) -> Result<String, JWTError> {
+    let now = jsonwebtoken::get_current_timestamp();
+    let claims = Claims {
+        sub: client_id.to_string(),
+        iat: now as usize,
+        exp: (now + expiry) as usize,
+        client_id: Some(client_id.to_string()),
+        scope: Some(scope.to_string()),
+        gty: Some(CLIENT_CREDENTIALS_GTY.to_string()),
+        roles: vec![],
+        aud: Some(client_id.to_string()),
+    };
+    encode_access_token(&claims)
+}
+
+fn encode_access_token(claims: &Claims) -> Result<String, JWTError> {
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512);
+    header.typ = Some(ACCESS_TOKEN_TYP.to_string());
+    Ok(jsonwebtoken::encode(
+        &header,
+        claims,
+        &jsonwebtoken::EncodingKey::from_secret(JWT_SECRET.as_slice()),
+    )?)
+}
+
+// Note: ID tokens are signed with RS256 so that the clients can verify them with the public key
+// from `/oauth/jwks`, PEM (PKCS#1 or PKCS#8) of the private key is read from the path in env
+// `OIDC_SIGNING_KEY_PATH`, generate with `openssl genpkey -algorithm RSA -out oidc.pem`
+struct SigningKey {
+    kid: String,
+    encoding_key: jsonwebtoken::EncodingKey,
+    // base64url big-endian modulus and exponent of the public key
+    n: String,
+    e: String,
+}
+
+static SIGNING_KEY: once_cell::sync::Lazy<SigningKey> =
+    once_cell::sync::Lazy::new(|| load_signing_key().unwrap_or_else(|e| panic!("{}", e)));
+
+fn load_signing_key() -> Result<SigningKey, String> {
+    use rsa::pkcs1::DecodeRsaPrivateKey;
+    use rsa::pkcs8::DecodePrivateKey;
+    use rsa::traits::PublicKeyParts;
+
+    let path = std::env::var("OIDC_SIGNING_KEY_PATH")
+        .map_err(|e| format!("OIDC_SIGNING_KEY_PATH not found in env {}", e))?;
+    let pem = std::fs::read_to_string(path.as_str())
+        .map_err(|e| format!("failed to read the oidc signing key {}: {}", path, e))?;
+    let private_key = rsa::RsaPrivateKey::from_pkcs8_pem(pem.as_str())
+        .or_else(|_| rsa::RsaPrivateKey::from_pkcs1_pem(pem.as_str()))
+        .map_err(|_| "OIDC signing key must be a RSA private key".to_string())?;
+    let n = base64_url(&private_key.n().to_bytes_be());
+    let e = base64_url(&private_key.e().to_bytes_be());
+    Ok(SigningKey {
+        kid: thumbprint(n.as_str(), e.as_str()),
+        encoding_key: jsonwebtoken::EncodingKey::from_rsa_pem(pem.as_bytes())
+            .map_err(|_| "OIDC signing key must be a RSA private key".to_string())?,
+        n,
+        e,
+    })
+}
+
+// Note: readiness check of the keys, a session token is signed and verified with the secret and
+// the oidc signing key is loaded from its path again, so that a broken key file is reported
+// instead of panicking at the first oidc request
+pub fn check_keys() -> Result<(), String> {
+    let token = create_jwt("0".to_string(), &Default::default()).map_err(|e| e.to_string())?;
+    decode_claims(token.as_str()).map_err(|e| e.to_string())?;
+    load_signing_key().map(|_| ())
+}
+
+fn base64_url(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+// RFC 7638 JWK thumbprint, used as the `kid`
+fn thumbprint(n: &str, e: &str) -> String {
+    use sha2::Digest;
+    let jwk = format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n);
+    base64_url(&sha2::Sha256::digest(jwk.as_bytes()))
+}
+
+pub fn jwks() -> serde_json::Value {
+    serde_json::json!({
+        "keys": [{
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "kid": SIGNING_KEY.kid,
+            "n": SIGNING_KEY.n,
+            "e": SIGNING_KEY.e,
+        }]
+    })
+}
+
+#[derive(serde::Serialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub iat: u64,
+    pub exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+pub fn create_id_token(claims: &IdTokenClaims) -> Result<String, JWTError> {
+    let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some(SIGNING_KEY.kid.clone());
+    Ok(jsonwebtoken::encode(
+        &header,
+        claims,
+        &SIGNING_KEY.encoding_key,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn principal_of_tokens() {
+        let user = super::decode_claims(
+            &super::create_jwt("42".to_string(), &Default::default()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(user.principal().unwrap(), super::Principal::User(42));
+        let client = super::create_client_credentials_token("job", "ai:read", 60).unwrap();
+        let client = super::decode_claims(format!("Bearer {}", client).as_str()).unwrap();
+        assert_eq!(
+            client.principal().unwrap(),
+            super::Principal::Client("job".to_string())
+        );
+        let delegated =
+            super::create_access_token("42".to_string(), "app", "openid", &[], 60).unwrap();
+        let delegated = super::decode_claims(delegated.as_str()).unwrap();
+        assert_eq!(
+            delegated.principal().unwrap(),
+            super::Principal::Delegated {
+                user_id: 42,
+                client_id: "app".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn token_type_is_checked() {
+        let encode = |typ: Option<&str>, claims: serde_json::Value| {
+            let mut header = jsonwebtoken::Header::new(jsonwebtoken::Algorithm::HS512);
+            header.typ = typ.map(String::from);
+            jsonwebtoken::encode(
+                &header,
+                &claims,
+                &jsonwebtoken::EncodingKey::from_secret(super::JWT_SECRET.as_slice()),
+            )
+            .unwrap()
+        };
+        let exp = jsonwebtoken::get_current_timestamp() + 60;
+        // an access token without its typ, or of another audience
+        for token in [
+            encode(
+                Some("JWT"),
+                serde_json::json!({"sub": "42", "iat": 0, "exp": exp, "client_id": "app", "aud": "app"}),
+            ),
+            encode(
+                Some(super::ACCESS_TOKEN_TYP),
+                serde_json::json!({"sub": "42", "iat": 0, "exp": exp, "client_id": "app", "aud": "other"}),
+            ),
+            // a session token with the typ of the access tokens
+            encode(
+                Some(super::ACCESS_TOKEN_TYP),
+                serde_json::json!({"sub": "42", "iat": 0, "exp": exp}),
+            ),
+        ] {
+            assert!(matches!(
+                super::decode_claims(token.as_str()),
+                Err(super::JWTError::InvalidTokenType)
+            ));
+        }
+    }
+
+    #[test]
+    fn rfc7638_thumbprint() {
+        // example key of https://www.rfc-editor.org/rfc/rfc7638#section-3.1
+        let n = "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw";
+        assert_eq!(
+            super::thumbprint(n, "AQAB"),
+            "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs"
+        );
+    }
 }
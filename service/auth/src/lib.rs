@@ -1,14 +1,25 @@
+pub mod api_key;
+pub mod audit;
+pub mod authenticate;
+pub mod authorization;
 pub mod communication;
 pub mod controller;
+pub mod crypto;
 pub mod error;
+pub mod forward_auth;
 pub mod get_identities;
 mod github;
 pub mod http;
 pub mod jwt;
+pub mod metrics;
+pub mod oidc;
 pub mod otp;
+pub mod session;
+pub mod user;
 pub mod utils;
 
-pub use get_identities::Identity;
+pub use auth_types::expression;
+pub use auth_types::Identity;
 
 pub(crate) static BREVO_API_KEY: once_cell::sync::Lazy<String> = {
     once_cell::sync::Lazy::new(|| match std::env::var("BREVO_API_KEY") {
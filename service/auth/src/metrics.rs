@@ -0,0 +1,126 @@
+// Note: metrics of the auth flows in the default prometheus registry, the service exposes all of
+// them at `/metrics`. Labels are fixed sets of values, never the emails, tokens or the github paths
+static OTP_SENT: once_cell::sync::Lazy<prometheus::IntCounter> = once_cell::sync::Lazy::new(|| {
+    prometheus::register_int_counter!("auth_otp_sent_total", "OTPs sent by email")
+        .expect("auth_otp_sent_total is registered once")
+});
+
+static OTP_RESENT: once_cell::sync::Lazy<prometheus::IntCounter> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter!("auth_otp_resent_total", "OTPs sent again by email")
+            .expect("auth_otp_resent_total is registered once")
+    });
+
+static OTP_VERIFIED: once_cell::sync::Lazy<prometheus::IntCounter> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter!("auth_otp_verified_total", "OTPs verified")
+            .expect("auth_otp_verified_total is registered once")
+    });
+
+static OTP_FAILED: once_cell::sync::Lazy<prometheus::IntCounterVec> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter_vec!(
+            "auth_otp_failed_total",
+            "Failed send, resend and verify of the OTPs by the reason",
+            &["operation", "reason"]
+        )
+        .expect("auth_otp_failed_total is registered once")
+    });
+
+static EMAIL_LATENCY: once_cell::sync::Lazy<prometheus::Histogram> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_histogram!(
+            "auth_email_send_seconds",
+            "Latency of the email provider api",
+            vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]
+        )
+        .expect("auth_email_send_seconds is registered once")
+    });
+
+static EMAIL_ERRORS: once_cell::sync::Lazy<prometheus::IntCounterVec> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter_vec!(
+            "auth_email_errors_total",
+            "Failed calls of the email provider api, `request` did not get a response and `status` \
+             got a non 2xx one",
+            &["kind"]
+        )
+        .expect("auth_email_errors_total is registered once")
+    });
+
+static GITHUB_CALLS: once_cell::sync::Lazy<prometheus::IntCounterVec> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter_vec!(
+            "auth_github_api_calls_total",
+            "Calls of the github rest api and the oauth token exchange by the outcome",
+            &["api", "outcome"]
+        )
+        .expect("auth_github_api_calls_total is registered once")
+    });
+
+// the counters without labels are exported from the start instead of after the first event
+pub fn init() {
+    once_cell::sync::Lazy::force(&OTP_SENT);
+    once_cell::sync::Lazy::force(&OTP_RESENT);
+    once_cell::sync::Lazy::force(&OTP_VERIFIED);
+    once_cell::sync::Lazy::force(&EMAIL_LATENCY);
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Otp {
+    Send,
+    Resend,
+    Verify,
+}
+
+pub(crate) fn otp<T>(operation: Otp, result: &Result<T, crate::otp::OtpError>) {
+    match (operation, result) {
+        (Otp::Send, Ok(_)) => OTP_SENT.inc(),
+        (Otp::Resend, Ok(_)) => OTP_RESENT.inc(),
+        (Otp::Verify, Ok(_)) => OTP_VERIFIED.inc(),
+        (operation, Err(err)) => {
+            let operation = match operation {
+                Otp::Send => "send",
+                Otp::Resend => "resend",
+                Otp::Verify => "verify",
+            };
+            OTP_FAILED
+                .with_label_values(&[operation, err.reason()])
+                .inc()
+        }
+    }
+}
+
+pub(crate) fn email(latency: std::time::Duration, error: Option<&str>) {
+    EMAIL_LATENCY.observe(latency.as_secs_f64());
+    if let Some(kind) = error {
+        EMAIL_ERRORS.with_label_values(&[kind]).inc();
+    }
+}
+
+pub(crate) fn github(api: &str, outcome: &str) {
+    GITHUB_CALLS.with_label_values(&[api, outcome]).inc();
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn otp_outcomes() {
+        let before = super::OTP_FAILED
+            .with_label_values(&["verify", "expired"])
+            .get();
+        super::otp::<()>(
+            super::Otp::Verify,
+            &Err(crate::otp::OtpError::Expired("expired".to_string())),
+        );
+        let sent = super::OTP_SENT.get();
+        super::otp(super::Otp::Send, &Ok(()));
+        assert_eq!(
+            super::OTP_FAILED
+                .with_label_values(&["verify", "expired"])
+                .get(),
+            before + 1
+        );
+        assert_eq!(super::OTP_SENT.get(), sent + 1);
+    }
+}
@@ -0,0 +1,147 @@
+use hyper::body::Incoming;
+
+pub(crate) async fn authorize(
+    req: &hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let query = super::query(req);
+    let param = |name: &str| query.get(name).filter(|v| !v.is_empty());
+
+    // Note: until the client and the redirect uri are verified the error is shown to the user, it
+    // must not be redirected to an unknown uri
+    let client_id = param("client_id")
+        .ok_or_else(|| super::OidcError::InvalidRequest("client_id is required".to_string()))?;
+    let client = db::oauth::get_client(client_id, db_pool)
+        .await?
+        .ok_or(super::OidcError::InvalidClient)?;
+    let redirect_uri = param("redirect_uri")
+        .ok_or_else(|| super::OidcError::InvalidRequest("redirect_uri is required".to_string()))?;
+    if !client.is_redirect_uri_allowed(redirect_uri) {
+        return Err(super::OidcError::InvalidRequest(
+            "redirect_uri is not registered for the client".to_string(),
+        ));
+    }
+    let state = param("state").map(|s| s.as_str());
+    let redirect_error =
+        |error: &str, description: &str| redirect(redirect_uri, error, description, state);
+
+    if param("response_type").map(|s| s.as_str()) != Some("code") {
+        return Ok(redirect_error(
+            "unsupported_response_type",
+            "only code response_type is supported",
+        ));
+    }
+    let scope = param("scope").map(|s| s.as_str()).unwrap_or_default();
+    if !super::has_scope(scope, "openid") {
+        return Ok(redirect_error("invalid_scope", "openid scope is required"));
+    }
+    if let Some(s) = super::disallowed_scope(&client, scope) {
+        return Ok(redirect_error(
+            "invalid_scope",
+            format!("scope {} is not allowed for the client", s).as_str(),
+        ));
+    }
+    let code_challenge = param("code_challenge");
+    let code_challenge_method = match (code_challenge, param("code_challenge_method")) {
+        (Some(_), Some(method)) if method.eq("S256") => Some("S256"),
+        // Note: plain method gives no protection if the code is stolen, so it is not supported
+        (Some(_), _) => {
+            return Ok(redirect_error(
+                "invalid_request",
+                "code_challenge_method must be S256",
+            ))
+        }
+        (None, _) if client.is_public() => {
+            return Ok(redirect_error(
+                "invalid_request",
+                "code_challenge is required for public clients",
+            ))
+        }
+        (None, _) => None,
+    };
+
+    let user_id = match crate::session::from_headers(req.headers()) {
+        Some(token) => crate::session::active_user_id(token.as_str(), db_pool).await?,
+        None => None,
+    };
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(login_redirect(req)),
+    };
+
+    let code = crate::crypto::random_token(32);
+    db::oauth::create_code(
+        db::oauth::NewOAuthCode {
+            code_hash: crate::crypto::hash(code.as_str()),
+            client_id: client.id,
+            user_id,
+            redirect_uri: redirect_uri.to_string(),
+            scope: scope.to_string(),
+            nonce: param("nonce").cloned(),
+            code_challenge: code_challenge.cloned(),
+            code_challenge_method: code_challenge_method.map(String::from),
+            expires_at: chrono::Utc::now()
+                + chrono::Duration::seconds(super::AUTHORIZATION_CODE_TTL_SECS),
+        },
+        db_pool,
+    )
+    .await?;
+    tracing::info!(
+        message = "oidc authorization code issued",
+        user_id = user_id,
+        client_id = client.client_id
+    );
+
+    let mut params = url::form_urlencoded::Serializer::new(String::new());
+    params.append_pair("code", code.as_str());
+    if let Some(state) = state {
+        params.append_pair("state", state);
+    }
+    Ok(found(
+        append_query(redirect_uri, params.finish().as_str()).as_str(),
+    ))
+}
+
+pub(super) fn login_redirect(req: &hyper::Request<Incoming>) -> hyper::Response<Vec<u8>> {
+    let next = req
+        .uri()
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or_default();
+    found(crate::session::login_url(next).as_str())
+}
+
+// authorization error response of RFC 6749 section 4.1.2.1
+fn redirect(
+    redirect_uri: &str,
+    error: &str,
+    description: &str,
+    state: Option<&str>,
+) -> hyper::Response<Vec<u8>> {
+    let mut params = url::form_urlencoded::Serializer::new(String::new());
+    params.append_pair("error", error);
+    params.append_pair("error_description", description);
+    if let Some(state) = state {
+        params.append_pair("state", state);
+    }
+    found(append_query(redirect_uri, params.finish().as_str()).as_str())
+}
+
+// Note: registered redirect uri may have its own query
+fn append_query(uri: &str, query: &str) -> String {
+    match uri.contains('?') {
+        true => format!("{}&{}", uri, query),
+        false => format!("{}?{}", uri, query),
+    }
+}
+
+fn found(location: &str) -> hyper::Response<Vec<u8>> {
+    let mut response = hyper::Response::new(vec![]);
+    *response.status_mut() = hyper::StatusCode::FOUND;
+    response.headers_mut().insert(
+        hyper::header::LOCATION,
+        hyper::header::HeaderValue::from_str(location)
+            .unwrap_or_else(|_| hyper::header::HeaderValue::from_static("/")),
+    );
+    response
+}
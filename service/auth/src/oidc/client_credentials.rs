@@ -0,0 +1,52 @@
+// Note: RFC 6749 section 4.4 client credentials grant for the backend jobs and services, the token
+// has the client id as `sub` and no user, see `jwt::Principal`
+pub(crate) async fn token(
+    headers: &hyper::HeaderMap,
+    form: &std::collections::HashMap<String, String>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let param = |name: &str| form.get(name).filter(|v| !v.is_empty()).map(|s| s.as_str());
+    let (client_id, client_secret) =
+        super::credentials(headers, param("client_id"), param("client_secret"))?;
+    let client = match db::machine_client::get(client_id.as_str(), db_pool).await? {
+        Some(client)
+            if client_secret.is_some_and(|secret| {
+                crate::crypto::verify_hash(secret.as_str(), client.client_secret_hash.as_str())
+            }) =>
+        {
+            client
+        }
+        _ => return Err(super::OidcError::InvalidClient),
+    };
+
+    // Note: all the allowed scopes if the client does not ask for any
+    let scope = param("scope").unwrap_or(client.allowed_scopes.as_str());
+    if let Some(s) = scope
+        .split_whitespace()
+        .find(|s| !super::has_scope(client.allowed_scopes.as_str(), s))
+    {
+        return Err(super::OidcError::InvalidScope(format!(
+            "scope {} is not allowed for the client",
+            s
+        )));
+    }
+    let access_token = crate::jwt::create_client_credentials_token(
+        client.client_id.as_str(),
+        scope,
+        super::ACCESS_TOKEN_EXPIRY,
+    )?;
+    tracing::info!(
+        message = "client credentials token issued",
+        client_id = client.client_id,
+        client_name = client.name
+    );
+    Ok(super::token::no_store(super::json(
+        serde_json::json!({
+            "access_token": access_token,
+            "token_type": "Bearer",
+            "expires_in": super::ACCESS_TOKEN_EXPIRY,
+            "scope": scope,
+        }),
+        hyper::StatusCode::OK,
+    )))
+}
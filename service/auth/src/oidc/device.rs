@@ -0,0 +1,297 @@
+// Note: RFC 8628 device authorization grant for the cli tools, the cli shows the user code and the
+// `/device` url, the user approves it in the browser after the OTP or GitHub login, meanwhile the
+// cli polls the token endpoint with the device code
+use hyper::body::Incoming;
+
+pub const GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+const DEVICE_CODE_TTL_SECS: i64 = 10 * 60;
+const POLL_INTERVAL_SECS: i32 = 5;
+// added to the interval each time the device polls faster than it
+const SLOW_DOWN_SECS: i32 = 5;
+// no vowels so that the codes do not spell words, and no lookalike letters
+const USER_CODE_CHARS: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LEN: usize = 8;
+const DEVICE_PAGE: &str = "/device";
+
+// Note: the pending user codes could be guessed on the `/device` page, the invalid codes sent from
+// an ip or by a user are limited in a window, after it the page answers 429 till the window ends
+const MAX_INVALID_CODES: u32 = 5;
+const INVALID_CODES_WINDOW: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+static INVALID_CODES: once_cell::sync::Lazy<Attempts> =
+    once_cell::sync::Lazy::new(|| Attempts::new(MAX_INVALID_CODES, INVALID_CODES_WINDOW));
+
+pub(crate) async fn code(
+    req: hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let (parts, body) = req.into_parts();
+    let form = super::form(&parts.extensions, body).await?;
+    let param = |name: &str| form.get(name).filter(|v| !v.is_empty()).map(|s| s.as_str());
+
+    let client = super::authenticate_client(
+        &parts.headers,
+        param("client_id"),
+        param("client_secret"),
+        db_pool,
+    )
+    .await?;
+    let scope = param("scope").unwrap_or("openid");
+    if let Some(s) = super::disallowed_scope(&client, scope) {
+        return Err(super::OidcError::InvalidScope(format!(
+            "scope {} is not allowed for the client",
+            s
+        )));
+    }
+
+    let device_code = crate::crypto::random_token(32);
+    let user_code = user_code();
+    db::device::create(
+        db::device::NewDeviceCode {
+            device_code_hash: crate::crypto::hash(device_code.as_str()),
+            user_code: user_code.clone(),
+            client_id: client.id,
+            scope: scope.to_string(),
+            poll_interval: POLL_INTERVAL_SECS,
+            expires_at: chrono::Utc::now() + chrono::Duration::seconds(DEVICE_CODE_TTL_SECS),
+        },
+        db_pool,
+    )
+    .await?;
+
+    let verification_uri = format!("{}{}", super::ISSUER.as_str(), DEVICE_PAGE);
+    let display_code = display(user_code.as_str());
+    Ok(super::json(
+        serde_json::json!({
+            "device_code": device_code,
+            "user_code": display_code,
+            "verification_uri": verification_uri,
+            "verification_uri_complete": format!("{}?user_code={}", verification_uri, display_code),
+            "expires_in": DEVICE_CODE_TTL_SECS,
+            "interval": POLL_INTERVAL_SECS,
+        }),
+        hyper::StatusCode::OK,
+    ))
+}
+
+// Note: page to type the user code and approve it, the user logs in first
+pub(crate) async fn page(
+    req: &hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    if session_user(req.headers(), db_pool).await?.is_none() {
+        return Ok(super::authorize::login_redirect(req));
+    }
+    let bytes = tokio::fs::read("service/auth/device.html")
+        .await
+        .map_err(|e| super::OidcError::ReadBody(e.to_string()))?;
+    Ok(router::html(bytes))
+}
+
+// approve or deny of the user code by the logged in user, sent by the `/device` page
+pub(crate) async fn verify(
+    req: hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let ip = crate::audit::Client::from_request(&req).ip;
+    let (parts, body) = req.into_parts();
+    let user_id = session_user(&parts.headers, db_pool)
+        .await?
+        .ok_or(super::OidcError::LoginRequired)?;
+    if !crate::session::verify_csrf(&parts.method, &parts.headers) {
+        return Err(super::OidcError::InvalidCsrf);
+    }
+    let keys = [
+        Some(format!("user:{}", user_id)),
+        ip.map(|ip| format!("ip:{}", ip)),
+    ];
+    let now = std::time::Instant::now();
+    if keys
+        .iter()
+        .flatten()
+        .any(|key| INVALID_CODES.is_locked(key, now))
+    {
+        return Err(super::OidcError::TooManyAttempts);
+    }
+    let form = super::form(&parts.extensions, body).await?;
+    let user_code = form
+        .get("user_code")
+        .map(|code| normalize(code))
+        .filter(|code| code.len() == USER_CODE_LEN)
+        .ok_or_else(|| super::OidcError::InvalidRequest("user_code is invalid".to_string()))?;
+    let approve = form.get("action").map(|a| a.as_str()) == Some("approve");
+
+    if !db::device::decide(user_code.as_str(), user_id, approve, db_pool).await? {
+        for key in keys.into_iter().flatten() {
+            INVALID_CODES.fail(key, now);
+        }
+        tracing::info!(message = "oidc device code is invalid", user_id = user_id);
+        return Err(super::OidcError::InvalidRequest(
+            "user_code is invalid or expired".to_string(),
+        ));
+    }
+    tracing::info!(
+        message = "oidc device decided",
+        user_id = user_id,
+        approved = approve
+    );
+    Ok(super::json(
+        serde_json::json!({"approved": approve}),
+        hyper::StatusCode::OK,
+    ))
+}
+
+// Note: user and scope of the approved device, the pending, denied and expired codes are the
+// errors of RFC 8628 section 3.5
+pub(crate) async fn poll(
+    device_code: &str,
+    client: &db::oauth::OAuthClientDB,
+    db_pool: &db::pg::DbPool,
+) -> Result<(i64, String), super::OidcError> {
+    let code = db::device::poll(crate::crypto::hash(device_code).as_str(), db_pool)
+        .await?
+        .ok_or_else(|| super::OidcError::InvalidGrant("device_code is invalid".to_string()))?;
+    if code.client_id != client.id {
+        return Err(super::OidcError::InvalidGrant(
+            "device_code is issued to another client".to_string(),
+        ));
+    }
+    let now = chrono::Utc::now();
+    if code.expires_at <= now {
+        return Err(super::OidcError::ExpiredToken);
+    }
+    match code.status.as_str() {
+        db::device::STATUS_PENDING => {
+            let too_fast = code.last_polled_at.is_some_and(|last| {
+                now - last < chrono::Duration::seconds(code.poll_interval as i64)
+            });
+            if too_fast {
+                db::device::set_poll_interval(
+                    code.id,
+                    code.poll_interval + SLOW_DOWN_SECS,
+                    db_pool,
+                )
+                .await?;
+                return Err(super::OidcError::SlowDown);
+            }
+            Err(super::OidcError::AuthorizationPending)
+        }
+        db::device::STATUS_DENIED => Err(super::OidcError::AccessDenied),
+        db::device::STATUS_APPROVED => match code.user_id {
+            Some(user_id) if db::device::mark_used(code.id, db_pool).await? => {
+                Ok((user_id, code.scope))
+            }
+            _ => Err(super::OidcError::InvalidGrant(
+                "device_code is already used".to_string(),
+            )),
+        },
+        _ => Err(super::OidcError::InvalidGrant(
+            "device_code is already used".to_string(),
+        )),
+    }
+}
+
+// failures of a key in the window from its first failure, in memory of this instance
+struct Attempts {
+    max: u32,
+    window: std::time::Duration,
+    failures: std::sync::Mutex<std::collections::HashMap<String, (u32, std::time::Instant)>>,
+}
+
+impl Attempts {
+    fn new(max: u32, window: std::time::Duration) -> Attempts {
+        Attempts {
+            max,
+            window,
+            failures: Default::default(),
+        }
+    }
+
+    fn is_locked(&self, key: &str, now: std::time::Instant) -> bool {
+        let failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        match failures.get(key) {
+            Some((count, since)) => *count >= self.max && now < *since + self.window,
+            None => false,
+        }
+    }
+
+    fn fail(&self, key: String, now: std::time::Instant) {
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        // Note: the ended windows are dropped once in a while, so the map does not keep every ip
+        if failures.len() >= 10_000 {
+            failures.retain(|_, (_, since)| now < *since + self.window);
+        }
+        let entry = failures.entry(key).or_insert((0, now));
+        if now >= entry.1 + self.window {
+            *entry = (0, now);
+        }
+        entry.0 += 1;
+    }
+}
+
+async fn session_user(
+    headers: &hyper::HeaderMap,
+    db_pool: &db::pg::DbPool,
+) -> Result<Option<i64>, db::DBError> {
+    match crate::session::from_headers(headers) {
+        Some(token) => crate::session::active_user_id(token.as_str(), db_pool).await,
+        None => Ok(None),
+    }
+}
+
+fn user_code() -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..USER_CODE_LEN)
+        .map(|_| USER_CODE_CHARS[rng.gen_range(0..USER_CODE_CHARS.len())] as char)
+        .collect()
+}
+
+// `BCDFGHJK` -> `BCDF-GHJK`
+fn display(user_code: &str) -> String {
+    let (first, second) = user_code.split_at(USER_CODE_LEN / 2);
+    format!("{}-{}", first, second)
+}
+
+// Note: users type the code in any case, with or without the dash
+fn normalize(user_code: &str) -> String {
+    user_code
+        .chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn invalid_codes_lock_the_key_for_the_window() {
+        let minute = std::time::Duration::from_secs(60);
+        let attempts = super::Attempts::new(3, minute);
+        let now = std::time::Instant::now();
+        for _ in 0..2 {
+            attempts.fail("ip:192.0.2.1".to_string(), now);
+        }
+        assert!(!attempts.is_locked("ip:192.0.2.1", now));
+        attempts.fail("ip:192.0.2.1".to_string(), now);
+        assert!(attempts.is_locked("ip:192.0.2.1", now));
+        assert!(attempts.is_locked("ip:192.0.2.1", now + minute / 2));
+        // other keys are not locked
+        assert!(!attempts.is_locked("ip:192.0.2.2", now));
+        assert!(!attempts.is_locked("user:1", now));
+        // a new window starts after the end of the last one
+        assert!(!attempts.is_locked("ip:192.0.2.1", now + minute));
+        attempts.fail("ip:192.0.2.1".to_string(), now + minute);
+        assert!(!attempts.is_locked("ip:192.0.2.1", now + minute));
+    }
+
+    #[test]
+    fn user_code_round_trip() {
+        let code = super::user_code();
+        assert_eq!(code.len(), super::USER_CODE_LEN);
+        let display = super::display(code.as_str());
+        assert_eq!(display.len(), super::USER_CODE_LEN + 1);
+        assert_eq!(super::normalize(display.to_lowercase().as_str()), code);
+        assert_eq!(super::normalize(" bcdf - ghjk "), "BCDFGHJK");
+    }
+}
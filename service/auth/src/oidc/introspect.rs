@@ -0,0 +1,114 @@
+// Note: RFC 7662 token introspection for the resource servers, a confidential client can ask about
+// the access tokens issued to it, the clients with `can_introspect` about any session or access
+// token. A token is active only if its signature and expiry are valid and it is not revoked in
+// `authapp_user_token`, the tokens the client may not see are answered as inactive
+use hyper::body::Incoming;
+
+pub(crate) async fn introspect(
+    req: hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let (parts, body) = req.into_parts();
+    let form = super::form(&parts.extensions, body).await?;
+    let param = |name: &str| form.get(name).filter(|v| !v.is_empty()).map(|s| s.as_str());
+
+    let client = super::authenticate_client(
+        &parts.headers,
+        param("client_id"),
+        param("client_secret"),
+        db_pool,
+    )
+    .await?;
+    if client.is_public() {
+        return Err(super::OidcError::UnauthorizedClient(
+            "public clients can not introspect the tokens".to_string(),
+        ));
+    }
+    let token = param("token")
+        .ok_or_else(|| super::OidcError::InvalidRequest("token is required".to_string()))?;
+
+    let inactive = || super::json(serde_json::json!({"active": false}), hyper::StatusCode::OK);
+    let claims = match crate::jwt::decode_claims(token) {
+        Ok(claims) => claims,
+        Err(_) => return Ok(inactive()),
+    };
+    if !may_introspect(&client, &claims) {
+        tracing::info!(
+            message = "introspection of a token of another client",
+            client_id = client.client_id
+        );
+        return Ok(inactive());
+    }
+    // Note: tokens of the machine clients are not recorded, they are active while the client is
+    let active = match claims.principal() {
+        Ok(crate::jwt::Principal::Client(client_id)) => {
+            db::machine_client::get(client_id.as_str(), db_pool)
+                .await?
+                .is_some()
+        }
+        Ok(crate::jwt::Principal::User(_)) | Ok(crate::jwt::Principal::Delegated { .. }) => {
+            db::user::is_token_active(token, db_pool).await?
+        }
+        // Note: api keys are not jwts, they never decode to it
+        Ok(crate::jwt::Principal::ApiKey { .. }) | Err(_) => false,
+    };
+    if !active {
+        return Ok(inactive());
+    }
+
+    let mut info = serde_json::json!({
+        "active": true,
+        "sub": claims.sub,
+        "exp": claims.exp,
+        "iat": claims.iat,
+        "iss": super::ISSUER.as_str(),
+        "token_type": "Bearer",
+    });
+    if let Some(scope) = claims.scope {
+        info["scope"] = serde_json::json!(scope);
+    }
+    if let Some(client_id) = claims.client_id {
+        info["client_id"] = serde_json::json!(client_id);
+    }
+    if let Some(gty) = claims.gty {
+        info["gty"] = serde_json::json!(gty);
+    }
+    Ok(super::json(info, hyper::StatusCode::OK))
+}
+
+fn may_introspect(client: &db::oauth::OAuthClientDB, claims: &crate::jwt::Claims) -> bool {
+    client.can_introspect || claims.client_id.as_deref() == Some(client.client_id.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn clients_introspect_their_own_tokens() {
+        let mut client = db::oauth::OAuthClientDB {
+            id: 1,
+            client_id: "app".to_string(),
+            client_secret_hash: Some("hash".to_string()),
+            name: "app".to_string(),
+            redirect_uris: serde_json::json!([]),
+            allowed_scopes: "openid".to_string(),
+            active: true,
+            can_introspect: false,
+        };
+        let claims = |token: String| crate::jwt::decode_claims(token.as_str()).unwrap();
+        let own = claims(
+            crate::jwt::create_access_token("42".to_string(), "app", "openid", &[], 60).unwrap(),
+        );
+        let other = claims(
+            crate::jwt::create_access_token("42".to_string(), "other", "openid", &[], 60).unwrap(),
+        );
+        let session =
+            claims(crate::jwt::create_jwt("42".to_string(), &Default::default()).unwrap());
+        assert!(super::may_introspect(&client, &own));
+        assert!(!super::may_introspect(&client, &other));
+        assert!(!super::may_introspect(&client, &session));
+
+        client.can_introspect = true;
+        assert!(super::may_introspect(&client, &other));
+        assert!(super::may_introspect(&client, &session));
+    }
+}
@@ -0,0 +1,335 @@
+// Note: OpenID Connect provider for our other apps, authorization code flow with PKCE
+// Docs: https://openid.net/specs/openid-connect-core-1_0.html
+// Clients are registered with `python manage.py create_oauth_client`, login of the user is the
+// same OTP or GitHub login of this service
+use hyper::body::Incoming;
+
+pub mod authorize;
+pub mod client_credentials;
+pub mod device;
+pub mod introspect;
+pub mod revoke;
+pub mod token;
+pub mod userinfo;
+
+// authorization code has to be exchanged within this
+const AUTHORIZATION_CODE_TTL_SECS: i64 = 5 * 60;
+const ACCESS_TOKEN_EXPIRY: u64 = 60 * 60; // 1 hour
+const ID_TOKEN_EXPIRY: u64 = 60 * 60; // 1 hour
+const SCOPES_SUPPORTED: &[&str] = &["openid", "email", "profile"];
+
+// public url of this service, e.g. `https://auth.example.com`, it is the `iss` of the ID tokens
+pub(crate) static ISSUER: once_cell::sync::Lazy<String> = {
+    once_cell::sync::Lazy::new(|| match std::env::var("OIDC_ISSUER") {
+        Ok(val) => val.trim_end_matches('/').to_string(),
+        Err(e) => panic!("{}{}", "OIDC_ISSUER not found in env ", e),
+    })
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum OidcError {
+    #[error("InvalidRequest: {}", _0)]
+    InvalidRequest(String),
+    #[error("InvalidClient")]
+    InvalidClient,
+    #[error("InvalidGrant: {}", _0)]
+    InvalidGrant(String),
+    #[error("UnauthorizedClient: {}", _0)]
+    UnauthorizedClient(String),
+    #[error("UnsupportedGrantType")]
+    UnsupportedGrantType,
+    #[error("InvalidToken")]
+    InvalidToken,
+    #[error("InvalidScope: {}", _0)]
+    InvalidScope(String),
+    #[error("LoginRequired")]
+    LoginRequired,
+    #[error("InvalidCsrfToken")]
+    InvalidCsrf,
+    // device flow errors of RFC 8628 section 3.5
+    #[error("AuthorizationPending")]
+    AuthorizationPending,
+    #[error("SlowDown")]
+    SlowDown,
+    #[error("AccessDenied")]
+    AccessDenied,
+    #[error("ExpiredToken")]
+    ExpiredToken,
+    #[error("TooManyAttempts")]
+    TooManyAttempts,
+    #[error("BodyReadError: {}", _0)]
+    ReadBody(String),
+    #[error("BodyError: {}", _0)]
+    Body(#[from] router::BodyError),
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+    #[error("JWTError: {}", _0)]
+    Jwt(#[from] crate::jwt::JWTError),
+}
+
+impl OidcError {
+    // error response of RFC 6749 section 5.2
+    fn response(&self) -> hyper::Response<Vec<u8>> {
+        let (status, error, description) = match self {
+            OidcError::InvalidRequest(d) => (
+                hyper::StatusCode::BAD_REQUEST,
+                "invalid_request",
+                d.as_str(),
+            ),
+            OidcError::InvalidClient => (
+                hyper::StatusCode::UNAUTHORIZED,
+                "invalid_client",
+                "client authentication failed",
+            ),
+            OidcError::InvalidGrant(d) => {
+                (hyper::StatusCode::BAD_REQUEST, "invalid_grant", d.as_str())
+            }
+            OidcError::UnauthorizedClient(d) => (
+                hyper::StatusCode::BAD_REQUEST,
+                "unauthorized_client",
+                d.as_str(),
+            ),
+            OidcError::UnsupportedGrantType => (
+                hyper::StatusCode::BAD_REQUEST,
+                "unsupported_grant_type",
+                "grant type is not supported",
+            ),
+            OidcError::InvalidToken => (
+                hyper::StatusCode::UNAUTHORIZED,
+                "invalid_token",
+                "access token is invalid or expired",
+            ),
+            OidcError::InvalidScope(d) => {
+                (hyper::StatusCode::BAD_REQUEST, "invalid_scope", d.as_str())
+            }
+            OidcError::LoginRequired => (
+                hyper::StatusCode::UNAUTHORIZED,
+                "login_required",
+                "login to approve the device",
+            ),
+            OidcError::InvalidCsrf => (
+                hyper::StatusCode::FORBIDDEN,
+                "invalid_request",
+                "csrf token is missing or invalid",
+            ),
+            OidcError::AuthorizationPending => (
+                hyper::StatusCode::BAD_REQUEST,
+                "authorization_pending",
+                "user has not approved the device yet",
+            ),
+            OidcError::SlowDown => (
+                hyper::StatusCode::BAD_REQUEST,
+                "slow_down",
+                "polling too fast, increase the interval by 5 seconds",
+            ),
+            OidcError::AccessDenied => (
+                hyper::StatusCode::BAD_REQUEST,
+                "access_denied",
+                "user denied the device",
+            ),
+            OidcError::ExpiredToken => (
+                hyper::StatusCode::BAD_REQUEST,
+                "expired_token",
+                "device code is expired",
+            ),
+            OidcError::TooManyAttempts => (
+                hyper::StatusCode::TOO_MANY_REQUESTS,
+                "too_many_attempts",
+                "too many invalid codes, try again later",
+            ),
+            OidcError::Body(router::BodyError::TooLarge(_)) => (
+                hyper::StatusCode::PAYLOAD_TOO_LARGE,
+                "invalid_request",
+                "request body is too large",
+            ),
+            OidcError::ReadBody(_) | OidcError::Body(_) | OidcError::DB(_) | OidcError::Jwt(_) => (
+                hyper::StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "server error",
+            ),
+        };
+        let mut response = json(
+            serde_json::json!({"error": error, "error_description": description}),
+            status,
+        );
+        match self {
+            OidcError::InvalidClient => {
+                response.headers_mut().insert(
+                    hyper::header::WWW_AUTHENTICATE,
+                    hyper::header::HeaderValue::from_static("Basic"),
+                );
+            }
+            OidcError::InvalidToken => {
+                response.headers_mut().insert(
+                    hyper::header::WWW_AUTHENTICATE,
+                    hyper::header::HeaderValue::from_static("Bearer error=\"invalid_token\""),
+                );
+            }
+            _ => {}
+        }
+        response
+    }
+}
+
+pub fn register(router: &mut router::Router<db::pg::DbPool>) {
+    router
+        .get("/.well-known/openid-configuration", |_, _| async {
+            respond(Ok(json(discovery(), hyper::StatusCode::OK)))
+        })
+        .get("/oauth/jwks", |_, _| async {
+            respond(Ok(json(crate::jwt::jwks(), hyper::StatusCode::OK)))
+        })
+        .get("/oauth/authorize", |req, db_pool| async move {
+            respond(authorize::authorize(&req, &db_pool).await)
+        })
+        .post("/oauth/token", |req, db_pool| async move {
+            respond(token::token(req, &db_pool).await)
+        })
+        .post("/oauth/device/code", |req, db_pool| async move {
+            respond(device::code(req, &db_pool).await)
+        })
+        .post("/oauth/device/verify", |req, db_pool| async move {
+            respond(device::verify(req, &db_pool).await)
+        })
+        .get("/device", |req, db_pool| async move {
+            respond(device::page(&req, &db_pool).await)
+        })
+        .post("/oauth/introspect", |req, db_pool| async move {
+            respond(introspect::introspect(req, &db_pool).await)
+        })
+        .post("/oauth/revoke", |req, db_pool| async move {
+            respond(revoke::revoke(req, &db_pool).await)
+        })
+        .get("/oauth/userinfo", userinfo)
+        .post("/oauth/userinfo", userinfo);
+}
+
+async fn userinfo(
+    req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    respond(userinfo::userinfo(&req, &db_pool).await)
+}
+
+// Note: errors of the oidc endpoints are answered as RFC 6749 says, they are not server errors of
+// the router
+fn respond(
+    result: Result<hyper::Response<Vec<u8>>, OidcError>,
+) -> Result<hyper::Response<Vec<u8>>, crate::error::AuthError> {
+    match result {
+        Ok(response) => Ok(response),
+        Err(err) => {
+            if err.response().status().is_server_error() {
+                tracing::error!(message = "err:oidc", error = err.to_string());
+            } else {
+                tracing::info!(message = "oidc request rejected", error = err.to_string());
+            }
+            Ok(err.response())
+        }
+    }
+}
+
+fn discovery() -> serde_json::Value {
+    let issuer = ISSUER.as_str();
+    serde_json::json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/oauth/authorize", issuer),
+        "token_endpoint": format!("{}/oauth/token", issuer),
+        "userinfo_endpoint": format!("{}/oauth/userinfo", issuer),
+        "introspection_endpoint": format!("{}/oauth/introspect", issuer),
+        "revocation_endpoint": format!("{}/oauth/revoke", issuer),
+        "jwks_uri": format!("{}/oauth/jwks", issuer),
+        "device_authorization_endpoint": format!("{}/oauth/device/code", issuer),
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code", device::GRANT_TYPE, "client_credentials"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "scopes_supported": SCOPES_SUPPORTED,
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+        "code_challenge_methods_supported": ["S256"],
+        "claims_supported": ["iss", "sub", "aud", "iat", "exp", "nonce", "email", "email_verified", "name"],
+    })
+}
+
+fn json(data: serde_json::Value, status: hyper::StatusCode) -> hyper::Response<Vec<u8>> {
+    let mut response = hyper::Response::new(data.to_string().into_bytes());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn query(req: &hyper::Request<Incoming>) -> std::collections::HashMap<String, String> {
+    url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect()
+}
+
+async fn form(
+    extensions: &hyper::http::Extensions,
+    body: Incoming,
+) -> Result<std::collections::HashMap<String, String>, OidcError> {
+    let body = router::body(extensions, body).await?;
+    Ok(url::form_urlencoded::parse(&body).into_owned().collect())
+}
+
+// Note: confidential clients send the secret with basic auth or in the form, public clients send
+// only the client_id and are bound to the code by PKCE
+pub(crate) async fn authenticate_client(
+    headers: &hyper::HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+    db_pool: &db::pg::DbPool,
+) -> Result<db::oauth::OAuthClientDB, OidcError> {
+    let (client_id, client_secret) = credentials(headers, client_id, client_secret)?;
+    let client = db::oauth::get_client(client_id.as_str(), db_pool)
+        .await?
+        .ok_or(OidcError::InvalidClient)?;
+    match (client.client_secret_hash.as_deref(), client_secret) {
+        (Some(hash), Some(secret)) if crate::crypto::verify_hash(secret.as_str(), hash) => {
+            Ok(client)
+        }
+        (None, None) => Ok(client),
+        _ => Err(OidcError::InvalidClient),
+    }
+}
+
+// client id and secret from the basic auth, or from the form
+fn credentials(
+    headers: &hyper::HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Result<(String, Option<String>), OidcError> {
+    let basic = headers
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD
+                .decode(value.trim())
+                .ok()
+        })
+        .and_then(|value| String::from_utf8(value).ok());
+    Ok(match basic.as_deref().and_then(|v| v.split_once(':')) {
+        Some((id, secret)) => (id.to_string(), Some(secret.to_string())),
+        None => (
+            client_id.ok_or(OidcError::InvalidClient)?.to_string(),
+            client_secret.map(|s| s.to_string()),
+        ),
+    })
+}
+
+// Note: scopes are space separated
+fn has_scope(scope: &str, name: &str) -> bool {
+    scope.split_whitespace().any(|s| s.eq(name))
+}
+
+// first scope of the request which the client is not allowed to ask for
+fn disallowed_scope<'a>(client: &db::oauth::OAuthClientDB, scope: &'a str) -> Option<&'a str> {
+    scope
+        .split_whitespace()
+        .find(|s| !has_scope(client.allowed_scopes.as_str(), s))
+}
@@ -0,0 +1,56 @@
+// Note: RFC 7009 token revocation, a client can revoke only the access tokens issued to it. Unknown,
+// expired or already revoked tokens are answered with 200 as well, the client can not do anything
+// about them
+use hyper::body::Incoming;
+
+pub(crate) async fn revoke(
+    req: hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let audit_client = crate::audit::Client::from_request(&req);
+    let (parts, body) = req.into_parts();
+    let form = super::form(&parts.extensions, body).await?;
+    let param = |name: &str| form.get(name).filter(|v| !v.is_empty()).map(|s| s.as_str());
+
+    let client = super::authenticate_client(
+        &parts.headers,
+        param("client_id"),
+        param("client_secret"),
+        db_pool,
+    )
+    .await?;
+    let token = param("token")
+        .ok_or_else(|| super::OidcError::InvalidRequest("token is required".to_string()))?;
+
+    if let Ok(claims) = crate::jwt::decode_claims(token) {
+        let audit = |outcome| {
+            crate::audit::record(
+                crate::audit::Record {
+                    event: crate::audit::Event::OauthRevoke,
+                    outcome,
+                    user_id: claims.sub.parse().ok(),
+                    email: None,
+                },
+                &audit_client,
+                db_pool,
+            )
+        };
+        if claims.client_id.as_deref() != Some(client.client_id.as_str()) {
+            audit(crate::audit::Outcome::Failure("unauthorized_client")).await;
+            return Err(super::OidcError::UnauthorizedClient(
+                "token is not issued to the client".to_string(),
+            ));
+        }
+        if db::user::revoke_token(token, db_pool).await? {
+            audit(crate::audit::Outcome::Success).await;
+            tracing::info!(
+                message = "oidc access token revoked",
+                user_id = claims.sub,
+                client_id = client.client_id
+            );
+        }
+    }
+    let mut response = hyper::Response::new(vec![]);
+    *response.status_mut() = hyper::StatusCode::OK;
+    Ok(response)
+}
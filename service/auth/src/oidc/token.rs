@@ -0,0 +1,221 @@
+use hyper::body::Incoming;
+
+pub(crate) async fn token(
+    req: hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let (parts, body) = req.into_parts();
+    let form = super::form(&parts.extensions, body).await?;
+    let param = |name: &str| form.get(name).filter(|v| !v.is_empty()).map(|s| s.as_str());
+
+    let grant_type = param("grant_type");
+    // Note: machine clients are not the oidc clients, they are authenticated on their own
+    if grant_type == Some("client_credentials") {
+        return super::client_credentials::token(&parts.headers, &form, db_pool).await;
+    }
+    if grant_type != Some("authorization_code") && grant_type != Some(super::device::GRANT_TYPE) {
+        return Err(super::OidcError::UnsupportedGrantType);
+    }
+    let client = super::authenticate_client(
+        &parts.headers,
+        param("client_id"),
+        param("client_secret"),
+        db_pool,
+    )
+    .await?;
+    if grant_type == Some(super::device::GRANT_TYPE) {
+        let device_code = param("device_code").ok_or_else(|| {
+            super::OidcError::InvalidRequest("device_code is required".to_string())
+        })?;
+        let (user_id, scope) = super::device::poll(device_code, &client, db_pool).await?;
+        return issue(user_id, &client, scope.as_str(), None, db_pool).await;
+    }
+
+    let code = param("code")
+        .ok_or_else(|| super::OidcError::InvalidRequest("code is required".to_string()))?;
+    let code = db::oauth::take_code(crate::crypto::hash(code).as_str(), db_pool)
+        .await?
+        .ok_or_else(|| super::OidcError::InvalidGrant("code is invalid or used".to_string()))?;
+    if code.client_id != client.id {
+        return Err(super::OidcError::InvalidGrant(
+            "code is issued to another client".to_string(),
+        ));
+    }
+    if code.expires_at <= chrono::Utc::now() {
+        return Err(super::OidcError::InvalidGrant(
+            "code is expired".to_string(),
+        ));
+    }
+    if param("redirect_uri") != Some(code.redirect_uri.as_str()) {
+        return Err(super::OidcError::InvalidGrant(
+            "redirect_uri does not match".to_string(),
+        ));
+    }
+    match (code.code_challenge.as_deref(), param("code_verifier")) {
+        (Some(challenge), Some(verifier)) if verify_pkce(challenge, verifier) => {}
+        (Some(_), _) => {
+            return Err(super::OidcError::InvalidGrant(
+                "code_verifier does not match".to_string(),
+            ))
+        }
+        (None, _) if client.is_public() => {
+            return Err(super::OidcError::InvalidGrant(
+                "code_verifier is required".to_string(),
+            ))
+        }
+        (None, _) => {}
+    }
+    issue(
+        code.user_id,
+        &client,
+        code.scope.as_str(),
+        code.nonce,
+        db_pool,
+    )
+    .await
+}
+
+// access token for the client, and ID token if the `openid` scope is granted
+async fn issue(
+    user_id: i64,
+    client: &db::oauth::OAuthClientDB,
+    scope: &str,
+    nonce: Option<String>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let user = match db::user::get(user_id, db_pool).await? {
+        Some(user) if user.active => user,
+        _ => {
+            return Err(super::OidcError::InvalidGrant(
+                "user is inactive".to_string(),
+            ))
+        }
+    };
+    let permissions = crate::authorization::permissions(user.id, db_pool).await?;
+    let scope = granted_scope(client.allowed_scopes.as_str(), &permissions, scope);
+    let scope = scope.as_str();
+    let access_token = crate::jwt::create_access_token(
+        user.id.to_string(),
+        client.client_id.as_str(),
+        scope,
+        &permissions.roles,
+        super::ACCESS_TOKEN_EXPIRY,
+    )?;
+    // Note: recorded to be introspected and revoked by the clients
+    db::user::create_client_token(
+        user.id,
+        access_token.as_str(),
+        client.client_id.as_str(),
+        scope,
+        db_pool,
+    )
+    .await?;
+    tracing::info!(
+        message = "oidc tokens issued",
+        user_id = user.id,
+        client_id = client.client_id
+    );
+
+    let mut response = serde_json::json!({
+        "access_token": access_token,
+        "token_type": "Bearer",
+        "expires_in": super::ACCESS_TOKEN_EXPIRY,
+        "scope": scope,
+    });
+    if super::has_scope(scope, "openid") {
+        response["id_token"] = serde_json::json!(id_token(user, client, scope, nonce)?);
+    }
+    Ok(no_store(super::json(response, hyper::StatusCode::OK)))
+}
+
+// token responses must not be cached, RFC 6749 section 5.1
+pub(super) fn no_store(mut response: hyper::Response<Vec<u8>>) -> hyper::Response<Vec<u8>> {
+    response.headers_mut().insert(
+        hyper::header::CACHE_CONTROL,
+        hyper::header::HeaderValue::from_static("no-store"),
+    );
+    response.headers_mut().insert(
+        hyper::header::PRAGMA,
+        hyper::header::HeaderValue::from_static("no-cache"),
+    );
+    response
+}
+
+fn id_token(
+    user: db::user::UserDB,
+    client: &db::oauth::OAuthClientDB,
+    scope: &str,
+    nonce: Option<String>,
+) -> Result<String, super::OidcError> {
+    let now = jsonwebtoken::get_current_timestamp();
+    let email_scope = super::has_scope(scope, "email");
+    Ok(crate::jwt::create_id_token(&crate::jwt::IdTokenClaims {
+        iss: super::ISSUER.to_string(),
+        sub: user.id.to_string(),
+        aud: client.client_id.clone(),
+        iat: now,
+        exp: now + super::ID_TOKEN_EXPIRY,
+        nonce,
+        // Note: emails are verified by the OTP, or are the verified primary email on GitHub
+        email_verified: (email_scope && user.email.is_some()).then_some(true),
+        email: user.email.filter(|_| email_scope),
+        name: user.name.filter(|_| super::has_scope(scope, "profile")),
+    })?)
+}
+
+// RFC 7636 S256, BASE64URL(SHA256(code_verifier)) == code_challenge
+fn verify_pkce(code_challenge: &str, code_verifier: &str) -> bool {
+    use base64::Engine;
+    use sha2::Digest;
+    let digest = sha2::Sha256::digest(code_verifier.as_bytes());
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(digest)
+        .eq(code_challenge)
+}
+
+// Note: the client gets the oidc scopes and only those of the user's own scopes it asked for,
+// both limited to the scopes the client is allowed now, they may have been cut since the code
+fn granted_scope(
+    allowed_scopes: &str,
+    permissions: &crate::authorization::Permissions,
+    scope: &str,
+) -> String {
+    scope
+        .split_whitespace()
+        .filter(|s| super::has_scope(allowed_scopes, s))
+        .filter(|s| super::SCOPES_SUPPORTED.contains(s) || permissions.has_scope(s))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn pkce_s256() {
+        // example of https://www.rfc-editor.org/rfc/rfc7636#appendix-B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        assert!(super::verify_pkce(
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM",
+            verifier
+        ));
+        assert!(!super::verify_pkce(verifier, verifier));
+    }
+
+    #[test]
+    fn granted_scope_of_client_and_user() {
+        let permissions = crate::authorization::Permissions {
+            roles: vec![],
+            scopes: vec!["books:read".to_string(), "books:write".to_string()],
+        };
+        let granted =
+            |allowed: &str, scope: &str| super::granted_scope(allowed, &permissions, scope);
+        assert_eq!(
+            granted("openid email books:read", "openid email books:read"),
+            "openid email books:read"
+        );
+        // Note: the user's scope and the oidc scope the client is not allowed any more
+        assert_eq!(granted("openid", "openid email books:read"), "openid");
+        // Note: the client's scope which the user does not have
+        assert_eq!(granted("openid admin", "openid admin"), "openid");
+    }
+}
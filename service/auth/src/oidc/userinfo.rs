@@ -0,0 +1,41 @@
+use hyper::body::Incoming;
+
+// Note: claims of the user allowed by the scopes of the access token
+pub(crate) async fn userinfo(
+    req: &hyper::Request<Incoming>,
+    db_pool: &db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, super::OidcError> {
+    let token = req
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(super::OidcError::InvalidToken)?;
+    let claims = crate::jwt::decode_claims(token).map_err(|_| super::OidcError::InvalidToken)?;
+    // Note: only the access tokens issued to the clients, session tokens are not accepted here
+    let scope = match (claims.client_id.as_ref(), claims.scope.as_deref()) {
+        (Some(_), Some(scope)) if super::has_scope(scope, "openid") => scope,
+        _ => return Err(super::OidcError::InvalidToken),
+    };
+    if !db::user::is_token_active(token, db_pool).await? {
+        return Err(super::OidcError::InvalidToken);
+    }
+    let user_id = claims
+        .sub
+        .parse::<i64>()
+        .map_err(|_| super::OidcError::InvalidToken)?;
+    let user = match db::user::get(user_id, db_pool).await? {
+        Some(user) if user.active => user,
+        _ => return Err(super::OidcError::InvalidToken),
+    };
+
+    let mut info = serde_json::json!({"sub": user.id.to_string()});
+    if let (true, Some(email)) = (super::has_scope(scope, "email"), user.email) {
+        info["email"] = serde_json::json!(email);
+        info["email_verified"] = serde_json::json!(true);
+    }
+    if let (true, Some(name)) = (super::has_scope(scope, "profile"), user.name) {
+        info["name"] = serde_json::json!(name);
+    }
+    Ok(super::json(info, hyper::StatusCode::OK))
+}
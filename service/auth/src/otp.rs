@@ -1,3 +1,5 @@
+pub use auth_types::otp::{SendOtpReq, SendOtpRes, VerifyOtpReq, VerifyOtpRes};
+
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub struct OtpBucketItem {
     otp: u32,
@@ -75,28 +77,50 @@ pub enum OtpError {
     JWT(#[from] crate::jwt::JWTError),
 }
 
+impl OtpError {
+    // reason of the failed otp metrics and audit events
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            OtpError::SendMail(_) => "send_mail",
+            OtpError::Serde(_) => "serde",
+            OtpError::DBError(_) => "db",
+            OtpError::OTPNotFound(_) => "not_found",
+            OtpError::Expired(_) => "expired",
+            OtpError::AmbiguousVerificationRequest(_) => "already_verified",
+            OtpError::JWT(_) => "jwt",
+        }
+    }
+}
+
 fn generate_otp() -> u32 {
     use rand::Rng;
     let mut rng = rand::thread_rng();
     rng.gen_range(100000..=999_999)
 }
 
-#[derive(serde::Deserialize)]
-pub struct SendOtpReq {
-    pub email: String,
-    pub phone: Option<String>,
-}
-
-#[derive(serde::Serialize)]
-pub struct SendOtpRes {
-    pub email: String,
-    pub message: String,
-}
-
 pub async fn send_otp(
     otp_req: SendOtpReq,
+    client: &crate::audit::Client,
     db_pool: db::pg::DbPool,
 ) -> Result<SendOtpRes, OtpError> {
+    let email = otp_req.email.clone();
+    let result = send(otp_req, db_pool.clone()).await;
+    crate::metrics::otp(crate::metrics::Otp::Send, &result);
+    crate::audit::record(
+        crate::audit::Record {
+            event: crate::audit::Event::OtpSend,
+            outcome: crate::audit::outcome(&result, OtpError::reason),
+            user_id: None,
+            email: Some(email.as_str()),
+        },
+        client,
+        &db_pool,
+    )
+    .await;
+    result
+}
+
+async fn send(otp_req: SendOtpReq, db_pool: db::pg::DbPool) -> Result<SendOtpRes, OtpError> {
     let otp = generate_otp();
     let otp_bucket = vec![OtpBucketItem {
         otp,
@@ -107,9 +131,10 @@ pub async fn send_otp(
         &serde_json::to_value(otp_bucket)?,
         "SENDING",
         &db_pool,
-    )?;
+    )
+    .await?;
     crate::communication::send_email(otp, otp_req.email.as_str()).await?;
-    db::otp::otp_update_status(otp_id, "SEND", &db_pool)?;
+    db::otp::otp_update_status(otp_id, "SEND", &db_pool).await?;
     Ok(SendOtpRes {
         email: otp_req.email,
         message: "OTP send successfully".to_string(),
@@ -118,12 +143,33 @@ pub async fn send_otp(
 
 pub async fn resend_otp(
     otp_req: SendOtpReq,
+    client: &crate::audit::Client,
     db_pool: db::pg::DbPool,
 ) -> Result<SendOtpRes, OtpError> {
-    let db_otp =
-        db::otp::get_otp(otp_req.email.as_str(), &db_pool)?.ok_or(OtpError::OTPNotFound(
-            format!("Not otp has entry found with email: {}", otp_req.email),
-        ))?;
+    let email = otp_req.email.clone();
+    let result = resend(otp_req, db_pool.clone()).await;
+    crate::metrics::otp(crate::metrics::Otp::Resend, &result);
+    crate::audit::record(
+        crate::audit::Record {
+            event: crate::audit::Event::OtpResend,
+            outcome: crate::audit::outcome(&result, OtpError::reason),
+            user_id: None,
+            email: Some(email.as_str()),
+        },
+        client,
+        &db_pool,
+    )
+    .await;
+    result
+}
+
+async fn resend(otp_req: SendOtpReq, db_pool: db::pg::DbPool) -> Result<SendOtpRes, OtpError> {
+    let db_otp = db::otp::get_otp(otp_req.email.as_str(), &db_pool)
+        .await?
+        .ok_or(OtpError::OTPNotFound(format!(
+            "Not otp has entry found with email: {}",
+            otp_req.email
+        )))?;
 
     if db_otp.status.eq("VERIFIED") {
         return Err(OtpError::OTPNotFound(format!(
@@ -136,37 +182,48 @@ pub async fn resend_otp(
     let otp_bucket = OtpBucket::new(db_otp.otp_bucket)?
         .filter_old()
         .append(OtpBucketItem::new(new_otp));
-    db::otp::otp_update_bucket(db_otp.id, &otp_bucket.to_value()?, "RESENDING", &db_pool)?;
+    db::otp::otp_update_bucket(db_otp.id, &otp_bucket.to_value()?, "RESENDING", &db_pool).await?;
     crate::communication::send_email(new_otp, otp_req.email.as_str()).await?;
-    db::otp::otp_update_status(db_otp.id, "RESEND", &db_pool)?;
+    db::otp::otp_update_status(db_otp.id, "RESEND", &db_pool).await?;
     Ok(SendOtpRes {
         email: otp_req.email,
         message: "OTP resend successfully".to_string(),
     })
 }
 
-#[derive(serde::Deserialize)]
-pub struct VerifyOtpReq {
-    #[serde(rename = "email")]
-    pub email: String,
-    #[serde(rename = "phone")]
-    pub phone: Option<String>,
-    pub otp: u32,
-}
-
-#[derive(serde::Serialize)]
-pub struct VerifyOtpRes {
-    user_token: String,
-}
-
 pub async fn verify_otp(
     otp_req: VerifyOtpReq,
+    client: &crate::audit::Client,
     db_pool: db::pg::DbPool,
 ) -> Result<VerifyOtpRes, OtpError> {
-    let db_otp =
-        db::otp::get_otp(otp_req.email.as_str(), &db_pool)?.ok_or(OtpError::OTPNotFound(
-            format!("Not otp has entry found with email: {}", otp_req.email),
-        ))?;
+    let email = otp_req.email.clone();
+    let result = verify(otp_req, db_pool.clone()).await;
+    crate::metrics::otp(crate::metrics::Otp::Verify, &result);
+    crate::audit::record(
+        crate::audit::Record {
+            event: crate::audit::Event::OtpVerify,
+            outcome: crate::audit::outcome(&result, OtpError::reason),
+            user_id: result.as_ref().ok().map(|(user_id, _)| *user_id),
+            email: Some(email.as_str()),
+        },
+        client,
+        &db_pool,
+    )
+    .await;
+    result.map(|(_, response)| response)
+}
+
+// user of the verified email and its session
+async fn verify(
+    otp_req: VerifyOtpReq,
+    db_pool: db::pg::DbPool,
+) -> Result<(i64, VerifyOtpRes), OtpError> {
+    let db_otp = db::otp::get_otp(otp_req.email.as_str(), &db_pool)
+        .await?
+        .ok_or(OtpError::OTPNotFound(format!(
+            "Not otp has entry found with email: {}",
+            otp_req.email
+        )))?;
 
     if db_otp.status.eq("VERIFIED") {
         return Err(OtpError::AmbiguousVerificationRequest(
@@ -183,19 +240,25 @@ pub async fn verify_otp(
 
     tracing::info!(message = "otp is verified", email = otp_req.email);
     // get or create user
-    let user_id = db::user::upsert_with_email(otp_req.email.as_str(), &db_pool)?;
+    let user_id = db::user::upsert_with_email(otp_req.email.as_str(), &db_pool).await?;
     // generate the token
-    let jwt_token = crate::jwt::create_jwt(user_id.to_string())?;
+    let permissions = crate::authorization::permissions(user_id, &db_pool).await?;
+    let jwt_token = crate::jwt::create_jwt(user_id.to_string(), &permissions)?;
     // inactive all the active tokens if any and issue the new token
-    db::user::create_token(user_id, jwt_token.as_str(), &db_pool)?;
+    db::user::create_token(user_id, jwt_token.as_str(), &db_pool).await?;
     db::otp::otp_update_bucket(
         db_otp.id,
         &otp_bucket.empty().to_value()?,
         "VERIFIED",
         &db_pool,
-    )?;
+    )
+    .await?;
 
-    Ok(VerifyOtpRes {
-        user_token: jwt_token,
-    })
+    Ok((
+        user_id,
+        VerifyOtpRes {
+            csrf_token: crate::session::csrf_token(jwt_token.as_str()),
+            user_token: jwt_token,
+        },
+    ))
 }
@@ -0,0 +1,301 @@
+// Note: the browser only holds our own session token (the jwt issued on login), all the provider
+// tokens stay in the database
+pub const SESSION_COOKIE: &str = "auth-session";
+// Note: csrf token of the session for the browser apps, it is not HttpOnly so that the page reads it
+// and sends it back in the `X-CSRF-Token` header, see `verify_csrf`
+pub const CSRF_COOKIE: &str = "auth-csrf";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+// page to go after the github login, kept in the cookie while the user is on github
+pub const NEXT_COOKIE: &str = "auth-next";
+const NEXT_COOKIE_MAX_AGE: u64 = 10 * 60;
+// Note: login page of this service, user comes back to the page after the OTP or GitHub login with
+// the `next` query parameter
+const LOGIN_URL: &str = "/auth/login/";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionMode {
+    // session token is in the login responses as well, for the api clients and the mobile apps
+    Token,
+    // session token is only in the HttpOnly cookie, the javascript of the page never sees it
+    Cookie,
+}
+
+pub static SESSION_MODE: once_cell::sync::Lazy<SessionMode> = once_cell::sync::Lazy::new(|| {
+    session_mode(std::env::var("SESSION_MODE").ok()).unwrap_or_else(|e| panic!("{}", e))
+});
+
+// Note: `None` is for the browser apps on the other sites, they get the cookie only with it
+static SAME_SITE: once_cell::sync::Lazy<&'static str> = once_cell::sync::Lazy::new(|| {
+    same_site(std::env::var("SESSION_COOKIE_SAMESITE").ok()).unwrap_or_else(|e| panic!("{}", e))
+});
+
+// Note: called at the startup, see `SESSION_MODE` and `SAME_SITE`
+pub fn check_session_config() -> Result<(), String> {
+    session_mode(std::env::var("SESSION_MODE").ok())?;
+    same_site(std::env::var("SESSION_COOKIE_SAMESITE").ok()).map(|_| ())
+}
+
+fn session_mode(val: Option<String>) -> Result<SessionMode, String> {
+    match val {
+        Some(val) if val.eq_ignore_ascii_case("cookie") => Ok(SessionMode::Cookie),
+        Some(val) if val.eq_ignore_ascii_case("token") => Ok(SessionMode::Token),
+        Some(val) => Err(format!(
+            "SESSION_MODE must be token or cookie, found {}",
+            val
+        )),
+        None => Ok(SessionMode::Token),
+    }
+}
+
+fn same_site(val: Option<String>) -> Result<&'static str, String> {
+    match val {
+        Some(val) if val.eq_ignore_ascii_case("lax") => Ok("Lax"),
+        Some(val) if val.eq_ignore_ascii_case("strict") => Ok("Strict"),
+        Some(val) if val.eq_ignore_ascii_case("none") => Ok("None"),
+        Some(val) => Err(format!(
+            "SESSION_COOKIE_SAMESITE must be lax, strict or none, found {}",
+            val
+        )),
+        None => Ok("Lax"),
+    }
+}
+
+pub fn cookie(token: &str, host: &str) -> String {
+    format!(
+        "{}={}; HttpOnly; Secure; SameSite={}; Path=/; Domain={}; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        *SAME_SITE,
+        sanitize_port(host),
+        crate::jwt::JWT_EXPIRY
+    )
+}
+
+// session and csrf cookies set by the logins
+pub fn login_cookies(token: &str, host: &str) -> [String; 2] {
+    [
+        cookie(token, host),
+        format!(
+            "{}={}; Secure; SameSite={}; Path=/; Domain={}; Max-Age={}",
+            CSRF_COOKIE,
+            csrf_token(token),
+            *SAME_SITE,
+            sanitize_port(host),
+            crate::jwt::JWT_EXPIRY
+        ),
+    ]
+}
+
+// Note: the csrf token is bound to the session token, there is nothing to store and it changes with
+// every login
+pub fn csrf_token(session_token: &str) -> String {
+    use base64::Engine;
+    use hmac::Mac;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .encode(csrf_mac(session_token).finalize().into_bytes())
+}
+
+fn csrf_mac(session_token: &str) -> hmac::Hmac<sha2::Sha256> {
+    use hmac::Mac;
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(crate::jwt::JWT_SECRET.as_slice())
+        .expect("hmac takes keys of any size");
+    mac.update(b"csrf:");
+    mac.update(session_token.as_bytes());
+    mac
+}
+
+// Note: browsers send the session cookie on their own with the requests of the other sites, the
+// state changing requests of a valid cookie session need the csrf token of it in `X-CSRF-Token`.
+// Requests without the cookie (bearer api clients) are not at risk
+pub fn verify_csrf(
+    method: &hyper::Method,
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+) -> bool {
+    use base64::Engine;
+    use hmac::Mac;
+
+    if matches!(
+        *method,
+        hyper::Method::GET | hyper::Method::HEAD | hyper::Method::OPTIONS | hyper::Method::TRACE
+    ) {
+        return true;
+    }
+    let session = match cookie_value(headers, SESSION_COOKIE) {
+        Some(token) if user_id(token.as_str()).is_ok() => token,
+        _ => return true,
+    };
+    let token = match headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            base64::engine::general_purpose::URL_SAFE_NO_PAD
+                .decode(value)
+                .ok()
+        }) {
+        Some(token) => token,
+        None => return false,
+    };
+    csrf_mac(session.as_str()).verify_slice(&token).is_ok()
+}
+
+// Note: access tokens issued to the oidc clients are not accepted as the session
+pub fn user_id(token: &str) -> Result<i64, crate::jwt::JWTError> {
+    let claims = crate::jwt::decode_claims(token)?;
+    if claims.client_id.is_some() {
+        return Err(crate::jwt::JWTError::NotSessionToken);
+    }
+    claims
+        .sub
+        .parse::<i64>()
+        .map_err(|_| crate::jwt::JWTError::InvalidSubject)
+}
+
+// Note: the user of the session token if the session is still active, the logged out sessions and
+// the ones revoked from the sessions list are turned away before their token expires
+pub async fn active_user_id(
+    token: &str,
+    db_pool: &db::pg::DbPool,
+) -> Result<Option<i64>, db::DBError> {
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    let user_id = match user_id(token) {
+        Ok(user_id) => user_id,
+        Err(_) => return Ok(None),
+    };
+    match db::user::is_token_active(token, db_pool).await? {
+        true => Ok(Some(user_id)),
+        false => Ok(None),
+    }
+}
+
+pub fn clear_cookies(host: &str) -> [String; 2] {
+    [
+        format!(
+            "{}=; HttpOnly; Secure; SameSite={}; Path=/; Domain={}; Max-Age=0",
+            SESSION_COOKIE,
+            *SAME_SITE,
+            sanitize_port(host)
+        ),
+        format!(
+            "{}=; Secure; SameSite={}; Path=/; Domain={}; Max-Age=0",
+            CSRF_COOKIE,
+            *SAME_SITE,
+            sanitize_port(host)
+        ),
+    ]
+}
+
+pub fn next_cookie(next: &str) -> String {
+    format!(
+        "{}={}; HttpOnly; Secure; SameSite=Lax; Path=/auth/; Max-Age={}",
+        NEXT_COOKIE,
+        url::form_urlencoded::byte_serialize(next.as_bytes()).collect::<String>(),
+        NEXT_COOKIE_MAX_AGE
+    )
+}
+
+pub fn clear_next_cookie() -> String {
+    format!(
+        "{}=; HttpOnly; Secure; SameSite=Lax; Path=/auth/; Max-Age=0",
+        NEXT_COOKIE
+    )
+}
+
+pub fn next_from_headers(headers: &hyper::HeaderMap<hyper::header::HeaderValue>) -> Option<String> {
+    cookie_value(headers, NEXT_COOKIE).and_then(|value| {
+        url::form_urlencoded::parse(format!("v={}", value).as_bytes())
+            .next()
+            .map(|(_, next)| next.into_owned())
+    })
+}
+
+// Note: only the relative paths of this service, `//host` and `/\host` are taken as other hosts by
+// the browsers, redirecting to them would make the login an open redirect
+pub fn safe_next(next: &str) -> Option<&str> {
+    match next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") {
+        true => Some(next),
+        false => None,
+    }
+}
+
+// login page which comes back to `next` after the login
+pub fn login_url(next: &str) -> String {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("next", next)
+        .finish();
+    format!("{}?{}", LOGIN_URL, query)
+}
+
+pub(crate) fn cookie_value(
+    headers: &hyper::HeaderMap<hyper::header::HeaderValue>,
+    name: &str,
+) -> Option<String> {
+    headers
+        .get_all(hyper::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(n, _)| n.eq(&name))
+        .map(|(_, value)| value.to_string())
+}
+
+// Note: session token from the session cookie, or from the authorization header for the api clients
+pub fn from_headers(headers: &hyper::HeaderMap<hyper::header::HeaderValue>) -> Option<String> {
+    cookie_value(headers, SESSION_COOKIE).or_else(|| {
+        headers
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+    })
+}
+
+fn sanitize_port(host: &str) -> String {
+    match host.split_once(":") {
+        Some((domain, _port)) => domain.to_string(),
+        None => host.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn session_config_of_env() {
+        let val = |v: &str| Some(v.to_string());
+        assert_eq!(super::session_mode(None), Ok(super::SessionMode::Token));
+        assert_eq!(
+            super::session_mode(val("Cookie")),
+            Ok(super::SessionMode::Cookie)
+        );
+        assert!(super::session_mode(val("cookies")).is_err());
+        assert_eq!(super::same_site(None), Ok("Lax"));
+        assert_eq!(super::same_site(val("NONE")), Ok("None"));
+        assert!(super::same_site(val("off")).is_err());
+    }
+
+    #[test]
+    fn csrf_of_cookie_sessions() {
+        let token =
+            crate::jwt::create_jwt("42".to_string(), &Default::default()).expect("jwt is created");
+        let mut headers = hyper::HeaderMap::new();
+        assert!(super::verify_csrf(&hyper::Method::POST, &headers));
+
+        headers.insert(
+            hyper::header::COOKIE,
+            hyper::header::HeaderValue::from_str(&format!("{}={}", super::SESSION_COOKIE, token))
+                .unwrap(),
+        );
+        assert!(super::verify_csrf(&hyper::Method::GET, &headers));
+        assert!(!super::verify_csrf(&hyper::Method::POST, &headers));
+
+        headers.insert(
+            super::CSRF_HEADER,
+            hyper::header::HeaderValue::from_str(&super::csrf_token("other session")).unwrap(),
+        );
+        assert!(!super::verify_csrf(&hyper::Method::POST, &headers));
+        headers.insert(
+            super::CSRF_HEADER,
+            hyper::header::HeaderValue::from_str(&super::csrf_token(&token)).unwrap(),
+        );
+        assert!(super::verify_csrf(&hyper::Method::POST, &headers));
+    }
+}
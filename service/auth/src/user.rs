@@ -0,0 +1,98 @@
+// Note: profile and the sessions of the logged in user, the session token of the request is the
+// one of the cookie or the authorization header
+pub use auth_types::user::{Profile, RevokeSessionReq, Session, UpdateProfileReq};
+
+const MAX_NAME_LEN: usize = 127;
+
+#[derive(thiserror::Error, Debug)]
+pub enum UserError {
+    #[error("DBError: {}", _0)]
+    DB(#[from] db::DBError),
+    #[error("InvalidRequest: {}", _0)]
+    InvalidRequest(String),
+    #[error("NotFound")]
+    NotFound,
+}
+
+impl UserError {
+    // reason of the failed audit events
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            UserError::DB(_) => "db",
+            UserError::InvalidRequest(_) => "invalid_request",
+            UserError::NotFound => "not_found",
+        }
+    }
+}
+
+pub async fn profile(user_id: i64, db_pool: &db::pg::DbPool) -> Result<Profile, UserError> {
+    let user = db::user::get(user_id, db_pool)
+        .await?
+        .filter(|user| user.active)
+        .ok_or(UserError::NotFound)?;
+    let permissions = crate::authorization::permissions(user_id, db_pool).await?;
+    Ok(Profile {
+        id: user.id,
+        name: user.name,
+        email: user.email,
+        phone: user.phone,
+        roles: permissions.roles,
+        scopes: permissions.scopes,
+    })
+}
+
+pub async fn update_profile(
+    user_id: i64,
+    req: UpdateProfileReq,
+    db_pool: &db::pg::DbPool,
+) -> Result<Profile, UserError> {
+    let name = req.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(UserError::InvalidRequest(format!(
+            "name is required, at most {} characters",
+            MAX_NAME_LEN
+        )));
+    }
+    db::user::update_name(user_id, name, db_pool).await?;
+    profile(user_id, db_pool).await
+}
+
+pub async fn sessions(
+    user_id: i64,
+    token: &str,
+    db_pool: &db::pg::DbPool,
+) -> Result<Vec<Session>, UserError> {
+    Ok(db::user::active_tokens(user_id, db_pool)
+        .await?
+        .into_iter()
+        .map(|t| Session {
+            id: t.id,
+            current: t.token.eq(token),
+            client_id: t.client_id,
+            scope: t.scope,
+            created_on: t.created_on.to_rfc3339(),
+        })
+        .collect())
+}
+
+pub async fn revoke_session(
+    user_id: i64,
+    req: RevokeSessionReq,
+    db_pool: &db::pg::DbPool,
+) -> Result<(), UserError> {
+    if !db::user::revoke_user_token(req.id, user_id, db_pool).await? {
+        return Err(UserError::NotFound);
+    }
+    tracing::info!(
+        message = "session revoked",
+        user_id = user_id,
+        token_id = req.id
+    );
+    Ok(())
+}
+
+pub async fn logout(user_id: i64, token: &str, db_pool: &db::pg::DbPool) -> Result<(), UserError> {
+    db::user::revoke_token(token, db_pool).await?;
+    tracing::info!(message = "logged out", user_id = user_id);
+    Ok(())
+}
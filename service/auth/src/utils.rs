@@ -11,5 +11,5 @@ where
         let key = key(&item);
         map.entry(key).or_default().push(item)
     }
-    return map;
+    map
 }
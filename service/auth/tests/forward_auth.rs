@@ -0,0 +1,197 @@
+// Note: runs the auth subrequest of the sample `nginx.conf` against the `/auth/verify` handler. The
+// callers here are turned away before the database is asked, so the pool is never connected
+const NGINX_CONF: &str = include_str!("nginx.conf");
+
+fn pool() -> db::pg::DbPool {
+    diesel::r2d2::Pool::builder().build_unchecked(diesel::r2d2::ConnectionManager::new(
+        "postgres://localhost/unused",
+    ))
+}
+
+// Note: the tokens are signed and verified with `JWT_SECRET`, set before the first of them
+fn jwt_secret() {
+    std::env::set_var("JWT_SECRET", "secret of the integration tests of auth");
+}
+
+// directives of the `location` block, as (name, arguments)
+fn location(name: &str) -> Vec<(String, String)> {
+    let start = NGINX_CONF
+        .find(format!("location {} {{", name).as_str())
+        .unwrap_or_else(|| panic!("location {} not found in nginx.conf", name));
+    let block = &NGINX_CONF[start..];
+    let block = &block[block.find('{').unwrap() + 1..block.find('}').unwrap()];
+    block
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .split(';')
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .map(|(name, args)| (name.to_string(), args.trim().to_string()))
+        .collect()
+}
+
+fn directive<'a>(directives: &'a [(String, String)], name: &str) -> &'a str {
+    directives
+        .iter()
+        .find(|(n, _)| n.eq(name))
+        .map(|(_, args)| args.as_str())
+        .unwrap_or_else(|| panic!("{} not found", name))
+}
+
+// the subrequest nginx sends for the original request, the original headers are passed as they are
+fn subrequest(original: &[(&str, &str)]) -> (hyper::Uri, hyper::HeaderMap) {
+    let auth = location("= /_auth");
+    let upstream = directive(&auth, "proxy_pass");
+    let path = &upstream[upstream.find("/auth/").unwrap()..];
+    let mut headers = hyper::HeaderMap::new();
+    for (name, value) in original {
+        headers.append(
+            hyper::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    for (_, args) in auth.iter().filter(|(n, _)| n.eq("proxy_set_header")) {
+        let (name, value) = args.split_once(char::is_whitespace).unwrap();
+        let value = match value.trim() {
+            "$host" => "app.example.com",
+            "$request_uri" => "/dashboard?tab=1",
+            _ => "",
+        };
+        headers.insert(
+            hyper::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    (path.parse().unwrap(), headers)
+}
+
+#[test]
+fn nginx_config_matches_handler() {
+    let auth = location("= /_auth");
+    assert!(directive(&auth, "proxy_pass").contains("/auth/verify?"));
+    // Note: `$upstream_http_x_auth_user_id` is the `X-Auth-User-Id` response header
+    let app = location("/");
+    let upstream_headers: Vec<String> = app
+        .iter()
+        .filter(|(n, _)| n.eq("auth_request_set"))
+        .filter_map(|(_, args)| args.split_once("$upstream_http_"))
+        .map(|(_, header)| header.replace('_', "-"))
+        .collect();
+    assert_eq!(
+        upstream_headers,
+        vec![
+            auth::forward_auth::USER_ID_HEADER,
+            auth::forward_auth::EMAIL_HEADER
+        ]
+    );
+    assert_eq!(directive(&app, "error_page"), "401 = @login");
+    let login = location("@login");
+    let login = directive(&login, "return");
+    assert!(login.starts_with(
+        format!(
+            "302 {}",
+            auth::session::login_url("").trim_end_matches("next=")
+        )
+        .as_str()
+    ));
+}
+
+#[tokio::test]
+async fn nginx_subrequest_without_session() {
+    jwt_secret();
+    let pool = pool();
+    let (uri, headers) = subrequest(&[("accept", "text/html")]);
+    let response = auth::forward_auth::verify(&uri, &headers, &pool)
+        .await
+        .unwrap();
+    // Note: not redirected, nginx takes any other status of the subrequest as an error
+    assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+
+    let (uri, headers) = subrequest(&[("cookie", "auth-session=expired.or.forged")]);
+    let response = auth::forward_auth::verify(&uri, &headers, &pool)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn machine_clients_are_not_let_through() {
+    jwt_secret();
+    let token = auth::jwt::create_client_credentials_token("job", "ai:use", 60).unwrap();
+    let (uri, headers) = subrequest(&[("authorization", format!("Bearer {}", token).as_str())]);
+    let response = auth::forward_auth::verify(&uri, &headers, &pool())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+    assert!(response
+        .headers()
+        .get(auth::forward_auth::USER_ID_HEADER)
+        .is_none());
+}
+
+#[tokio::test]
+async fn traefik_browser_is_redirected_to_login() {
+    let mut headers = hyper::HeaderMap::new();
+    headers.insert("accept", "text/html,application/xhtml+xml".parse().unwrap());
+    headers.insert("x-forwarded-method", "GET".parse().unwrap());
+    headers.insert("x-forwarded-uri", "/dashboard?tab=1".parse().unwrap());
+    let uri: hyper::Uri = "/auth/verify".parse().unwrap();
+    let response = auth::forward_auth::verify(&uri, &headers, &pool())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::FOUND);
+    assert_eq!(
+        response.headers().get(hyper::header::LOCATION).unwrap(),
+        "/auth/login/?next=%2Fdashboard%3Ftab%3D1"
+    );
+
+    // Note: api clients get 401
+    headers.remove("accept");
+    let response = auth::forward_auth::verify(&uri, &headers, &pool())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn invalid_expression_is_reported() {
+    let uri: hyper::Uri = "/auth/verify?expression=org%3Afastn-stack%20AND"
+        .parse()
+        .unwrap();
+    let response = auth::forward_auth::verify(&uri, &hyper::HeaderMap::new(), &pool())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn deep_expression_is_reported() {
+    // Note: the uri is at most 64 KB, way past the depth which overflowed the stack of the tests
+    let query = format!(
+        "expression={}org%3Aa{}",
+        "(".repeat(20_000),
+        ")".repeat(20_000)
+    );
+    let uri: hyper::Uri = format!("/auth/verify?{}", query).parse().unwrap();
+    let response = auth::forward_auth::verify(&uri, &hyper::HeaderMap::new(), &pool())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn unknown_identity_is_reported() {
+    for query in [
+        "expression=NOT%20gitlab-group%3Aa",
+        "identity=gitlab-group:a",
+    ] {
+        let uri: hyper::Uri = format!("/auth/verify?{}", query).parse().unwrap();
+        let response = auth::forward_auth::verify(&uri, &hyper::HeaderMap::new(), &pool())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,49 @@
+// Note: the requests here are turned away before the database or github is asked, so the pool is
+// never connected
+fn pool() -> db::pg::DbPool {
+    diesel::r2d2::Pool::builder().build_unchecked(diesel::r2d2::ConnectionManager::new(
+        "postgres://localhost/unused",
+    ))
+}
+
+// Note: the tokens are signed and verified with `JWT_SECRET`, set before the first of them
+fn jwt_secret() {
+    std::env::set_var("JWT_SECRET", "secret of the integration tests of auth");
+}
+
+fn request(tokens: &[(&str, &str)], expression: &str) -> auth::get_identities::GetIdsRequest {
+    serde_json::from_value(serde_json::json!({
+        "tokens": tokens
+            .iter()
+            .map(|(key, value)| serde_json::json!({"key": key, "value": value}))
+            .collect::<Vec<_>>(),
+        "expression": expression,
+    }))
+    .unwrap()
+}
+
+#[tokio::test]
+async fn not_without_session_is_unauthenticated() {
+    jwt_secret();
+    for tokens in [vec![], vec![("auth-session", "expired.or.forged")]] {
+        let result =
+            auth::get_identities::get_identities(request(&tokens, "NOT org:a"), pool()).await;
+        assert!(matches!(
+            result,
+            Err(auth::get_identities::GetIdsError::Unauthenticated(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn expression_without_session_is_not_parsed() {
+    jwt_secret();
+    let deep = format!("{}org:a{}", "(".repeat(60_000), ")".repeat(60_000));
+    for expression in [deep.as_str(), "NOT gitlab-group:a", "org:a AND"] {
+        let result = auth::get_identities::get_identities(request(&[], expression), pool()).await;
+        assert!(matches!(
+            result,
+            Err(auth::get_identities::GetIdsError::Unauthenticated(_))
+        ));
+    }
+}
@@ -0,0 +1,154 @@
+// Note: needs the database migrated by django in env `DATABASE_URL`, the test is skipped without it
+fn pool() -> Option<db::pg::DbPool> {
+    let url = std::env::var("DATABASE_URL").ok()?;
+    Some(
+        diesel::r2d2::Pool::builder()
+            .max_size(2)
+            .build(diesel::r2d2::ConnectionManager::new(url))
+            .expect("DATABASE_URL is not reachable"),
+    )
+}
+
+// Note: the tokens are signed and verified with `JWT_SECRET`, set before the first of them
+fn jwt_secret() {
+    std::env::set_var("JWT_SECRET", "secret of the integration tests of auth");
+}
+
+#[tokio::test]
+async fn revoked_session_is_unauthorized() {
+    let pool = match pool() {
+        Some(pool) => pool,
+        None => return,
+    };
+    jwt_secret();
+    let email = format!("revoked-{}@example.com", auth::crypto::random_token(6));
+    let user_id = db::user::upsert_with_email(email.as_str(), &pool)
+        .await
+        .unwrap();
+    let token = auth::jwt::create_jwt(user_id.to_string(), &Default::default()).unwrap();
+    db::user::create_token(user_id, token.as_str(), &pool)
+        .await
+        .unwrap();
+
+    let mut headers = hyper::HeaderMap::new();
+    headers.insert(
+        hyper::header::COOKIE,
+        format!("{}={}", auth::session::SESSION_COOKIE, token)
+            .parse()
+            .unwrap(),
+    );
+    let mut bearer = hyper::HeaderMap::new();
+    bearer.insert(
+        hyper::header::AUTHORIZATION,
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    let uri: hyper::Uri = "/auth/verify".parse().unwrap();
+    let response = auth::forward_auth::verify(&uri, &headers, &pool)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+    assert!(auth::authenticate::authenticate(&bearer, &pool)
+        .await
+        .is_ok());
+
+    // Note: logged out, the token itself has not expired
+    assert!(db::user::revoke_token(token.as_str(), &pool).await.unwrap());
+    assert_eq!(
+        auth::session::active_user_id(token.as_str(), &pool)
+            .await
+            .unwrap(),
+        None
+    );
+    for headers in [&headers, &bearer] {
+        let response = auth::forward_auth::verify(&uri, headers, &pool)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+    }
+    assert!(matches!(
+        auth::authenticate::authenticate(&bearer, &pool).await,
+        Err(auth::authenticate::AuthenticateError::RevokedToken)
+    ));
+}
+
+#[tokio::test]
+async fn unknown_predicate_is_an_expression_error() {
+    let pool = match pool() {
+        Some(pool) => pool,
+        None => return,
+    };
+    jwt_secret();
+    let email = format!("expression-{}@example.com", auth::crypto::random_token(6));
+    let user_id = db::user::upsert_with_email(email.as_str(), &pool)
+        .await
+        .unwrap();
+    let token = auth::jwt::create_jwt(user_id.to_string(), &Default::default()).unwrap();
+    db::user::create_token(user_id, token.as_str(), &pool)
+        .await
+        .unwrap();
+
+    let deep = format!("{}org:a{}", "(".repeat(60_000), ")".repeat(60_000));
+    for expression in [
+        "NOT gitlab-group:a",
+        "org:a OR NOT github-unknown:b",
+        "starred:a/../../user/emails",
+        "org:a AND NOT team:a/b?per_page=1",
+        "follower:a/b",
+        deep.as_str(),
+    ] {
+        let request = serde_json::from_value(serde_json::json!({
+            "tokens": [{"key": auth::session::SESSION_COOKIE, "value": token}],
+            "expression": expression,
+        }))
+        .unwrap();
+        let result = auth::get_identities::get_identities(request, pool.clone()).await;
+        assert!(matches!(
+            result,
+            Err(auth::get_identities::GetIdsError::Expression(_))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn deactivated_client_is_unauthorized() {
+    use diesel::RunQueryDsl;
+
+    let pool = match pool() {
+        Some(pool) => pool,
+        None => return,
+    };
+    jwt_secret();
+    let client_id = format!("job-{}", auth::crypto::random_token(6));
+    // Note: the machine clients are created in the django admin
+    diesel::sql_query(
+        "INSERT INTO authapp_machine_client (created_on, updated_on, client_id, \
+         client_secret_hash, name, allowed_scopes, active) \
+         VALUES (now(), now(), $1, '', 'job', 'ai:use', true)",
+    )
+    .bind::<diesel::sql_types::Text, _>(client_id.as_str())
+    .execute(&mut pool.get().unwrap())
+    .unwrap();
+    let token =
+        auth::jwt::create_client_credentials_token(client_id.as_str(), "ai:use", 60).unwrap();
+    let mut bearer = hyper::HeaderMap::new();
+    bearer.insert(
+        hyper::header::AUTHORIZATION,
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    assert!(auth::authenticate::authenticate(&bearer, &pool)
+        .await
+        .is_ok());
+
+    diesel::sql_query("UPDATE authapp_machine_client SET active = false WHERE client_id = $1")
+        .bind::<diesel::sql_types::Text, _>(client_id.as_str())
+        .execute(&mut pool.get().unwrap())
+        .unwrap();
+    let err = auth::authenticate::authenticate(&bearer, &pool)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        auth::authenticate::AuthenticateError::InactiveClient
+    ));
+    assert!(err.is_unauthorized());
+}
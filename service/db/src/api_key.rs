@@ -0,0 +1,147 @@
+use diesel::prelude::*;
+use diesel::{OptionalExtension, RunQueryDsl};
+
+// last used time is written at most once in this many seconds per key
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+#[derive(diesel::Queryable)]
+pub struct ApiKeyDB {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub active: bool,
+    pub created_on: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct NewApiKey {
+    pub user_id: i64,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: String,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+pub async fn create(key: NewApiKey, pool: &crate::pg::DbPool) -> Result<i64, crate::DBError> {
+    use crate::schema::authapp_user_api_key;
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        Ok(
+            diesel::insert_into(authapp_user_api_key::dsl::authapp_user_api_key)
+                .values((
+                    authapp_user_api_key::dsl::user_id.eq(key.user_id),
+                    authapp_user_api_key::dsl::name.eq(key.name),
+                    authapp_user_api_key::dsl::key_prefix.eq(key.key_prefix),
+                    authapp_user_api_key::dsl::key_hash.eq(key.key_hash),
+                    authapp_user_api_key::dsl::scopes.eq(key.scopes),
+                    authapp_user_api_key::dsl::expires_at.eq(key.expires_at),
+                    authapp_user_api_key::dsl::active.eq(true),
+                    authapp_user_api_key::dsl::created_on.eq(now),
+                    authapp_user_api_key::dsl::updated_on.eq(now),
+                ))
+                .returning(authapp_user_api_key::dsl::id)
+                .get_result::<i64>(conn)?,
+        )
+    })
+    .await
+}
+
+// all the keys of the user including the revoked ones, latest first
+pub async fn list(user_id: i64, pool: &crate::pg::DbPool) -> Result<Vec<ApiKeyDB>, crate::DBError> {
+    use crate::schema::authapp_user_api_key;
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_user_api_key::dsl::authapp_user_api_key
+            .filter(authapp_user_api_key::dsl::user_id.eq(user_id))
+            .order(authapp_user_api_key::dsl::id.desc())
+            .select((
+                authapp_user_api_key::dsl::id,
+                authapp_user_api_key::dsl::user_id,
+                authapp_user_api_key::dsl::name,
+                authapp_user_api_key::dsl::key_prefix,
+                authapp_user_api_key::dsl::scopes,
+                authapp_user_api_key::dsl::expires_at,
+                authapp_user_api_key::dsl::last_used_at,
+                authapp_user_api_key::dsl::active,
+                authapp_user_api_key::dsl::created_on,
+            ))
+            .load::<ApiKeyDB>(conn)?)
+    })
+    .await
+}
+
+// Note: returns false if the key is not found, not of the user or already revoked
+pub async fn revoke(
+    id: i64,
+    user_id: i64,
+    pool: &crate::pg::DbPool,
+) -> Result<bool, crate::DBError> {
+    use crate::schema::authapp_user_api_key;
+    crate::pg::run(pool, move |conn| {
+        let updated = diesel::update(
+            authapp_user_api_key::dsl::authapp_user_api_key
+                .filter(authapp_user_api_key::dsl::id.eq(id))
+                .filter(authapp_user_api_key::dsl::user_id.eq(user_id))
+                .filter(authapp_user_api_key::dsl::active.eq(true)),
+        )
+        .set((
+            authapp_user_api_key::dsl::active.eq(false),
+            authapp_user_api_key::dsl::updated_on.eq(chrono::Utc::now()),
+        ))
+        .execute(conn)?;
+        Ok(updated > 0)
+    })
+    .await
+}
+
+// Note: active and not expired key with the hash, last used time of the key is updated
+pub async fn verify(
+    key_hash: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<Option<ApiKeyDB>, crate::DBError> {
+    use crate::schema::authapp_user_api_key;
+    let key_hash = key_hash.to_string();
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        let key = authapp_user_api_key::dsl::authapp_user_api_key
+            .filter(authapp_user_api_key::dsl::key_hash.eq(key_hash))
+            .filter(authapp_user_api_key::dsl::active.eq(true))
+            .filter(
+                authapp_user_api_key::dsl::expires_at
+                    .is_null()
+                    .or(authapp_user_api_key::dsl::expires_at.gt(now)),
+            )
+            .select((
+                authapp_user_api_key::dsl::id,
+                authapp_user_api_key::dsl::user_id,
+                authapp_user_api_key::dsl::name,
+                authapp_user_api_key::dsl::key_prefix,
+                authapp_user_api_key::dsl::scopes,
+                authapp_user_api_key::dsl::expires_at,
+                authapp_user_api_key::dsl::last_used_at,
+                authapp_user_api_key::dsl::active,
+                authapp_user_api_key::dsl::created_on,
+            ))
+            .get_result::<ApiKeyDB>(conn)
+            .optional()?;
+        if let Some(ref key) = key {
+            diesel::update(
+                authapp_user_api_key::dsl::authapp_user_api_key
+                    .filter(authapp_user_api_key::dsl::id.eq(key.id))
+                    .filter(
+                        authapp_user_api_key::dsl::last_used_at
+                            .is_null()
+                            .or(authapp_user_api_key::dsl::last_used_at
+                                .lt(now - chrono::Duration::seconds(LAST_USED_RESOLUTION_SECS))),
+                    ),
+            )
+            .set(authapp_user_api_key::dsl::last_used_at.eq(now))
+            .execute(conn)?;
+        }
+        Ok(key)
+    })
+    .await
+}
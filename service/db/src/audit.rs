@@ -0,0 +1,105 @@
+use diesel::prelude::*;
+use diesel::RunQueryDsl;
+
+#[derive(diesel::Queryable)]
+pub struct AuditEventDB {
+    pub id: i64,
+    pub created_on: chrono::DateTime<chrono::Utc>,
+    pub event: String,
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub user_id: Option<i64>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+pub struct NewAuditEvent {
+    pub event: String,
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub user_id: Option<i64>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+}
+
+// Note: the table is append-only, there is no update or delete of the events
+pub async fn insert(event: NewAuditEvent, pool: &crate::pg::DbPool) -> Result<i64, crate::DBError> {
+    use crate::schema::authapp_audit_event;
+    crate::pg::run(pool, move |conn| {
+        Ok(
+            diesel::insert_into(authapp_audit_event::dsl::authapp_audit_event)
+                .values((
+                    authapp_audit_event::dsl::created_on.eq(chrono::Utc::now()),
+                    authapp_audit_event::dsl::event.eq(event.event),
+                    authapp_audit_event::dsl::outcome.eq(event.outcome),
+                    authapp_audit_event::dsl::reason.eq(event.reason),
+                    authapp_audit_event::dsl::user_id.eq(event.user_id),
+                    authapp_audit_event::dsl::email.eq(event.email),
+                    authapp_audit_event::dsl::ip.eq(event.ip),
+                    authapp_audit_event::dsl::user_agent.eq(event.user_agent),
+                    authapp_audit_event::dsl::request_id.eq(event.request_id),
+                ))
+                .returning(authapp_audit_event::dsl::id)
+                .get_result::<i64>(conn)?,
+        )
+    })
+    .await
+}
+
+#[derive(Default)]
+pub struct AuditFilter {
+    pub user_id: Option<i64>,
+    pub email: Option<String>,
+    pub event: Option<String>,
+    pub outcome: Option<String>,
+    pub ip: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    // events older than this id, the id of the last event of the previous page
+    pub before_id: Option<i64>,
+}
+
+// latest first, at most `limit` events
+pub async fn list(
+    filter: AuditFilter,
+    limit: i64,
+    pool: &crate::pg::DbPool,
+) -> Result<Vec<AuditEventDB>, crate::DBError> {
+    use crate::schema::authapp_audit_event;
+    crate::pg::run(pool, move |conn| {
+        let mut query = authapp_audit_event::dsl::authapp_audit_event
+            .order(authapp_audit_event::dsl::id.desc())
+            .limit(limit)
+            .into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(authapp_audit_event::dsl::user_id.eq(user_id));
+        }
+        if let Some(email) = filter.email {
+            query = query.filter(authapp_audit_event::dsl::email.eq(email));
+        }
+        if let Some(event) = filter.event {
+            query = query.filter(authapp_audit_event::dsl::event.eq(event));
+        }
+        if let Some(outcome) = filter.outcome {
+            query = query.filter(authapp_audit_event::dsl::outcome.eq(outcome));
+        }
+        if let Some(ip) = filter.ip {
+            query = query.filter(authapp_audit_event::dsl::ip.eq(ip));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(authapp_audit_event::dsl::created_on.ge(since));
+        }
+        if let Some(until) = filter.until {
+            query = query.filter(authapp_audit_event::dsl::created_on.lt(until));
+        }
+        if let Some(before_id) = filter.before_id {
+            query = query.filter(authapp_audit_event::dsl::id.lt(before_id));
+        }
+        Ok(query.load::<AuditEventDB>(conn)?)
+    })
+    .await
+}
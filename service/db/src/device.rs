@@ -0,0 +1,166 @@
+use diesel::prelude::*;
+use diesel::{OptionalExtension, RunQueryDsl};
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_APPROVED: &str = "approved";
+pub const STATUS_DENIED: &str = "denied";
+pub const STATUS_USED: &str = "used";
+
+#[derive(diesel::Queryable)]
+pub struct DeviceCodeDB {
+    pub id: i64,
+    pub user_code: String,
+    pub scope: String,
+    pub status: String,
+    pub poll_interval: i32,
+    pub last_polled_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub client_id: i64,
+    pub user_id: Option<i64>,
+}
+
+pub struct NewDeviceCode {
+    pub device_code_hash: String,
+    pub user_code: String,
+    pub client_id: i64,
+    pub scope: String,
+    pub poll_interval: i32,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn create(code: NewDeviceCode, pool: &crate::pg::DbPool) -> Result<i64, crate::DBError> {
+    use crate::schema::authapp_oauth_device_code;
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        Ok(
+            diesel::insert_into(authapp_oauth_device_code::dsl::authapp_oauth_device_code)
+                .values((
+                    authapp_oauth_device_code::dsl::device_code_hash.eq(code.device_code_hash),
+                    authapp_oauth_device_code::dsl::user_code.eq(code.user_code),
+                    authapp_oauth_device_code::dsl::client_id.eq(code.client_id),
+                    authapp_oauth_device_code::dsl::scope.eq(code.scope),
+                    authapp_oauth_device_code::dsl::status.eq(STATUS_PENDING),
+                    authapp_oauth_device_code::dsl::poll_interval.eq(code.poll_interval),
+                    authapp_oauth_device_code::dsl::expires_at.eq(code.expires_at),
+                    authapp_oauth_device_code::dsl::created_on.eq(now),
+                    authapp_oauth_device_code::dsl::updated_on.eq(now),
+                ))
+                .returning(authapp_oauth_device_code::dsl::id)
+                .get_result::<i64>(conn)?,
+        )
+    })
+    .await
+}
+
+// Note: approves or denies the pending code for the user, returns false if the code is not found,
+// expired or already decided
+pub async fn decide(
+    user_code: &str,
+    user_id: i64,
+    approve: bool,
+    pool: &crate::pg::DbPool,
+) -> Result<bool, crate::DBError> {
+    use crate::schema::authapp_oauth_device_code;
+    let user_code = user_code.to_string();
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        let updated = diesel::update(
+            authapp_oauth_device_code::dsl::authapp_oauth_device_code
+                .filter(authapp_oauth_device_code::dsl::user_code.eq(user_code))
+                .filter(authapp_oauth_device_code::dsl::status.eq(STATUS_PENDING))
+                .filter(authapp_oauth_device_code::dsl::expires_at.gt(now)),
+        )
+        .set((
+            authapp_oauth_device_code::dsl::status.eq(if approve {
+                STATUS_APPROVED
+            } else {
+                STATUS_DENIED
+            }),
+            authapp_oauth_device_code::dsl::user_id.eq(user_id),
+            authapp_oauth_device_code::dsl::updated_on.eq(now),
+        ))
+        .execute(conn)?;
+        Ok(updated > 0)
+    })
+    .await
+}
+
+// Note: returns the code as it was before this poll and records the poll time, the row is locked
+// so the concurrent polls of the same device see each other's time
+pub async fn poll(
+    device_code_hash: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<Option<DeviceCodeDB>, crate::DBError> {
+    use crate::schema::authapp_oauth_device_code;
+    let device_code_hash = device_code_hash.to_string();
+    crate::pg::run(pool, move |conn| {
+        Ok(conn.transaction(|conn| {
+            let code = authapp_oauth_device_code::dsl::authapp_oauth_device_code
+                .filter(authapp_oauth_device_code::dsl::device_code_hash.eq(device_code_hash))
+                .select((
+                    authapp_oauth_device_code::dsl::id,
+                    authapp_oauth_device_code::dsl::user_code,
+                    authapp_oauth_device_code::dsl::scope,
+                    authapp_oauth_device_code::dsl::status,
+                    authapp_oauth_device_code::dsl::poll_interval,
+                    authapp_oauth_device_code::dsl::last_polled_at,
+                    authapp_oauth_device_code::dsl::expires_at,
+                    authapp_oauth_device_code::dsl::client_id,
+                    authapp_oauth_device_code::dsl::user_id,
+                ))
+                .for_update()
+                .get_result::<DeviceCodeDB>(conn)
+                .optional()?;
+            if let Some(ref code) = code {
+                let now = chrono::Utc::now();
+                diesel::update(
+                    authapp_oauth_device_code::dsl::authapp_oauth_device_code.find(code.id),
+                )
+                .set((
+                    authapp_oauth_device_code::dsl::last_polled_at.eq(now),
+                    authapp_oauth_device_code::dsl::updated_on.eq(now),
+                ))
+                .execute(conn)?;
+            }
+            diesel::result::QueryResult::Ok(code)
+        })?)
+    })
+    .await
+}
+
+pub async fn set_poll_interval(
+    id: i64,
+    poll_interval: i32,
+    pool: &crate::pg::DbPool,
+) -> Result<(), crate::DBError> {
+    use crate::schema::authapp_oauth_device_code;
+    crate::pg::run(pool, move |conn| {
+        diesel::update(authapp_oauth_device_code::dsl::authapp_oauth_device_code.find(id))
+            .set((
+                authapp_oauth_device_code::dsl::poll_interval.eq(poll_interval),
+                authapp_oauth_device_code::dsl::updated_on.eq(chrono::Utc::now()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
+
+// Note: tokens are issued once for the approved code, returns false if it is already used
+pub async fn mark_used(id: i64, pool: &crate::pg::DbPool) -> Result<bool, crate::DBError> {
+    use crate::schema::authapp_oauth_device_code;
+    crate::pg::run(pool, move |conn| {
+        let updated = diesel::update(
+            authapp_oauth_device_code::dsl::authapp_oauth_device_code
+                .filter(authapp_oauth_device_code::dsl::id.eq(id))
+                .filter(authapp_oauth_device_code::dsl::status.eq(STATUS_APPROVED)),
+        )
+        .set((
+            authapp_oauth_device_code::dsl::status.eq(STATUS_USED),
+            authapp_oauth_device_code::dsl::updated_on.eq(chrono::Utc::now()),
+        ))
+        .execute(conn)?;
+        Ok(updated > 0)
+    })
+    .await
+}
@@ -1,6 +1,13 @@
+pub mod api_key;
+pub mod audit;
+pub mod device;
+pub mod machine_client;
+pub mod oauth;
 pub mod otp;
 pub mod pg;
+pub mod provider_token;
 pub mod redis;
+pub mod role;
 pub mod schema;
 pub mod user;
 
@@ -10,4 +17,6 @@ pub enum DBError {
     Diesel(#[from] diesel::result::Error),
     #[error("PooledConnectionError: cannot get the connection from r2d2 pool")]
     PooledConnection(String),
+    #[error("TaskError: {}", _0)]
+    Task(String),
 }
@@ -0,0 +1,35 @@
+use diesel::prelude::*;
+use diesel::{OptionalExtension, RunQueryDsl};
+
+#[derive(diesel::Queryable)]
+pub struct MachineClientDB {
+    pub id: i64,
+    pub client_id: String,
+    pub client_secret_hash: String,
+    pub name: String,
+    pub allowed_scopes: String,
+}
+
+// Note: only the active clients
+pub async fn get(
+    client_id: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<Option<MachineClientDB>, crate::DBError> {
+    use crate::schema::authapp_machine_client;
+    let client_id = client_id.to_string();
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_machine_client::dsl::authapp_machine_client
+            .filter(authapp_machine_client::dsl::client_id.eq(client_id))
+            .filter(authapp_machine_client::dsl::active.eq(true))
+            .select((
+                authapp_machine_client::dsl::id,
+                authapp_machine_client::dsl::client_id,
+                authapp_machine_client::dsl::client_secret_hash,
+                authapp_machine_client::dsl::name,
+                authapp_machine_client::dsl::allowed_scopes,
+            ))
+            .get_result::<MachineClientDB>(conn)
+            .optional()?)
+    })
+    .await
+}
@@ -0,0 +1,145 @@
+use diesel::prelude::*;
+use diesel::{OptionalExtension, RunQueryDsl};
+
+#[derive(diesel::Queryable)]
+pub struct OAuthClientDB {
+    pub id: i64,
+    pub client_id: String,
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: serde_json::Value,
+    pub allowed_scopes: String,
+    pub active: bool,
+    pub can_introspect: bool,
+}
+
+impl OAuthClientDB {
+    pub fn is_redirect_uri_allowed(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris
+            .as_array()
+            .map(|uris| uris.iter().any(|uri| uri.as_str() == Some(redirect_uri)))
+            .unwrap_or(false)
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.client_secret_hash.is_none()
+    }
+}
+
+pub async fn get_client(
+    client_id: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<Option<OAuthClientDB>, crate::DBError> {
+    use crate::schema::authapp_oauth_client;
+    let client_id = client_id.to_string();
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_oauth_client::dsl::authapp_oauth_client
+            .filter(authapp_oauth_client::dsl::client_id.eq(client_id))
+            .filter(authapp_oauth_client::dsl::active.eq(true))
+            .select((
+                authapp_oauth_client::dsl::id,
+                authapp_oauth_client::dsl::client_id,
+                authapp_oauth_client::dsl::client_secret_hash,
+                authapp_oauth_client::dsl::name,
+                authapp_oauth_client::dsl::redirect_uris,
+                authapp_oauth_client::dsl::allowed_scopes,
+                authapp_oauth_client::dsl::active,
+                authapp_oauth_client::dsl::can_introspect,
+            ))
+            .get_result::<OAuthClientDB>(conn)
+            .optional()?)
+    })
+    .await
+}
+
+#[derive(diesel::Queryable)]
+pub struct OAuthCodeDB {
+    pub id: i64,
+    pub client_id: i64,
+    pub user_id: i64,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub created_on: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct NewOAuthCode {
+    pub code_hash: String,
+    pub client_id: i64,
+    pub user_id: i64,
+    pub redirect_uri: String,
+    pub scope: String,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub async fn create_code(
+    code: NewOAuthCode,
+    pool: &crate::pg::DbPool,
+) -> Result<i64, crate::DBError> {
+    use crate::schema::authapp_oauth_code;
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        Ok(
+            diesel::insert_into(authapp_oauth_code::dsl::authapp_oauth_code)
+                .values((
+                    authapp_oauth_code::dsl::code_hash.eq(code.code_hash),
+                    authapp_oauth_code::dsl::client_id.eq(code.client_id),
+                    authapp_oauth_code::dsl::user_id.eq(code.user_id),
+                    authapp_oauth_code::dsl::redirect_uri.eq(code.redirect_uri),
+                    authapp_oauth_code::dsl::scope.eq(code.scope),
+                    authapp_oauth_code::dsl::nonce.eq(code.nonce),
+                    authapp_oauth_code::dsl::code_challenge.eq(code.code_challenge),
+                    authapp_oauth_code::dsl::code_challenge_method.eq(code.code_challenge_method),
+                    authapp_oauth_code::dsl::expires_at.eq(code.expires_at),
+                    authapp_oauth_code::dsl::used.eq(false),
+                    authapp_oauth_code::dsl::created_on.eq(now),
+                    authapp_oauth_code::dsl::updated_on.eq(now),
+                ))
+                .returning(authapp_oauth_code::dsl::id)
+                .get_result::<i64>(conn)?,
+        )
+    })
+    .await
+}
+
+// Note: marks the code used and returns it, the code can be exchanged only once, so the same code
+// sent twice gets `None` the second time
+pub async fn take_code(
+    code_hash: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<Option<OAuthCodeDB>, crate::DBError> {
+    use crate::schema::authapp_oauth_code;
+    let code_hash = code_hash.to_string();
+    crate::pg::run(pool, move |conn| {
+        Ok(diesel::update(
+            authapp_oauth_code::dsl::authapp_oauth_code
+                .filter(authapp_oauth_code::dsl::code_hash.eq(code_hash))
+                .filter(authapp_oauth_code::dsl::used.eq(false)),
+        )
+        .set((
+            authapp_oauth_code::dsl::used.eq(true),
+            authapp_oauth_code::dsl::updated_on.eq(chrono::Utc::now()),
+        ))
+        .returning((
+            authapp_oauth_code::dsl::id,
+            authapp_oauth_code::dsl::client_id,
+            authapp_oauth_code::dsl::user_id,
+            authapp_oauth_code::dsl::redirect_uri,
+            authapp_oauth_code::dsl::scope,
+            authapp_oauth_code::dsl::nonce,
+            authapp_oauth_code::dsl::code_challenge,
+            authapp_oauth_code::dsl::code_challenge_method,
+            authapp_oauth_code::dsl::expires_at,
+            authapp_oauth_code::dsl::created_on,
+        ))
+        .get_result::<OAuthCodeDB>(conn)
+        .optional()?)
+    })
+    .await
+}
@@ -1,11 +1,273 @@
 pub type DbPool = diesel::r2d2::Pool<diesel::r2d2::ConnectionManager<diesel::PgConnection>>;
 
-pub fn get_connection_pool(url: &str) -> DbPool {
+// Note: the pool is shared by the requests running their queries on the blocking threads of tokio
+// (see `run`), `max_size` is the most queries running at once
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    // idle connections kept open, `max_size` if not set
+    pub min_idle: Option<u32>,
+    pub connection_timeout: std::time::Duration,
+    pub idle_timeout: Option<std::time::Duration>,
+    pub max_lifetime: Option<std::time::Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 10,
+            min_idle: None,
+            connection_timeout: std::time::Duration::from_secs(30),
+            idle_timeout: Some(std::time::Duration::from_secs(600)),
+            max_lifetime: Some(std::time::Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum PoolConfigError {
+    #[error("{0} is not a number, found {1}")]
+    NotANumber(String, String),
+    #[error("DB_POOL_MAX_SIZE must be at least 1")]
+    MaxSize,
+    #[error("DB_POOL_MIN_IDLE {0} is more than DB_POOL_MAX_SIZE {1}")]
+    MinIdle(u32, u32),
+    #[error("DB_POOL_CONNECTION_TIMEOUT_SECS must be at least 1")]
+    ConnectionTimeout,
+}
+
+impl PoolConfig {
+    // `DB_POOL_MAX_SIZE`, `DB_POOL_MIN_IDLE` and the `DB_POOL_*_SECS` timeouts, `0` secs for no
+    // idle timeout or max lifetime
+    pub fn from_env() -> Result<PoolConfig, PoolConfigError> {
+        PoolConfig::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<PoolConfig, PoolConfigError> {
+        let number = |name: &str| -> Result<Option<u64>, PoolConfigError> {
+            match var(name) {
+                Some(val) => val
+                    .trim()
+                    .parse()
+                    .map(Some)
+                    .map_err(|_| PoolConfigError::NotANumber(name.to_string(), val)),
+                None => Ok(None),
+            }
+        };
+        let size = |name: &str| -> Result<Option<u32>, PoolConfigError> {
+            match number(name)? {
+                Some(n) => u32::try_from(n)
+                    .map(Some)
+                    .map_err(|_| PoolConfigError::NotANumber(name.to_string(), n.to_string())),
+                None => Ok(None),
+            }
+        };
+        let secs = |name: &str| Ok(number(name)?.map(std::time::Duration::from_secs));
+        let optional_secs = |name: &str, default: Option<std::time::Duration>| {
+            Ok(match secs(name)? {
+                Some(duration) if duration.is_zero() => None,
+                Some(duration) => Some(duration),
+                None => default,
+            })
+        };
+        let default = PoolConfig::default();
+        let config = PoolConfig {
+            max_size: size("DB_POOL_MAX_SIZE")?.unwrap_or(default.max_size),
+            min_idle: size("DB_POOL_MIN_IDLE")?.or(default.min_idle),
+            connection_timeout: secs("DB_POOL_CONNECTION_TIMEOUT_SECS")?
+                .unwrap_or(default.connection_timeout),
+            idle_timeout: optional_secs("DB_POOL_IDLE_TIMEOUT_SECS", default.idle_timeout)?,
+            max_lifetime: optional_secs("DB_POOL_MAX_LIFETIME_SECS", default.max_lifetime)?,
+        };
+        // Note: r2d2 panics in `build` on these, they are reported at startup instead
+        if config.max_size == 0 {
+            return Err(PoolConfigError::MaxSize);
+        }
+        if config.connection_timeout.is_zero() {
+            return Err(PoolConfigError::ConnectionTimeout);
+        }
+        match config.min_idle {
+            Some(min_idle) if min_idle > config.max_size => {
+                Err(PoolConfigError::MinIdle(min_idle, config.max_size))
+            }
+            _ => Ok(config),
+        }
+    }
+}
+
+pub fn get_connection_pool(url: &str, config: &PoolConfig) -> DbPool {
     let connection_manager = diesel::r2d2::ConnectionManager::<diesel::PgConnection>::new(url);
     diesel::r2d2::Pool::builder()
-        .max_size(10)
-        .idle_timeout(Some(std::time::Duration::from_secs(600)))
-        .connection_timeout(std::time::Duration::from_secs(30))
+        .max_size(config.max_size)
+        .min_idle(config.min_idle)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+        .connection_timeout(config.connection_timeout)
+        .event_handler(Box::new(PoolMetrics))
         .build(connection_manager)
         .expect("Error in building the connection pool for postgres")
 }
+
+// Note: diesel is blocking, the query runs with a pooled connection on the blocking threads of
+// tokio so that waiting for the connection or postgres does not stall the worker threads. The
+// query owns its arguments, they are moved to the blocking thread
+pub async fn run<T, F>(pool: &DbPool, query: F) -> Result<T, crate::DBError>
+where
+    F: FnOnce(&mut diesel::PgConnection) -> Result<T, crate::DBError> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool
+            .get()
+            .map_err(|x| crate::DBError::PooledConnection(x.to_string()))?;
+        query(&mut conn)
+    })
+    .await
+    .map_err(|e| crate::DBError::Task(e.to_string()))?
+}
+
+// Note: `SELECT 1` on a pooled connection for the readiness check, it blocks up to `timeout`
+// waiting for a free connection, call it from `spawn_blocking`
+pub fn ping(pool: &DbPool, timeout: std::time::Duration) -> Result<(), crate::DBError> {
+    use diesel::RunQueryDsl;
+
+    let mut conn = pool
+        .get_timeout(timeout)
+        .map_err(|e| crate::DBError::PooledConnection(e.to_string()))?;
+    diesel::sql_query("SELECT 1").execute(&mut conn)?;
+    Ok(())
+}
+
+// Note: r2d2 does not keep the wait times, checkouts and timeouts are recorded by its events, the
+// connection counts are read from the pool state when `/metrics` is scraped
+static POOL_WAIT: once_cell::sync::Lazy<prometheus::Histogram> = once_cell::sync::Lazy::new(|| {
+    prometheus::register_histogram!(
+        "db_pool_wait_seconds",
+        "Time waited for a connection of the postgres pool",
+        vec![0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 30.0]
+    )
+    .expect("db_pool_wait_seconds is registered once")
+});
+
+static POOL_TIMEOUTS: once_cell::sync::Lazy<prometheus::IntCounter> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter!(
+            "db_pool_timeouts_total",
+            "Checkouts of the postgres pool which timed out"
+        )
+        .expect("db_pool_timeouts_total is registered once")
+    });
+
+static POOL_CONNECTIONS: once_cell::sync::Lazy<prometheus::IntGaugeVec> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_gauge_vec!(
+            "db_pool_connections",
+            "Connections of the postgres pool, all of them and the idle ones",
+            &["state"]
+        )
+        .expect("db_pool_connections is registered once")
+    });
+
+#[derive(Debug)]
+struct PoolMetrics;
+
+impl diesel::r2d2::HandleEvent for PoolMetrics {
+    fn handle_checkout(&self, event: diesel::r2d2::event::CheckoutEvent) {
+        POOL_WAIT.observe(event.duration().as_secs_f64());
+    }
+
+    fn handle_timeout(&self, event: diesel::r2d2::event::TimeoutEvent) {
+        POOL_WAIT.observe(event.timeout().as_secs_f64());
+        POOL_TIMEOUTS.inc();
+    }
+}
+
+pub fn record_pool_state(pool: &DbPool) {
+    let state = pool.state();
+    POOL_CONNECTIONS
+        .with_label_values(&["all"])
+        .set(state.connections as i64);
+    POOL_CONNECTIONS
+        .with_label_values(&["idle"])
+        .set(state.idle_connections as i64);
+    // the counters are there from the start instead of after the first checkout
+    once_cell::sync::Lazy::force(&POOL_WAIT);
+    once_cell::sync::Lazy::force(&POOL_TIMEOUTS);
+}
+
+#[cfg(test)]
+mod tests {
+    fn config(vars: &[(&str, &str)]) -> Result<super::PoolConfig, super::PoolConfigError> {
+        super::PoolConfig::from_vars(|name| {
+            vars.iter()
+                .find(|(var, _)| var.eq(&name))
+                .map(|(_, val)| val.to_string())
+        })
+    }
+
+    #[test]
+    fn pool_config_of_env() {
+        assert_eq!(config(&[]), Ok(super::PoolConfig::default()));
+        let parsed = config(&[
+            ("DB_POOL_MAX_SIZE", "4"),
+            ("DB_POOL_MIN_IDLE", "2"),
+            ("DB_POOL_CONNECTION_TIMEOUT_SECS", "5"),
+            ("DB_POOL_IDLE_TIMEOUT_SECS", "0"),
+        ])
+        .unwrap();
+        assert_eq!(parsed.max_size, 4);
+        assert_eq!(parsed.min_idle, Some(2));
+        assert_eq!(parsed.connection_timeout, std::time::Duration::from_secs(5));
+        assert_eq!(parsed.idle_timeout, None);
+        assert_eq!(
+            parsed.max_lifetime,
+            super::PoolConfig::default().max_lifetime
+        );
+
+        assert!(matches!(
+            config(&[("DB_POOL_MAX_SIZE", "ten")]),
+            Err(super::PoolConfigError::NotANumber(..))
+        ));
+        assert!(matches!(
+            config(&[("DB_POOL_MAX_SIZE", "-1")]),
+            Err(super::PoolConfigError::NotANumber(..))
+        ));
+        assert!(matches!(
+            config(&[("DB_POOL_MAX_SIZE", "5000000000")]),
+            Err(super::PoolConfigError::NotANumber(..))
+        ));
+        assert_eq!(
+            config(&[("DB_POOL_MAX_SIZE", "0")]),
+            Err(super::PoolConfigError::MaxSize)
+        );
+        assert_eq!(
+            config(&[("DB_POOL_MAX_SIZE", "4"), ("DB_POOL_MIN_IDLE", "5")]),
+            Err(super::PoolConfigError::MinIdle(5, 4))
+        );
+        assert_eq!(
+            config(&[("DB_POOL_CONNECTION_TIMEOUT_SECS", "0")]),
+            Err(super::PoolConfigError::ConnectionTimeout)
+        );
+    }
+
+    // Note: nothing listens on the port, the checkout waits for the connection timeout
+    #[tokio::test]
+    async fn run_does_not_block_the_runtime() {
+        let pool = diesel::r2d2::Pool::builder()
+            .connection_timeout(std::time::Duration::from_millis(500))
+            .build_unchecked(diesel::r2d2::ConnectionManager::new(
+                "postgres://127.0.0.1:1/auth",
+            ));
+        let query = super::run(&pool, |_| Ok(()));
+        tokio::pin!(query);
+        tokio::select! {
+            _ = &mut query => panic!("checkout is not waiting"),
+            _ = tokio::time::sleep(std::time::Duration::from_millis(50)) => {}
+        }
+        assert!(matches!(
+            query.await,
+            Err(crate::DBError::PooledConnection(_))
+        ));
+    }
+}
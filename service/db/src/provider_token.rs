@@ -0,0 +1,120 @@
+use diesel::prelude::*;
+use diesel::{OptionalExtension, RunQueryDsl};
+
+#[derive(diesel::Queryable)]
+pub struct ProviderTokenDB {
+    pub id: i64,
+    pub user_id: i64,
+    pub provider: String,
+    pub provider_user_id: String,
+    pub access_token: Vec<u8>,
+    pub refresh_token: Option<Vec<u8>>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub refresh_token_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_on: chrono::DateTime<chrono::Utc>,
+    pub updated_on: chrono::DateTime<chrono::Utc>,
+}
+
+// Note: access and refresh tokens are expected to be encrypted already
+pub struct ProviderTokenUpsert {
+    pub access_token: Vec<u8>,
+    pub refresh_token: Option<Vec<u8>>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub refresh_token_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+pub async fn upsert(
+    user_id: i64,
+    provider: &str,
+    provider_user_id: &str,
+    token: ProviderTokenUpsert,
+    pool: &crate::pg::DbPool,
+) -> Result<i64, crate::DBError> {
+    use crate::schema::authapp_user_provider_token;
+    let (provider, provider_user_id) = (provider.to_string(), provider_user_id.to_string());
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        Ok(
+            diesel::insert_into(authapp_user_provider_token::dsl::authapp_user_provider_token)
+                .values((
+                    authapp_user_provider_token::dsl::user_id.eq(user_id),
+                    authapp_user_provider_token::dsl::provider.eq(&provider),
+                    authapp_user_provider_token::dsl::provider_user_id.eq(&provider_user_id),
+                    authapp_user_provider_token::dsl::access_token.eq(&token.access_token),
+                    authapp_user_provider_token::dsl::refresh_token.eq(&token.refresh_token),
+                    authapp_user_provider_token::dsl::expires_at.eq(token.expires_at),
+                    authapp_user_provider_token::dsl::refresh_token_expires_at
+                        .eq(token.refresh_token_expires_at),
+                    authapp_user_provider_token::dsl::created_on.eq(now),
+                    authapp_user_provider_token::dsl::updated_on.eq(now),
+                ))
+                .on_conflict((
+                    authapp_user_provider_token::dsl::user_id,
+                    authapp_user_provider_token::dsl::provider,
+                ))
+                .do_update()
+                .set((
+                    authapp_user_provider_token::dsl::provider_user_id.eq(&provider_user_id),
+                    authapp_user_provider_token::dsl::access_token.eq(&token.access_token),
+                    authapp_user_provider_token::dsl::refresh_token.eq(&token.refresh_token),
+                    authapp_user_provider_token::dsl::expires_at.eq(token.expires_at),
+                    authapp_user_provider_token::dsl::refresh_token_expires_at
+                        .eq(token.refresh_token_expires_at),
+                    authapp_user_provider_token::dsl::updated_on.eq(now),
+                ))
+                .returning(authapp_user_provider_token::dsl::id)
+                .get_result::<i64>(conn)?,
+        )
+    })
+    .await
+}
+
+pub async fn get(
+    user_id: i64,
+    provider: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<Option<ProviderTokenDB>, crate::DBError> {
+    use crate::schema::authapp_user_provider_token;
+    let provider = provider.to_string();
+    crate::pg::run(pool, move |conn| {
+        Ok(
+            authapp_user_provider_token::dsl::authapp_user_provider_token
+                .filter(authapp_user_provider_token::dsl::user_id.eq(user_id))
+                .filter(authapp_user_provider_token::dsl::provider.eq(provider))
+                .select((
+                    authapp_user_provider_token::dsl::id,
+                    authapp_user_provider_token::dsl::user_id,
+                    authapp_user_provider_token::dsl::provider,
+                    authapp_user_provider_token::dsl::provider_user_id,
+                    authapp_user_provider_token::dsl::access_token,
+                    authapp_user_provider_token::dsl::refresh_token,
+                    authapp_user_provider_token::dsl::expires_at,
+                    authapp_user_provider_token::dsl::refresh_token_expires_at,
+                    authapp_user_provider_token::dsl::created_on,
+                    authapp_user_provider_token::dsl::updated_on,
+                ))
+                .get_result::<ProviderTokenDB>(conn)
+                .optional()?,
+        )
+    })
+    .await
+}
+
+pub async fn delete(
+    user_id: i64,
+    provider: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<(), crate::DBError> {
+    use crate::schema::authapp_user_provider_token;
+    let provider = provider.to_string();
+    crate::pg::run(pool, move |conn| {
+        diesel::delete(
+            authapp_user_provider_token::dsl::authapp_user_provider_token
+                .filter(authapp_user_provider_token::dsl::user_id.eq(user_id))
+                .filter(authapp_user_provider_token::dsl::provider.eq(provider)),
+        )
+        .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
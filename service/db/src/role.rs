@@ -0,0 +1,35 @@
+use diesel::prelude::*;
+use diesel::RunQueryDsl;
+
+// roles of the user and the scopes of the roles
+pub async fn user_roles(
+    user_id: i64,
+    pool: &crate::pg::DbPool,
+) -> Result<Vec<(String, String)>, crate::DBError> {
+    use crate::schema::{authapp_role, authapp_user_role};
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_user_role::dsl::authapp_user_role
+            .inner_join(authapp_role::table)
+            .filter(authapp_user_role::dsl::user_id.eq(user_id))
+            .order(authapp_role::dsl::name)
+            .select((authapp_role::dsl::name, authapp_role::dsl::scopes))
+            .load::<(String, String)>(conn)?)
+    })
+    .await
+}
+
+// scopes granted to the user directly
+pub async fn user_scopes(
+    user_id: i64,
+    pool: &crate::pg::DbPool,
+) -> Result<Vec<String>, crate::DBError> {
+    use crate::schema::authapp_user_scope;
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_user_scope::dsl::authapp_user_scope
+            .filter(authapp_user_scope::dsl::user_id.eq(user_id))
+            .order(authapp_user_scope::dsl::scope)
+            .select(authapp_user_scope::dsl::scope)
+            .load::<String>(conn)?)
+    })
+    .await
+}
@@ -1,5 +1,121 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    authapp_audit_event (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        #[max_length = 63]
+        event -> Text,
+        #[max_length = 15]
+        outcome -> Text,
+        #[max_length = 63]
+        reason -> Nullable<Text>,
+        user_id -> Nullable<Int8>,
+        #[max_length = 255]
+        email -> Nullable<Text>,
+        #[max_length = 45]
+        ip -> Nullable<Text>,
+        #[max_length = 512]
+        user_agent -> Nullable<Text>,
+        #[max_length = 64]
+        request_id -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    authapp_machine_client (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 64]
+        client_id -> Text,
+        #[max_length = 64]
+        client_secret_hash -> Text,
+        #[max_length = 127]
+        name -> Text,
+        #[max_length = 255]
+        allowed_scopes -> Text,
+        active -> Bool,
+    }
+}
+
+diesel::table! {
+    authapp_oauth_client (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 64]
+        client_id -> Text,
+        #[max_length = 64]
+        client_secret_hash -> Nullable<Text>,
+        #[max_length = 127]
+        name -> Text,
+        redirect_uris -> Jsonb,
+        #[max_length = 255]
+        allowed_scopes -> Text,
+        active -> Bool,
+        can_introspect -> Bool,
+    }
+}
+
+diesel::table! {
+    authapp_oauth_code (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 64]
+        code_hash -> Text,
+        redirect_uri -> Text,
+        #[max_length = 255]
+        scope -> Text,
+        #[max_length = 255]
+        nonce -> Nullable<Text>,
+        #[max_length = 128]
+        code_challenge -> Nullable<Text>,
+        #[max_length = 10]
+        code_challenge_method -> Nullable<Text>,
+        expires_at -> Timestamptz,
+        used -> Bool,
+        client_id -> Int8,
+        user_id -> Int8,
+    }
+}
+
+diesel::table! {
+    authapp_oauth_device_code (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 64]
+        device_code_hash -> Text,
+        #[max_length = 16]
+        user_code -> Text,
+        #[max_length = 255]
+        scope -> Text,
+        #[max_length = 16]
+        status -> Text,
+        poll_interval -> Int4,
+        last_polled_at -> Nullable<Timestamptz>,
+        expires_at -> Timestamptz,
+        client_id -> Int8,
+        user_id -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    authapp_role (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 63]
+        name -> Text,
+        #[max_length = 255]
+        description -> Nullable<Text>,
+        #[max_length = 1023]
+        scopes -> Text,
+    }
+}
+
 diesel::table! {
     authapp_user (id) {
         id -> Int8,
@@ -16,6 +132,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    authapp_user_api_key (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 127]
+        name -> Text,
+        #[max_length = 16]
+        key_prefix -> Text,
+        #[max_length = 64]
+        key_hash -> Text,
+        #[max_length = 255]
+        scopes -> Text,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        active -> Bool,
+        user_id -> Int8,
+    }
+}
+
 diesel::table! {
     authapp_user_otp (id) {
         id -> Int8,
@@ -32,19 +168,83 @@ diesel::table! {
 }
 
 diesel::table! {
-    authapp_user_token (id) {
+    authapp_user_provider_token (id) {
         id -> Int8,
         created_on -> Timestamptz,
         updated_on -> Timestamptz,
+        #[max_length = 50]
+        provider -> Text,
         #[max_length = 255]
+        provider_user_id -> Text,
+        access_token -> Bytea,
+        user_id -> Int8,
+        refresh_token -> Nullable<Bytea>,
+        expires_at -> Nullable<Timestamptz>,
+        refresh_token_expires_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    authapp_user_role (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        role_id -> Int8,
+        user_id -> Int8,
+    }
+}
+
+diesel::table! {
+    authapp_user_scope (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
+        #[max_length = 127]
+        scope -> Text,
+        user_id -> Int8,
+    }
+}
+
+diesel::table! {
+    authapp_user_token (id) {
+        id -> Int8,
+        created_on -> Timestamptz,
+        updated_on -> Timestamptz,
         token -> Text,
         active -> Bool,
         #[max_length = 255]
         device_number -> Nullable<Text>,
         user_id -> Int8,
+        #[max_length = 64]
+        client_id -> Nullable<Text>,
+        #[max_length = 255]
+        scope -> Nullable<Text>,
     }
 }
 
+diesel::joinable!(authapp_oauth_code -> authapp_oauth_client (client_id));
+diesel::joinable!(authapp_oauth_code -> authapp_user (user_id));
+diesel::joinable!(authapp_oauth_device_code -> authapp_oauth_client (client_id));
+diesel::joinable!(authapp_oauth_device_code -> authapp_user (user_id));
+diesel::joinable!(authapp_user_api_key -> authapp_user (user_id));
+diesel::joinable!(authapp_user_provider_token -> authapp_user (user_id));
+diesel::joinable!(authapp_user_role -> authapp_role (role_id));
+diesel::joinable!(authapp_user_role -> authapp_user (user_id));
+diesel::joinable!(authapp_user_scope -> authapp_user (user_id));
 diesel::joinable!(authapp_user_token -> authapp_user (user_id));
 
-diesel::allow_tables_to_appear_in_same_query!(authapp_user, authapp_user_otp, authapp_user_token,);
+diesel::allow_tables_to_appear_in_same_query!(
+    authapp_audit_event,
+    authapp_machine_client,
+    authapp_oauth_client,
+    authapp_oauth_code,
+    authapp_oauth_device_code,
+    authapp_role,
+    authapp_user,
+    authapp_user_api_key,
+    authapp_user_otp,
+    authapp_user_provider_token,
+    authapp_user_role,
+    authapp_user_scope,
+    authapp_user_token,
+);
@@ -1,63 +1,235 @@
 use diesel::prelude::*;
 
-pub fn upsert_with_email(email: &str, pool: &crate::pg::DbPool) -> Result<i64, crate::DBError> {
+pub async fn upsert_with_email(
+    email: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<i64, crate::DBError> {
     use crate::schema::authapp_user;
-    let mut conn = pool
-        .get()
-        .map_err(|x| crate::DBError::PooledConnection(x.to_string()))?;
-
-    let now = chrono::Utc::now();
-    let id = diesel::insert_into(authapp_user::dsl::authapp_user)
-        .values((
-            authapp_user::dsl::email.eq(email),
-            authapp_user::dsl::active.eq(true),
-            authapp_user::dsl::created_on.eq(now),
-            authapp_user::dsl::updated_on.eq(now),
-        ))
-        .on_conflict(authapp_user::dsl::email)
-        .do_update()
-        .set((
-            authapp_user::dsl::updated_on.eq(now),
-            authapp_user::dsl::last_login.eq(now),
-        ))
-        .returning(authapp_user::dsl::id)
-        .get_result::<i64>(&mut conn)?;
-    Ok(id)
+    let email = email.to_string();
+    crate::pg::run(pool, move |conn| {
+        let now = chrono::Utc::now();
+        let id = diesel::insert_into(authapp_user::dsl::authapp_user)
+            .values((
+                authapp_user::dsl::email.eq(email),
+                authapp_user::dsl::active.eq(true),
+                authapp_user::dsl::created_on.eq(now),
+                authapp_user::dsl::updated_on.eq(now),
+            ))
+            .on_conflict(authapp_user::dsl::email)
+            .do_update()
+            .set((
+                authapp_user::dsl::updated_on.eq(now),
+                authapp_user::dsl::last_login.eq(now),
+            ))
+            .returning(authapp_user::dsl::id)
+            .get_result::<i64>(conn)?;
+        Ok(id)
+    })
+    .await
+}
+
+pub async fn create_token(
+    user_id: i64,
+    token: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<(), crate::DBError> {
+    use crate::schema::authapp_user_token;
+    let token = token.to_string();
+    crate::pg::run(pool, move |conn| {
+        conn.transaction(|conn| {
+            let now = chrono::Utc::now();
+            // Note: access tokens of the oauth clients are not the login sessions, they stay active
+            diesel::update(
+                authapp_user_token::dsl::authapp_user_token
+                    .filter(authapp_user_token::dsl::user_id.eq(user_id))
+                    .filter(authapp_user_token::dsl::client_id.is_null())
+                    .filter(authapp_user_token::dsl::active.eq(true)),
+            )
+            .set((
+                authapp_user_token::dsl::active.eq(false),
+                authapp_user_token::dsl::updated_on.eq(now),
+            ))
+            .execute(conn)?;
+
+            diesel::insert_into(authapp_user_token::dsl::authapp_user_token)
+                .values((
+                    authapp_user_token::dsl::user_id.eq(user_id),
+                    authapp_user_token::dsl::active.eq(true),
+                    authapp_user_token::dsl::token.eq(token),
+                    authapp_user_token::dsl::created_on.eq(now),
+                    authapp_user_token::dsl::updated_on.eq(now),
+                ))
+                .execute(conn)?;
+            diesel::result::QueryResult::Ok(())
+        })?;
+        Ok(())
+    })
+    .await
 }
 
-pub fn create_token(
+// access token issued to the oauth client for the user
+pub async fn create_client_token(
     user_id: i64,
     token: &str,
+    client_id: &str,
+    scope: &str,
     pool: &crate::pg::DbPool,
 ) -> Result<(), crate::DBError> {
     use crate::schema::authapp_user_token;
-    let mut conn = pool
-        .get()
-        .map_err(|x| crate::DBError::PooledConnection(x.to_string()))?;
-    conn.transaction(|conn| {
+    let (token, client_id, scope) = (token.to_string(), client_id.to_string(), scope.to_string());
+    crate::pg::run(pool, move |conn| {
         let now = chrono::Utc::now();
-        diesel::update(
+        diesel::insert_into(authapp_user_token::dsl::authapp_user_token)
+            .values((
+                authapp_user_token::dsl::user_id.eq(user_id),
+                authapp_user_token::dsl::active.eq(true),
+                authapp_user_token::dsl::token.eq(token),
+                authapp_user_token::dsl::client_id.eq(client_id),
+                authapp_user_token::dsl::scope.eq(scope),
+                authapp_user_token::dsl::created_on.eq(now),
+                authapp_user_token::dsl::updated_on.eq(now),
+            ))
+            .execute(conn)?;
+        Ok(())
+    })
+    .await
+}
+
+pub async fn is_token_active(
+    token: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<bool, crate::DBError> {
+    use crate::schema::authapp_user_token;
+    let token = token.to_string();
+    crate::pg::run(pool, move |conn| {
+        Ok(diesel::select(diesel::dsl::exists(
             authapp_user_token::dsl::authapp_user_token
-                .filter(authapp_user_token::dsl::user_id.eq(user_id))
+                .filter(authapp_user_token::dsl::token.eq(token))
+                .filter(authapp_user_token::dsl::active.eq(true)),
+        ))
+        .get_result::<bool>(conn)?)
+    })
+    .await
+}
+
+// Note: returns false if the token is not found or already revoked
+pub async fn revoke_token(token: &str, pool: &crate::pg::DbPool) -> Result<bool, crate::DBError> {
+    use crate::schema::authapp_user_token;
+    let token = token.to_string();
+    crate::pg::run(pool, move |conn| {
+        let updated = diesel::update(
+            authapp_user_token::dsl::authapp_user_token
+                .filter(authapp_user_token::dsl::token.eq(token))
                 .filter(authapp_user_token::dsl::active.eq(true)),
         )
         .set((
             authapp_user_token::dsl::active.eq(false),
-            authapp_user_token::dsl::updated_on.eq(now),
+            authapp_user_token::dsl::updated_on.eq(chrono::Utc::now()),
         ))
         .execute(conn)?;
+        Ok(updated > 0)
+    })
+    .await
+}
 
-        diesel::insert_into(authapp_user_token::dsl::authapp_user_token)
-            .values((
-                authapp_user_token::dsl::user_id.eq(user_id),
-                authapp_user_token::dsl::active.eq(true),
-                authapp_user_token::dsl::token.eq(token),
-                authapp_user_token::dsl::created_on.eq(now),
-                authapp_user_token::dsl::updated_on.eq(now),
+#[derive(diesel::Queryable)]
+pub struct UserDB {
+    pub id: i64,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub active: bool,
+}
+
+pub async fn get(user_id: i64, pool: &crate::pg::DbPool) -> Result<Option<UserDB>, crate::DBError> {
+    use crate::schema::authapp_user;
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_user::dsl::authapp_user
+            .filter(authapp_user::dsl::id.eq(user_id))
+            .select((
+                authapp_user::dsl::id,
+                authapp_user::dsl::name,
+                authapp_user::dsl::email,
+                authapp_user::dsl::phone,
+                authapp_user::dsl::active,
+            ))
+            .get_result::<UserDB>(conn)
+            .optional()?)
+    })
+    .await
+}
+
+pub async fn update_name(
+    user_id: i64,
+    name: &str,
+    pool: &crate::pg::DbPool,
+) -> Result<(), crate::DBError> {
+    use crate::schema::authapp_user;
+    let name = name.to_string();
+    crate::pg::run(pool, move |conn| {
+        diesel::update(authapp_user::dsl::authapp_user.find(user_id))
+            .set((
+                authapp_user::dsl::name.eq(name),
+                authapp_user::dsl::updated_on.eq(chrono::Utc::now()),
             ))
             .execute(conn)?;
-        diesel::result::QueryResult::Ok(())
-    })?;
+        Ok(())
+    })
+    .await
+}
 
-    Ok(())
+#[derive(diesel::Queryable)]
+pub struct UserTokenDB {
+    pub id: i64,
+    pub token: String,
+    pub client_id: Option<String>,
+    pub scope: Option<String>,
+    pub created_on: chrono::DateTime<chrono::Utc>,
+}
+
+// active session and client tokens of the user, latest first
+pub async fn active_tokens(
+    user_id: i64,
+    pool: &crate::pg::DbPool,
+) -> Result<Vec<UserTokenDB>, crate::DBError> {
+    use crate::schema::authapp_user_token;
+    crate::pg::run(pool, move |conn| {
+        Ok(authapp_user_token::dsl::authapp_user_token
+            .filter(authapp_user_token::dsl::user_id.eq(user_id))
+            .filter(authapp_user_token::dsl::active.eq(true))
+            .order(authapp_user_token::dsl::id.desc())
+            .select((
+                authapp_user_token::dsl::id,
+                authapp_user_token::dsl::token,
+                authapp_user_token::dsl::client_id,
+                authapp_user_token::dsl::scope,
+                authapp_user_token::dsl::created_on,
+            ))
+            .load::<UserTokenDB>(conn)?)
+    })
+    .await
+}
+
+// Note: returns false if the token is not found, not of the user or already revoked
+pub async fn revoke_user_token(
+    id: i64,
+    user_id: i64,
+    pool: &crate::pg::DbPool,
+) -> Result<bool, crate::DBError> {
+    use crate::schema::authapp_user_token;
+    crate::pg::run(pool, move |conn| {
+        let updated = diesel::update(
+            authapp_user_token::dsl::authapp_user_token
+                .filter(authapp_user_token::dsl::id.eq(id))
+                .filter(authapp_user_token::dsl::user_id.eq(user_id))
+                .filter(authapp_user_token::dsl::active.eq(true)),
+        )
+        .set((
+            authapp_user_token::dsl::active.eq(false),
+            authapp_user_token::dsl::updated_on.eq(chrono::Utc::now()),
+        ))
+        .execute(conn)?;
+        Ok(updated > 0)
+    })
+    .await
 }
@@ -0,0 +1,119 @@
+// Note: CORS of the browser apps on the other origins, set for a group with `Group::cors`. The
+// router answers the preflight `OPTIONS` of the group's paths with the methods of the path, the
+// other responses of the group get the allow origin headers when the `Origin` is allowed
+#[derive(Clone, Debug)]
+pub struct Cors {
+    // `*` allows any origin without the credentials, with them only the listed origins are allowed
+    origins: Vec<String>,
+    credentials: bool,
+    max_age: std::time::Duration,
+}
+
+impl Cors {
+    pub fn new(origins: Vec<String>) -> Cors {
+        Cors {
+            origins: origins
+                .into_iter()
+                .map(|origin| origin.trim().trim_end_matches('/').to_string())
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+            credentials: false,
+            max_age: std::time::Duration::from_secs(10 * 60),
+        }
+    }
+
+    // cookies and the authorization header are sent by the browser only if this is set
+    pub fn allow_credentials(mut self, credentials: bool) -> Cors {
+        self.credentials = credentials;
+        self
+    }
+
+    // how long the browsers cache the preflight response
+    pub fn max_age(mut self, max_age: std::time::Duration) -> Cors {
+        self.max_age = max_age;
+        self
+    }
+
+    fn allow_origin(
+        &self,
+        origin: &hyper::header::HeaderValue,
+    ) -> Option<hyper::header::HeaderValue> {
+        let any = self.origins.iter().any(|o| o.eq("*"));
+        if any && !self.credentials {
+            return Some(hyper::header::HeaderValue::from_static("*"));
+        }
+        let value = origin.to_str().ok()?;
+        match self.origins.iter().any(|o| o.eq(value)) {
+            true => Some(origin.clone()),
+            false => None,
+        }
+    }
+
+    pub(crate) fn preflight(
+        &self,
+        headers: &hyper::HeaderMap,
+        methods: &[&str],
+    ) -> crate::Response {
+        let allow_origin = match headers
+            .get(hyper::header::ORIGIN)
+            .and_then(|origin| self.allow_origin(origin))
+        {
+            Some(allow_origin) => allow_origin,
+            None => {
+                return crate::json(
+                    serde_json::json!({"message": "origin not allowed", "success": false}),
+                    hyper::StatusCode::FORBIDDEN,
+                )
+            }
+        };
+        let mut response = hyper::Response::new(vec![]);
+        *response.status_mut() = hyper::StatusCode::NO_CONTENT;
+        let h = response.headers_mut();
+        h.insert(hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if let Ok(methods) = hyper::header::HeaderValue::from_str(methods.join(", ").as_str()) {
+            h.insert(hyper::header::ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+        // Note: the requested headers are allowed as they are, the handlers check what they need
+        if let Some(requested) = headers.get(hyper::header::ACCESS_CONTROL_REQUEST_HEADERS) {
+            h.insert(
+                hyper::header::ACCESS_CONTROL_ALLOW_HEADERS,
+                requested.clone(),
+            );
+        }
+        h.insert(
+            hyper::header::ACCESS_CONTROL_MAX_AGE,
+            hyper::header::HeaderValue::from(self.max_age.as_secs()),
+        );
+        self.credentials_and_vary(h);
+        response
+    }
+
+    pub(crate) fn apply(
+        &self,
+        origin: Option<&hyper::header::HeaderValue>,
+        response: &mut crate::Response,
+    ) {
+        if let Some(allow_origin) = origin.and_then(|origin| self.allow_origin(origin)) {
+            let h = response.headers_mut();
+            h.insert(hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+            h.insert(
+                hyper::header::ACCESS_CONTROL_EXPOSE_HEADERS,
+                hyper::header::HeaderValue::from_static(crate::REQUEST_ID_HEADER),
+            );
+            self.credentials_and_vary(h);
+        }
+    }
+
+    fn credentials_and_vary(&self, h: &mut hyper::HeaderMap) {
+        if self.credentials {
+            h.insert(
+                hyper::header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                hyper::header::HeaderValue::from_static("true"),
+            );
+        }
+        h.append(
+            hyper::header::VARY,
+            hyper::header::HeaderValue::from_static("Origin"),
+        );
+    }
+}
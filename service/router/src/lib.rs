@@ -0,0 +1,765 @@
+// Note: the crates register their routes on one `Router` instead of matching the path strings.
+// Paths are matched with the matchit tree, `{name}` is a path param and `{*name}` is the rest of the
+// path. Trailing slash is not part of the route, `/auth/verify` and `/auth/verify/` are the same.
+// A path without the handler for the method is answered with 405 and the `Allow` header, an
+// unknown path with 404. Every route has a body limit and a timeout, the ones over them get 413
+// and 408
+use std::future::Future;
+
+mod cors;
+
+pub use cors::Cors;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+pub const DEFAULT_BODY_LIMIT: usize = 64 * 1024;
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+pub type Response = hyper::Response<Vec<u8>>;
+pub type BoxFuture = std::pin::Pin<Box<dyn Future<Output = Result<Response, BoxError>> + Send>>;
+pub type Handler<S, B> = std::sync::Arc<dyn Fn(hyper::Request<B>, S) -> BoxFuture + Send + Sync>;
+pub type Middleware<S, B> =
+    std::sync::Arc<dyn Fn(hyper::Request<B>, S, Next<S, B>) -> BoxFuture + Send + Sync>;
+
+// rest of the chain of a middleware, the other middlewares of the group and the handler
+pub struct Next<S, B> {
+    handler: Handler<S, B>,
+}
+
+impl<S, B> Next<S, B> {
+    pub fn run(self, req: hyper::Request<B>, state: S) -> BoxFuture {
+        (self.handler)(req, state)
+    }
+}
+
+// path params of the matched route, in the request extensions
+#[derive(Clone, Debug, Default)]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    // Note: values are as they are in the path, they are not percent decoded
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(key, _)| key.eq(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub fn param<'a, B>(req: &'a hyper::Request<B>, name: &str) -> Option<&'a str> {
+    req.extensions().get::<Params>()?.get(name)
+}
+
+// id of the request in the `X-Request-Id` header and in the logs, set by the service for every
+// request
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+// address of the peer of the connection, the reverse proxy if there is one, set by the service
+// for every request
+#[derive(Clone, Copy, Debug)]
+pub struct RemoteAddr(pub std::net::SocketAddr);
+
+// pattern of the matched route like `/oauth/{id}`, in the request and the response extensions, for
+// the metrics and the logs which can not have every path
+#[derive(Clone, Debug)]
+pub struct MatchedPath(pub String);
+
+// body limit of the matched route, in the request extensions for `body`
+#[derive(Clone, Copy, Debug)]
+pub struct BodyLimit(pub usize);
+
+#[derive(thiserror::Error, Debug)]
+pub enum BodyError {
+    #[error("BodyTooLarge: over {} bytes", _0)]
+    TooLarge(usize),
+    #[error("BodyReadError: {}", _0)]
+    Read(String),
+}
+
+// Note: the body is read up to the limit of the route, `Content-Length` over it is rejected before
+// the handler but the chunked bodies are known only while reading them. Handlers return the
+// `TooLarge` error (as the source of their errors) for the router to answer 413
+pub async fn body<B>(
+    extensions: &hyper::http::Extensions,
+    body: B,
+) -> Result<hyper::body::Bytes, BodyError>
+where
+    B: hyper::body::Body,
+    B::Error: Into<BoxError>,
+{
+    use http_body_util::BodyExt;
+
+    let limit = extensions
+        .get::<BodyLimit>()
+        .map(|limit| limit.0)
+        .unwrap_or(DEFAULT_BODY_LIMIT);
+    match http_body_util::Limited::new(body, limit).collect().await {
+        Ok(collected) => Ok(collected.to_bytes()),
+        Err(err) if err.is::<http_body_util::LengthLimitError>() => Err(BodyError::TooLarge(limit)),
+        Err(err) => Err(BodyError::Read(err.to_string())),
+    }
+}
+
+// handler of a path for the method, `None` method handles all the methods without their own
+// handler
+struct Route<S, B> {
+    method: Option<hyper::Method>,
+    handler: Handler<S, B>,
+    body_limit: usize,
+    timeout: std::time::Duration,
+    cors: Option<std::sync::Arc<Cors>>,
+}
+
+type Endpoint<S, B> = Vec<Route<S, B>>;
+
+pub struct Router<S, B = hyper::body::Incoming> {
+    tree: matchit::Router<usize>,
+    paths: std::collections::HashMap<String, usize>,
+    endpoints: Vec<Endpoint<S, B>>,
+    patterns: Vec<String>,
+}
+
+impl<S, B> Default for Router<S, B>
+where
+    S: Send + 'static,
+    B: Send + 'static,
+{
+    fn default() -> Self {
+        Router::new()
+    }
+}
+
+impl<S, B> Router<S, B>
+where
+    S: Send + 'static,
+    B: Send + 'static,
+{
+    pub fn new() -> Router<S, B> {
+        Router {
+            tree: matchit::Router::new(),
+            paths: Default::default(),
+            patterns: vec![],
+            endpoints: vec![],
+        }
+    }
+
+    // routes of the group are under the prefix and go through the middlewares of the group
+    pub fn group(&mut self, prefix: &str) -> Group<'_, S, B> {
+        Group {
+            router: self,
+            prefix: prefix.trim_end_matches('/').to_string(),
+            middleware: vec![],
+            body_limit: DEFAULT_BODY_LIMIT,
+            timeout: DEFAULT_TIMEOUT,
+            cors: None,
+        }
+    }
+
+    pub fn route<F, Fut, E>(&mut self, method: hyper::Method, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.group("").route(method, path, handler);
+        self
+    }
+
+    pub fn get<F, Fut, E>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.route(hyper::Method::GET, path, handler)
+    }
+
+    pub fn post<F, Fut, E>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.route(hyper::Method::POST, path, handler)
+    }
+
+    pub fn any<F, Fut, E>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.group("").any(path, handler);
+        self
+    }
+
+    // Note: routes are registered at the start, the conflicting ones are bugs so they panic
+    fn insert(&mut self, path: &str, route: Route<S, B>) {
+        let path = normalize(path).to_string();
+        let index = match self.paths.get(path.as_str()) {
+            Some(index) => *index,
+            None => {
+                let index = self.endpoints.len();
+                if let Err(err) = self.tree.insert(path.as_str(), index) {
+                    panic!("invalid route {}: {}", path, err);
+                }
+                self.paths.insert(path.clone(), index);
+                self.endpoints.push(vec![]);
+                self.patterns.push(path.clone());
+                index
+            }
+        };
+        let endpoint = &mut self.endpoints[index];
+        if endpoint.iter().any(|r| r.method.eq(&route.method)) {
+            panic!("route is already registered: {:?} {}", route.method, path);
+        }
+        endpoint.push(route);
+    }
+
+    // Note: pattern of the route of the path, for the responses made outside of the router, e.g.
+    // the error of the handler is answered by the caller of `handle`
+    pub fn matched_path(&self, path: &str) -> Option<MatchedPath> {
+        self.tree
+            .at(normalize(path))
+            .ok()
+            .map(|matched| MatchedPath(self.patterns[*matched.value].clone()))
+    }
+
+    pub async fn handle(&self, mut req: hyper::Request<B>, state: S) -> Result<Response, BoxError> {
+        let (index, params) = match self.tree.at(normalize(req.uri().path())) {
+            Ok(matched) => (
+                *matched.value,
+                Params(
+                    matched
+                        .params
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect(),
+                ),
+            ),
+            Err(_) => return Ok(not_found(req.uri().path())),
+        };
+        let matched = MatchedPath(self.patterns[index].clone());
+        req.extensions_mut().insert(matched.clone());
+        let mut response = self.dispatch(index, req, state, params).await?;
+        response.extensions_mut().insert(matched);
+        Ok(response)
+    }
+
+    async fn dispatch(
+        &self,
+        index: usize,
+        req: hyper::Request<B>,
+        state: S,
+        params: Params,
+    ) -> Result<Response, BoxError> {
+        let endpoint = &self.endpoints[index];
+        if req.method() == hyper::Method::OPTIONS
+            && req
+                .headers()
+                .contains_key(hyper::header::ACCESS_CONTROL_REQUEST_METHOD)
+        {
+            if let Some(cors) = endpoint.iter().find_map(|r| r.cors.as_ref()) {
+                return Ok(cors.preflight(req.headers(), &methods(endpoint)));
+            }
+        }
+        let find =
+            |method: &hyper::Method| endpoint.iter().find(|r| r.method.as_ref() == Some(method));
+        // Note: HEAD is answered by the GET handler, hyper does not send the body of it
+        let route = find(req.method())
+            .or_else(|| match req.method() == hyper::Method::HEAD {
+                true => find(&hyper::Method::GET),
+                false => None,
+            })
+            .or_else(|| endpoint.iter().find(|r| r.method.is_none()));
+        let route = match route {
+            Some(route) => route,
+            None => return Ok(method_not_allowed(endpoint)),
+        };
+
+        let origin = req.headers().get(hyper::header::ORIGIN).cloned();
+        let mut response = call(route, req, state, params).await?;
+        if let Some(ref cors) = route.cors {
+            cors.apply(origin.as_ref(), &mut response);
+        }
+        Ok(response)
+    }
+}
+
+pub struct Group<'a, S, B> {
+    router: &'a mut Router<S, B>,
+    prefix: String,
+    middleware: Vec<Middleware<S, B>>,
+    body_limit: usize,
+    timeout: std::time::Duration,
+    cors: Option<std::sync::Arc<Cors>>,
+}
+
+impl<S, B> Group<'_, S, B>
+where
+    S: Send + 'static,
+    B: Send + 'static,
+{
+    // Note: the middleware applies to the routes added after it, the first one added runs first
+    pub fn layer<F, Fut>(&mut self, middleware: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S, Next<S, B>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, BoxError>> + Send + 'static,
+    {
+        self.middleware
+            .push(std::sync::Arc::new(move |req, state, next| {
+                Box::pin(middleware(req, state, next))
+            }));
+        self
+    }
+
+    // Note: like the middlewares, the limit and the timeout apply to the routes added after them
+    pub fn body_limit(&mut self, bytes: usize) -> &mut Self {
+        self.body_limit = bytes;
+        self
+    }
+
+    pub fn timeout(&mut self, timeout: std::time::Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn cors(&mut self, cors: Cors) -> &mut Self {
+        self.cors = Some(std::sync::Arc::new(cors));
+        self
+    }
+
+    // nested group, it has the middlewares, the limit and the timeout of this group as well
+    pub fn group(&mut self, prefix: &str) -> Group<'_, S, B> {
+        Group {
+            router: &mut *self.router,
+            prefix: format!("{}{}", self.prefix, prefix.trim_end_matches('/')),
+            middleware: self.middleware.clone(),
+            body_limit: self.body_limit,
+            timeout: self.timeout,
+            cors: self.cors.clone(),
+        }
+    }
+
+    pub fn route<F, Fut, E>(&mut self, method: hyper::Method, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.insert(Some(method), path, boxed(handler));
+        self
+    }
+
+    pub fn get<F, Fut, E>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.route(hyper::Method::GET, path, handler)
+    }
+
+    pub fn post<F, Fut, E>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.route(hyper::Method::POST, path, handler)
+    }
+
+    pub fn any<F, Fut, E>(&mut self, path: &str, handler: F) -> &mut Self
+    where
+        F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Response, E>> + Send + 'static,
+        E: Into<BoxError>,
+    {
+        self.insert(None, path, boxed(handler));
+        self
+    }
+
+    fn insert(&mut self, method: Option<hyper::Method>, path: &str, handler: Handler<S, B>) {
+        let handler = self
+            .middleware
+            .iter()
+            .rev()
+            .fold(handler, |next, middleware| {
+                let middleware = middleware.clone();
+                std::sync::Arc::new(move |req, state| {
+                    middleware(
+                        req,
+                        state,
+                        Next {
+                            handler: next.clone(),
+                        },
+                    )
+                })
+            });
+        let path = format!("{}{}", self.prefix, path);
+        self.router.insert(
+            path.as_str(),
+            Route {
+                method,
+                handler,
+                body_limit: self.body_limit,
+                timeout: self.timeout,
+                cors: self.cors.clone(),
+            },
+        );
+    }
+}
+
+async fn call<S, B>(
+    route: &Route<S, B>,
+    mut req: hyper::Request<B>,
+    state: S,
+    params: Params,
+) -> Result<Response, BoxError> {
+    let content_length = req
+        .headers()
+        .get(hyper::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if content_length.is_some_and(|length| length > route.body_limit) {
+        return Ok(payload_too_large());
+    }
+    req.extensions_mut().insert(params);
+    req.extensions_mut().insert(BodyLimit(route.body_limit));
+    match tokio::time::timeout(route.timeout, (route.handler)(req, state)).await {
+        Ok(Err(err)) if is_too_large(&*err) => Ok(payload_too_large()),
+        Ok(result) => result,
+        Err(_) => Ok(json(
+            serde_json::json!({"message": "request timeout", "success": false}),
+            hyper::StatusCode::REQUEST_TIMEOUT,
+        )),
+    }
+}
+
+fn boxed<S, B, F, Fut, E>(handler: F) -> Handler<S, B>
+where
+    F: Fn(hyper::Request<B>, S) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Response, E>> + Send + 'static,
+    E: Into<BoxError>,
+{
+    std::sync::Arc::new(move |req, state| {
+        let response = handler(req, state);
+        Box::pin(async move { response.await.map_err(Into::into) })
+    })
+}
+
+fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+pub fn json(body: serde_json::Value, status: hyper::StatusCode) -> Response {
+    let mut response = hyper::Response::new(body.to_string().into_bytes());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+fn not_found(path: &str) -> Response {
+    json(
+        serde_json::json!({"message": format!("route not found: {}", path), "success": false}),
+        hyper::StatusCode::NOT_FOUND,
+    )
+}
+
+fn payload_too_large() -> Response {
+    json(
+        serde_json::json!({"message": "request body is too large", "success": false}),
+        hyper::StatusCode::PAYLOAD_TOO_LARGE,
+    )
+}
+
+fn is_too_large(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(err) = source {
+        if let Some(BodyError::TooLarge(_)) = err.downcast_ref::<BodyError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+// Note: html pages must not be framed by the other sites, and are served over https only
+pub fn html(body: Vec<u8>) -> Response {
+    let mut response = hyper::Response::new(body);
+    let h = response.headers_mut();
+    h.insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    h.insert(
+        hyper::header::STRICT_TRANSPORT_SECURITY,
+        hyper::header::HeaderValue::from_static("max-age=63072000; includeSubDomains"),
+    );
+    h.insert(
+        hyper::header::X_CONTENT_TYPE_OPTIONS,
+        hyper::header::HeaderValue::from_static("nosniff"),
+    );
+    h.insert(
+        hyper::header::CONTENT_SECURITY_POLICY,
+        hyper::header::HeaderValue::from_static("frame-ancestors 'none'"),
+    );
+    h.insert(
+        hyper::header::X_FRAME_OPTIONS,
+        hyper::header::HeaderValue::from_static("DENY"),
+    );
+    h.insert(
+        hyper::header::REFERRER_POLICY,
+        hyper::header::HeaderValue::from_static("same-origin"),
+    );
+    response
+}
+
+fn methods<S, B>(endpoint: &Endpoint<S, B>) -> Vec<&str> {
+    let mut methods: Vec<&str> = endpoint
+        .iter()
+        .filter_map(|r| r.method.as_ref().map(|m| m.as_str()))
+        .collect();
+    if methods.contains(&"GET") {
+        methods.push("HEAD");
+    }
+    methods
+}
+
+fn method_not_allowed<S, B>(endpoint: &Endpoint<S, B>) -> Response {
+    let methods = methods(endpoint);
+    let mut response = json(
+        serde_json::json!({"message": "method not allowed", "success": false}),
+        hyper::StatusCode::METHOD_NOT_ALLOWED,
+    );
+    if let Ok(allow) = hyper::header::HeaderValue::from_str(methods.join(", ").as_str()) {
+        response.headers_mut().insert(hyper::header::ALLOW, allow);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    async fn echo(req: hyper::Request<String>, _: ()) -> Result<super::Response, super::BoxError> {
+        let body = super::param(&req, "id").unwrap_or("none").to_string();
+        Ok(hyper::Response::new(body.into_bytes()))
+    }
+
+    fn request(method: hyper::Method, path: &str) -> hyper::Request<String> {
+        hyper::Request::builder()
+            .method(method)
+            .uri(path)
+            .body(String::new())
+            .unwrap()
+    }
+
+    fn router() -> super::Router<(), String> {
+        let mut router = super::Router::new();
+        router.get("/users/{id}/", echo).post("/users", echo);
+        router
+            .group("/admin/")
+            .layer(
+                |req: hyper::Request<String>, state, next: super::Next<(), String>| async move {
+                    match req.headers().contains_key(hyper::header::AUTHORIZATION) {
+                        true => next.run(req, state).await,
+                        false => Ok(super::json(
+                            serde_json::json!({"success": false}),
+                            hyper::StatusCode::UNAUTHORIZED,
+                        )),
+                    }
+                },
+            )
+            .any("/{id}", echo);
+        router
+            .group("/uploads")
+            .body_limit(8)
+            .timeout(std::time::Duration::from_millis(20))
+            .post("/", upload)
+            .get("/slow/", |_, _| async {
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                Ok::<_, super::BoxError>(hyper::Response::new(vec![]))
+            });
+        router
+    }
+
+    fn cors_router() -> super::Router<(), String> {
+        let mut router = super::Router::new();
+        router
+            .group("/api")
+            .cors(
+                super::Cors::new(vec!["https://app.example.com".to_string()])
+                    .allow_credentials(true),
+            )
+            .get("/profile/", echo)
+            .post("/profile/", echo);
+        router
+    }
+
+    async fn upload(
+        req: hyper::Request<String>,
+        _: (),
+    ) -> Result<super::Response, super::BoxError> {
+        let (parts, body) = req.into_parts();
+        let body = super::body(&parts.extensions, body).await?;
+        Ok(hyper::Response::new(body.to_vec()))
+    }
+
+    #[tokio::test]
+    async fn params_and_trailing_slash() {
+        let router = router();
+        for path in ["/users/42", "/users/42/"] {
+            let response = router
+                .handle(request(hyper::Method::GET, path), ())
+                .await
+                .unwrap();
+            assert_eq!(response.body(), b"42");
+            assert_eq!(
+                response.extensions().get::<super::MatchedPath>().unwrap().0,
+                "/users/{id}"
+            );
+        }
+        let response = router
+            .handle(request(hyper::Method::POST, "/users/"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.body(), b"none");
+    }
+
+    #[tokio::test]
+    async fn method_not_allowed_and_not_found() {
+        let router = router();
+        let response = router
+            .handle(request(hyper::Method::DELETE, "/users/42"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[hyper::header::ALLOW], "GET, HEAD");
+
+        let response = router
+            .handle(request(hyper::Method::HEAD, "/users/42"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+
+        let response = router
+            .handle(request(hyper::Method::GET, "/groups/42"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::NOT_FOUND);
+        assert!(response.extensions().get::<super::MatchedPath>().is_none());
+    }
+
+    #[test]
+    fn matched_path_of_paths() {
+        let router = router();
+        assert_eq!(
+            router.matched_path("/users/42/").map(|matched| matched.0),
+            Some("/users/{id}".to_string())
+        );
+        assert!(router.matched_path("/groups/42").is_none());
+    }
+
+    #[tokio::test]
+    async fn group_middleware() {
+        let router = router();
+        let response = router
+            .handle(request(hyper::Method::PUT, "/admin/7"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::UNAUTHORIZED);
+
+        let mut req = request(hyper::Method::PUT, "/admin/7");
+        req.headers_mut().insert(
+            hyper::header::AUTHORIZATION,
+            hyper::header::HeaderValue::from_static("Bearer token"),
+        );
+        let response = router.handle(req, ()).await.unwrap();
+        assert_eq!(response.body(), b"7");
+    }
+
+    #[tokio::test]
+    async fn body_limit_and_timeout() {
+        let router = router();
+        let mut req = request(hyper::Method::POST, "/uploads");
+        *req.body_mut() = "12345678".to_string();
+        let response = router.handle(req, ()).await.unwrap();
+        assert_eq!(response.body(), b"12345678");
+
+        // Note: there is no `Content-Length`, the limit is hit while reading the body
+        let mut req = request(hyper::Method::POST, "/uploads");
+        *req.body_mut() = "123456789".to_string();
+        let response = router.handle(req, ()).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut req = request(hyper::Method::POST, "/uploads");
+        req.headers_mut().insert(
+            hyper::header::CONTENT_LENGTH,
+            hyper::header::HeaderValue::from_static("1024"),
+        );
+        let response = router.handle(req, ()).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = router
+            .handle(request(hyper::Method::GET, "/uploads/slow"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn cors_preflight() {
+        let router = cors_router();
+        let preflight = |origin: &'static str| {
+            let mut req = request(hyper::Method::OPTIONS, "/api/profile/");
+            let h = req.headers_mut();
+            h.insert(
+                hyper::header::ORIGIN,
+                hyper::header::HeaderValue::from_static(origin),
+            );
+            h.insert(
+                hyper::header::ACCESS_CONTROL_REQUEST_METHOD,
+                hyper::header::HeaderValue::from_static("POST"),
+            );
+            req
+        };
+        let response = router
+            .handle(preflight("https://app.example.com"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::NO_CONTENT);
+        let h = response.headers();
+        assert_eq!(
+            h[hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(
+            h[hyper::header::ACCESS_CONTROL_ALLOW_METHODS],
+            "GET, POST, HEAD"
+        );
+        assert_eq!(h[hyper::header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+
+        let response = router
+            .handle(preflight("https://evil.example.com"), ())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::FORBIDDEN);
+
+        let mut req = request(hyper::Method::GET, "/api/profile");
+        req.headers_mut().insert(
+            hyper::header::ORIGIN,
+            hyper::header::HeaderValue::from_static("https://app.example.com"),
+        );
+        let response = router.handle(req, ()).await.unwrap();
+        assert_eq!(
+            response.headers()[hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+    }
+}
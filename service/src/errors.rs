@@ -10,4 +10,6 @@ pub enum RouteError {
     FileReadError(#[from] std::io::Error),
     #[error("AIError")]
     AIError(#[from] ai::apis::AIError),
+    #[error("HandlerError: {0}")]
+    Handler(#[from] router::BoxError),
 }
@@ -0,0 +1,118 @@
+use hyper::body::Incoming;
+
+// Note: `/healthz` answers while the process serves requests, for the liveness probe. `/readyz`
+// checks what the requests depend on, the readiness probe takes the instance out of the load
+// balancer with the 503 instead of restarting it
+const DB_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(serde::Serialize, Debug)]
+struct Check {
+    ok: bool,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Check {
+    fn new(started: std::time::Instant, result: Result<(), String>) -> Check {
+        let latency_ms = started.elapsed().as_millis();
+        match result {
+            Ok(()) => Check {
+                ok: true,
+                latency_ms,
+                error: None,
+            },
+            Err(error) => Check {
+                ok: false,
+                latency_ms,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+pub fn register(router: &mut router::Router<db::pg::DbPool>) {
+    router.get("/healthz", healthz).get("/readyz", readyz);
+}
+
+async fn healthz(
+    _req: hyper::Request<Incoming>,
+    _db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, http_service::errors::RouteError> {
+    Ok(router::json(
+        serde_json::json!({"success": true}),
+        hyper::StatusCode::OK,
+    ))
+}
+
+async fn readyz(
+    _req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, http_service::errors::RouteError> {
+    let (database, email, jwt) = tokio::join!(
+        blocking(move || db::pg::ping(&db_pool, DB_TIMEOUT).map_err(|e| e.to_string())),
+        blocking(auth::communication::check_config),
+        blocking(auth::jwt::check_keys),
+    );
+    Ok(readiness(
+        [("database", database), ("email", email), ("jwt", jwt)].into_iter(),
+    ))
+}
+
+async fn blocking<F>(check: F) -> Check
+where
+    F: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    let started = std::time::Instant::now();
+    let result = match tokio::task::spawn_blocking(check).await {
+        Ok(result) => result,
+        Err(e) => Err(e.to_string()),
+    };
+    Check::new(started, result)
+}
+
+fn readiness(checks: impl Iterator<Item = (&'static str, Check)>) -> hyper::Response<Vec<u8>> {
+    let checks: std::collections::BTreeMap<_, _> = checks.collect();
+    let ready = checks.values().all(|check| check.ok);
+    for (name, check) in checks.iter().filter(|(_, check)| !check.ok) {
+        tracing::warn!(
+            message = "readiness check failed",
+            check = name,
+            error = check.error.as_deref().unwrap_or_default()
+        );
+    }
+    router::json(
+        serde_json::json!({"ready": ready, "checks": checks}),
+        match ready {
+            true => hyper::StatusCode::OK,
+            false => hyper::StatusCode::SERVICE_UNAVAILABLE,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn not_ready_if_any_check_fails() {
+        let started = std::time::Instant::now();
+        let response = super::readiness(
+            [
+                ("database", super::Check::new(started, Ok(()))),
+                (
+                    "email",
+                    super::Check::new(started, Err("BREVO_API_KEY is empty".to_string())),
+                ),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(response.status(), hyper::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(body["ready"], false);
+        assert_eq!(body["checks"]["database"]["ok"], true);
+        assert_eq!(body["checks"]["email"]["error"], "BREVO_API_KEY is empty");
+
+        let response =
+            super::readiness([("database", super::Check::new(started, Ok(())))].into_iter());
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+    }
+}
@@ -2,4 +2,9 @@ extern crate self as http_service;
 
 pub mod controller;
 pub mod errors;
+pub mod health;
+pub mod metrics;
+pub mod middleware;
 pub mod route;
+pub mod server;
+pub mod telemetry;
@@ -1,11 +1,12 @@
 use hyper::body::Incoming;
 
+#[derive(Clone)]
 pub struct HttpService {
     pool: db::pg::DbPool,
 }
 
 impl hyper::service::Service<hyper::Request<Incoming>> for HttpService {
-    type Response = hyper::Response<Vec<u8>>;
+    type Response = hyper::Response<http_body_util::Full<hyper::body::Bytes>>;
     type Error = hyper::Error;
     type Future = std::pin::Pin<
         Box<dyn futures::Future<Output = Result<Self::Response, Self::Error>> + Send>,
@@ -14,17 +15,9 @@ impl hyper::service::Service<hyper::Request<Incoming>> for HttpService {
     fn call(&self, req: hyper::Request<Incoming>) -> Self::Future {
         let pool = self.pool.clone();
         Box::pin(async move {
-            match service::route::handler(req, pool).await {
-                Ok(r) => Ok(r),
-                Err(e) => {
-                    tracing::error!(message = "error:route::handler", error = format!("{e}"));
-                    Ok(service::controller::response(
-                        serde_json::json!({"message": "Internal Server Error","success": false})
-                            .to_string(),
-                        hyper::StatusCode::INTERNAL_SERVER_ERROR,
-                    ))
-                }
-            }
+            Ok(service::middleware::serve(req, pool)
+                .await
+                .map(http_body_util::Full::from))
         })
     }
 }
@@ -38,38 +31,45 @@ pub fn read_env() -> String {
 
 async fn http_main() -> Result<(), Box<dyn std::error::Error + Sync + Send>> {
     // Setting the environment variables
-    // tracing_subscriber::fmt().init();
-    // let env_path = format!("{}.env", read_env());
-    // dotenv::from_path(env_path.as_str()).ok();
-    // tracing::info!("Environment set: {}", env_path);
+    let env_path = format!("{}.env", read_env());
+    dotenv::from_path(env_path.as_str()).ok();
+    let telemetry = service::telemetry::init(&service::telemetry::Config::from_env()?)?;
+    tracing::info!("Environment set: {}", env_path);
+    auth::jwt::check_secret()?;
+    auth::audit::check_trusted_proxies()?;
+    auth::controller::check_cors()?;
+    auth::session::check_session_config()?;
 
-    // let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL env var not found");
-    // Initializing the database pool
-    // db::redis::init_redis_pool();
+    let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL env var not found");
+    let pool = db::pg::get_connection_pool(db_url.as_str(), &db::pg::PoolConfig::from_env()?);
+
+    let port = match std::env::var("PORT") {
+        Ok(port) => port.parse()?,
+        Err(_) => 8001,
+    };
+    let tls = service::server::Tls::from_env()?.map(std::sync::Arc::new);
+    if let Some(tls) = tls.clone() {
+        service::server::reload_on_sighup(tls)?;
+    }
 
     // Creating the tcp listener
-    // let socket_address: std::net::SocketAddr = ([0, 0, 0, 0], 8001).into();
-    // let listener = tokio::net::TcpListener::bind(socket_address).await?;
-    // tracing::info!(
-    //     "#### Started at: {}:{} ####",
-    //     socket_address.ip(),
-    //     socket_address.port()
-    // );
-    // let db_url = db_url.clone();
-    // let pool = db::pg::get_connection_pool(db_url.as_str());
-    // loop {
-    //     let pool = pool.clone();
-    //     let (tcp_stream, _) = listener.accept().await?;
-    //     tokio::task::spawn(async move {
-    //         if let Err(http_err) = hyper::server::conn::http1::Builder::new()
-    //             .serve_connection(  tcp_stream, HttpService { pool })
-    //             .await
-    //         {
-    //             eprintln!("Error while serving HTTP connection: {}", http_err);
-    //         }
-    //     });
-    // }
-    todo!()
+    let socket_address: std::net::SocketAddr = ([0, 0, 0, 0], port).into();
+    let listener = tokio::net::TcpListener::bind(socket_address).await?;
+    tracing::info!(
+        "#### Started at: {}:{} (tls: {}) ####",
+        socket_address.ip(),
+        socket_address.port(),
+        tls.is_some()
+    );
+    let shutdown = service::server::shutdown_signal()?;
+    // Note: `serve` runs until the signal, the open connections are dropped with the runtime
+    let served = tokio::select! {
+        served = service::server::serve(listener, tls, HttpService { pool }) => served,
+        _ = shutdown => Ok(()),
+    };
+    // Note: the export of the last batch blocks until the collector answers
+    tokio::task::spawn_blocking(move || telemetry.shutdown()).await?;
+    Ok(served?)
 }
 
 fn main() {
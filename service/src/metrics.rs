@@ -0,0 +1,141 @@
+use hyper::body::Incoming;
+
+// Note: `/metrics` in the prometheus text format, the metrics of all the crates are in the default
+// registry. Requests are labelled with the pattern of the matched route, not the path, so that the
+// ids in the paths do not make new series. It is not authenticated, keep it off the public proxy
+static HTTP_REQUESTS: once_cell::sync::Lazy<prometheus::IntCounterVec> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_int_counter_vec!(
+            "http_requests_total",
+            "Requests by the method, the matched route and the status",
+            &["method", "route", "status"]
+        )
+        .expect("http_requests_total is registered once")
+    });
+
+static HTTP_LATENCY: once_cell::sync::Lazy<prometheus::HistogramVec> =
+    once_cell::sync::Lazy::new(|| {
+        prometheus::register_histogram_vec!(
+            "http_request_duration_seconds",
+            "Latency of the requests by the method, the matched route and the status",
+            &["method", "route", "status"]
+        )
+        .expect("http_request_duration_seconds is registered once")
+    });
+
+// route label of the requests which did not match any route
+const UNMATCHED: &str = "unmatched";
+
+pub fn register(router: &mut router::Router<db::pg::DbPool>) {
+    router.get("/metrics", metrics);
+}
+
+pub(crate) fn record(
+    method: &hyper::Method,
+    response: &hyper::Response<Vec<u8>>,
+    latency: std::time::Duration,
+) {
+    let route = response
+        .extensions()
+        .get::<router::MatchedPath>()
+        .map(|matched| matched.0.as_str())
+        .unwrap_or(UNMATCHED);
+    let status = response.status();
+    let labels = [method_label(method), route, status.as_str()];
+    HTTP_REQUESTS.with_label_values(&labels).inc();
+    HTTP_LATENCY
+        .with_label_values(&labels)
+        .observe(latency.as_secs_f64());
+}
+
+// methods are sent by the clients, the unknown ones are one label
+fn method_label(method: &hyper::Method) -> &'static str {
+    match *method {
+        hyper::Method::GET => "GET",
+        hyper::Method::POST => "POST",
+        hyper::Method::PUT => "PUT",
+        hyper::Method::PATCH => "PATCH",
+        hyper::Method::DELETE => "DELETE",
+        hyper::Method::HEAD => "HEAD",
+        hyper::Method::OPTIONS => "OPTIONS",
+        _ => "OTHER",
+    }
+}
+
+async fn metrics(
+    _req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, http_service::errors::RouteError> {
+    db::pg::record_pool_state(&db_pool);
+    auth::metrics::init();
+    let mut response = hyper::Response::new(encode()?);
+    response.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+    );
+    Ok(response)
+}
+
+fn encode() -> Result<Vec<u8>, router::BoxError> {
+    use prometheus::Encoder;
+
+    let mut buffer = vec![];
+    prometheus::TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    // Note: the counters are in the default registry which other tests write too, the test looks
+    // at the change of its own series
+    fn requests(labels: [&str; 3]) -> u64 {
+        super::HTTP_REQUESTS.with_label_values(&labels).get()
+    }
+
+    #[test]
+    fn requests_by_route() {
+        let matched = ["GET", "/oauth/{id}", "200"];
+        let unmatched = ["OTHER", "unmatched", "200"];
+        let (before_matched, before_unmatched) = (requests(matched), requests(unmatched));
+
+        let mut response = hyper::Response::new(vec![]);
+        response
+            .extensions_mut()
+            .insert(router::MatchedPath("/oauth/{id}".to_string()));
+        super::record(
+            &hyper::Method::GET,
+            &response,
+            std::time::Duration::from_millis(5),
+        );
+        super::record(
+            &hyper::Method::from_bytes(b"PURGE").unwrap(),
+            &hyper::Response::new(vec![]),
+            std::time::Duration::from_millis(5),
+        );
+
+        assert_eq!(requests(matched), before_matched + 1);
+        assert_eq!(requests(unmatched), before_unmatched + 1);
+        let text = String::from_utf8(super::encode().unwrap()).unwrap();
+        assert!(
+            text.contains(r#"http_requests_total{method="GET",route="/oauth/{id}",status="200"}"#)
+        );
+        assert!(text.contains("http_request_duration_seconds_bucket"));
+    }
+
+    #[test]
+    fn failed_handler_is_counted_by_route() {
+        let labels = ["GET", "/auth/health", "500"];
+        let before = requests(labels);
+        let mut response = hyper::Response::new(vec![]);
+        *response.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
+        response
+            .extensions_mut()
+            .insert(crate::route::matched_path("/auth/health").unwrap());
+        super::record(
+            &hyper::Method::GET,
+            &response,
+            std::time::Duration::from_millis(5),
+        );
+        assert_eq!(requests(labels), before + 1);
+    }
+}
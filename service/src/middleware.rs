@@ -0,0 +1,105 @@
+use hyper::body::Incoming;
+use tracing::Instrument;
+
+// Note: every request goes through here before the router. The request gets an id (the one of the
+// proxy in `X-Request-Id` if it is valid) which is in the span of the request, so in every log of
+// the handlers, and in the response. The access log is written once the response is ready
+pub async fn serve(
+    mut req: hyper::Request<Incoming>,
+    db_pool: db::pg::DbPool,
+) -> hyper::Response<Vec<u8>> {
+    let start = std::time::Instant::now();
+    let request_id = request_id(req.headers());
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let span = tracing::info_span!(
+        "request",
+        request_id = request_id.as_str(),
+        method = method.as_str(),
+        path = path.as_str()
+    );
+    req.extensions_mut()
+        .insert(router::RequestId(request_id.clone()));
+
+    let mut response = async {
+        match http_service::route::handler(req, db_pool).await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::error!(message = "error:route::handler", error = format!("{e}"));
+                let mut response = http_service::controller::response(
+                    serde_json::json!({"message": "Internal Server Error","success": false})
+                        .to_string(),
+                    hyper::StatusCode::INTERNAL_SERVER_ERROR,
+                );
+                // Note: the failed handler gave no response, the route is looked up for the metrics
+                if let Some(matched) = http_service::route::matched_path(path.as_str()) {
+                    response.extensions_mut().insert(matched);
+                }
+                response
+            }
+        }
+    }
+    .instrument(span.clone())
+    .await;
+
+    if let Ok(value) = hyper::header::HeaderValue::from_str(request_id.as_str()) {
+        response
+            .headers_mut()
+            .insert(router::REQUEST_ID_HEADER, value);
+    }
+    let latency = start.elapsed();
+    http_service::metrics::record(&method, &response, latency);
+    span.in_scope(|| {
+        tracing::info!(
+            message = "access",
+            method = method.as_str(),
+            path = path.as_str(),
+            status = response.status().as_u16(),
+            latency_ms = latency.as_millis() as u64,
+        )
+    });
+    response
+}
+
+// id of the proxy is kept only if it is short and safe to log, otherwise a new one is generated
+fn request_id(headers: &hyper::HeaderMap) -> String {
+    match headers
+        .get(router::REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(id)
+            if !id.is_empty()
+                && id.len() <= 64
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) =>
+        {
+            id.to_string()
+        }
+        _ => {
+            let bytes: [u8; 16] = rand::random();
+            bytes.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn request_id_of_proxy() {
+        let mut headers = hyper::HeaderMap::new();
+        assert_eq!(super::request_id(&headers).len(), 32);
+
+        headers.insert(
+            router::REQUEST_ID_HEADER,
+            hyper::header::HeaderValue::from_static("7f3c-a1.b_2"),
+        );
+        assert_eq!(super::request_id(&headers), "7f3c-a1.b_2");
+
+        headers.insert(
+            router::REQUEST_ID_HEADER,
+            hyper::header::HeaderValue::from_static("id with spaces"),
+        );
+        assert_ne!(super::request_id(&headers), "id with spaces");
+    }
+}
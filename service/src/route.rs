@@ -1,49 +1,57 @@
 use hyper::body::Incoming;
 
+// Note: routes of all the crates, they are registered once at the first request
+static ROUTER: once_cell::sync::Lazy<router::Router<db::pg::DbPool>> =
+    once_cell::sync::Lazy::new(|| {
+        let mut router = router::Router::new();
+        router
+            .get("/auth/health/", health)
+            .get("/", index)
+            .post("/", |_, _| async {
+                let mut response = router::json(serde_json::json!({}), hyper::StatusCode::OK);
+                *response.body_mut() = vec![];
+                Ok::<_, http_service::errors::RouteError>(response)
+            });
+        http_service::health::register(&mut router);
+        http_service::metrics::register(&mut router);
+        auth::controller::register(&mut router);
+        ai::apis::register(&mut router);
+        router
+    });
+
+pub fn matched_path(path: &str) -> Option<router::MatchedPath> {
+    ROUTER.matched_path(path)
+}
+
 pub async fn handler(
     req: hyper::Request<Incoming>,
     db_pool: db::pg::DbPool,
 ) -> Result<hyper::Response<Vec<u8>>, http_service::errors::RouteError> {
-    tracing::info!(method = req.method().as_str(), path = req.uri().path());
-
-    if req.uri().path().starts_with("/auth/health") {
-        let mut response = hyper::Response::new(vec![]);
-        *response.body_mut() = serde_json::json!({"success": true})
-            .to_string()
-            .into_bytes();
-        *response.status_mut() = hyper::StatusCode::OK;
-        response.headers_mut().append(
-            hyper::header::CONTENT_TYPE,
-            hyper::http::HeaderValue::from_str("application/json").unwrap(), // TODO: Remove unwrap
-        );
-        return Ok(response);
-    }
-
-    if req.uri().path().starts_with("/auth/") || req.uri().path().starts_with("/v1/api/auth/") {
-        // todo: handle the error here only
-        return Ok(auth::controller::routes(req, db_pool).await?);
-    }
+    Ok(ROUTER.handle(req, db_pool).await?)
+}
 
-    if req.uri().path().starts_with("/api/ai/") {
-        return Ok(ai::apis::handle(req, db_pool).await?);
-    }
+async fn health(
+    _req: hyper::Request<Incoming>,
+    _db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, http_service::errors::RouteError> {
+    Ok(router::json(
+        serde_json::json!({"success": true}),
+        hyper::StatusCode::OK,
+    ))
+}
 
-    match (req.method(), req.uri().path()) {
-        (&hyper::Method::GET, "/") => Ok(hyper::Response::new(
-            tokio::fs::read("service/index.html").await?,
-        )),
-        (&hyper::Method::POST, "/") => {
-            let mut response = hyper::Response::new(vec![]);
-            *response.status_mut() = hyper::StatusCode::OK;
-            response.headers_mut().append(
-                hyper::header::CONTENT_TYPE,
-                hyper::http::HeaderValue::from_str("application/json").unwrap(), // TODO: Remove unwrap
-            );
-            Ok(response)
-        }
+async fn index(
+    _req: hyper::Request<Incoming>,
+    _db_pool: db::pg::DbPool,
+) -> Result<hyper::Response<Vec<u8>>, http_service::errors::RouteError> {
+    Ok(router::html(tokio::fs::read("service/index.html").await?))
+}
 
-        _ => Ok(auth::not_found!(serde_json::json!(
-                {"message": format!("route not found: {}",req.uri().path()),"success": false})
-        .to_string())),
+#[cfg(test)]
+mod tests {
+    // Note: conflicting routes of the crates panic when the router is built
+    #[test]
+    fn routes_are_registered() {
+        once_cell::sync::Lazy::force(&super::ROUTER);
     }
 }
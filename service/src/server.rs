@@ -0,0 +1,193 @@
+// Note: connections are served with HTTP/1.1 or HTTP/2 (prior knowledge, or ALPN `h2` with TLS),
+// hyper-util's auto builder reads the preface of the connection. TLS is for running without the
+// reverse proxy, the certificate is reloaded on SIGHUP without dropping the open connections
+#[derive(thiserror::Error, Debug)]
+pub enum ServerError {
+    #[error("IOError: {0}")]
+    IO(#[from] std::io::Error),
+    #[error("PemError: {0}")]
+    Pem(String),
+    #[error("TlsError: {0}")]
+    Tls(#[from] rustls::Error),
+    #[error("TlsConfigError: {0}")]
+    Config(String),
+}
+
+pub struct Tls {
+    cert_path: String,
+    key_path: String,
+    config: std::sync::RwLock<std::sync::Arc<rustls::ServerConfig>>,
+}
+
+impl Tls {
+    pub fn new(cert_path: &str, key_path: &str) -> Result<Tls, ServerError> {
+        Ok(Tls {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            config: std::sync::RwLock::new(server_config(cert_path, key_path)?),
+        })
+    }
+
+    // `TLS_CERT_PATH` (PEM chain) and `TLS_KEY_PATH` (PEM key), none of them for plain http
+    pub fn from_env() -> Result<Option<Tls>, ServerError> {
+        match (
+            std::env::var("TLS_CERT_PATH"),
+            std::env::var("TLS_KEY_PATH"),
+        ) {
+            (Ok(cert_path), Ok(key_path)) => Ok(Some(Tls::new(&cert_path, &key_path)?)),
+            (Err(_), Err(_)) => Ok(None),
+            _ => Err(ServerError::Config(
+                "TLS_CERT_PATH and TLS_KEY_PATH are set together".to_string(),
+            )),
+        }
+    }
+
+    // Note: a broken certificate keeps the current one, the server stays up
+    pub fn reload(&self) -> Result<(), ServerError> {
+        let config = server_config(&self.cert_path, &self.key_path)?;
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = config;
+        Ok(())
+    }
+
+    fn acceptor(&self) -> tokio_rustls::TlsAcceptor {
+        tokio_rustls::TlsAcceptor::from(
+            self.config
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+fn server_config(
+    cert_path: &str,
+    key_path: &str,
+) -> Result<std::sync::Arc<rustls::ServerConfig>, ServerError> {
+    use rustls::pki_types::pem::PemObject;
+
+    let certs = rustls::pki_types::CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| ServerError::Pem(format!("{}: {}", cert_path, e)))?;
+    let key = rustls::pki_types::PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| ServerError::Pem(format!("{}: {}", key_path, e)))?;
+    let mut config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(std::sync::Arc::new(config))
+}
+
+pub fn reload_on_sighup(tls: std::sync::Arc<Tls>) -> Result<(), ServerError> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            match tls.reload() {
+                Ok(()) => tracing::info!(message = "tls certificate reloaded"),
+                Err(e) => tracing::error!(
+                    message = "tls certificate reload failed",
+                    error = e.to_string()
+                ),
+            }
+        }
+    });
+    Ok(())
+}
+
+// Note: SIGTERM (sent by the orchestrators) or ctrl-c, the handlers are installed right away so
+// that a signal sent before the future is polled is not lost
+pub fn shutdown_signal() -> Result<impl std::future::Future<Output = ()>, ServerError> {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    let interrupt = tokio::signal::ctrl_c();
+    Ok(async move {
+        tokio::select! {
+            _ = terminate.recv() => tracing::info!(message = "SIGTERM received, shutting down"),
+            _ = interrupt => tracing::info!(message = "ctrl-c received, shutting down"),
+        }
+    })
+}
+
+// Note: a peer that opens the connection and never finishes the handshake holds a task only
+// this long, accept errors (e.g. out of file descriptors) are retried after the back off
+const TLS_HANDSHAKE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+const ACCEPT_BACKOFF: std::time::Duration = std::time::Duration::from_millis(100);
+
+// Note: requests reach the service with the address of the peer as `router::RemoteAddr`
+pub async fn serve<S, B>(
+    listener: tokio::net::TcpListener,
+    tls: Option<std::sync::Arc<Tls>>,
+    service: S,
+) -> Result<(), ServerError>
+where
+    S: hyper::service::Service<
+            hyper::Request<hyper::body::Incoming>,
+            Response = hyper::Response<B>,
+            Error = hyper::Error,
+        > + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    B: hyper::body::Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    loop {
+        let (tcp_stream, remote) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                tracing::error!(message = "accept failed", error = e.to_string());
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+                continue;
+            }
+        };
+        let tls = tls.as_ref().map(|tls| tls.acceptor());
+        let service = service.clone();
+        let service = hyper::service::service_fn(move |mut req| {
+            req.extensions_mut().insert(router::RemoteAddr(remote));
+            service.call(req)
+        });
+        tokio::task::spawn(async move {
+            let builder =
+                hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new());
+            let result = match tls {
+                Some(acceptor) => {
+                    match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(tcp_stream))
+                        .await
+                    {
+                        Ok(Ok(stream)) => {
+                            builder
+                                .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                                .await
+                        }
+                        Ok(Err(e)) => {
+                            tracing::info!(
+                                message = "tls handshake failed",
+                                remote = remote.to_string(),
+                                error = e.to_string()
+                            );
+                            return;
+                        }
+                        Err(_) => {
+                            tracing::info!(
+                                message = "tls handshake timed out",
+                                remote = remote.to_string()
+                            );
+                            return;
+                        }
+                    }
+                }
+                None => {
+                    builder
+                        .serve_connection(hyper_util::rt::TokioIo::new(tcp_stream), service)
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                tracing::info!(
+                    message = "error while serving the connection",
+                    remote = remote.to_string(),
+                    error = e.to_string()
+                );
+            }
+        });
+    }
+}
@@ -0,0 +1,14 @@
+// Note: the signal is sent to the test process itself, its handler is installed first so it does not
+// terminate the process. This is the only test of the file
+#[tokio::test]
+async fn sigterm_resolves_the_shutdown_signal() {
+    let shutdown = service::server::shutdown_signal().unwrap();
+    let killed = std::process::Command::new("kill")
+        .args(["-TERM", std::process::id().to_string().as_str()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    tokio::time::timeout(std::time::Duration::from_secs(5), shutdown)
+        .await
+        .expect("shutdown signal is not received");
+}
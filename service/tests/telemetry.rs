@@ -0,0 +1,65 @@
+// Note: spans are exported to a local collector, it answers the OTLP/http export of the traces and
+// passes the requests to the test. The subscriber is global, so this is the only test of the file
+use http_body_util::BodyExt;
+
+type Export = (String, String, usize);
+
+async fn start_collector() -> (
+    std::net::SocketAddr,
+    tokio::sync::mpsc::UnboundedReceiver<Export>,
+) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let collector =
+        hyper::service::service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+            let sender = sender.clone();
+            async move {
+                let path = req.uri().path().to_string();
+                let content_type = req
+                    .headers()
+                    .get(hyper::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                let body = req.into_body().collect().await?.to_bytes();
+                sender.send((path, content_type, body.len())).ok();
+                Ok::<_, hyper::Error>(hyper::Response::new(http_body_util::Full::new(
+                    hyper::body::Bytes::new(),
+                )))
+            }
+        });
+    tokio::spawn(service::server::serve(listener, None, collector));
+    (addr, receiver)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spans_are_exported_to_the_collector() {
+    let (addr, mut exports) = start_collector().await;
+    std::env::set_var("OTEL_EXPORTER_OTLP_ENDPOINT", format!("http://{}", addr));
+    let telemetry = service::telemetry::init(&service::telemetry::Config {
+        format: service::telemetry::LogFormat::Json,
+        filter: "info".to_string(),
+        otlp: true,
+        service_name: "auth-test".to_string(),
+    })
+    .unwrap();
+
+    tracing::info_span!("request", request_id = "7f3c").in_scope(|| {
+        tracing::info_span!("user", user_id = 42).in_scope(|| {
+            tracing::info!(message = "otp is verified", otp = "123456");
+        })
+    });
+    tokio::task::spawn_blocking(move || telemetry.shutdown())
+        .await
+        .unwrap();
+
+    let (path, content_type, length) =
+        tokio::time::timeout(std::time::Duration::from_secs(10), exports.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(path, "/v1/traces");
+    assert_eq!(content_type, "application/x-protobuf");
+    assert!(length > 0);
+}
@@ -0,0 +1,114 @@
+// Note: the server of `service::server` with a self-signed certificate of `localhost`, the
+// responses carry the protocol version to check what was negotiated
+use std::sync::Arc;
+
+async fn version(
+    req: hyper::Request<hyper::body::Incoming>,
+) -> Result<hyper::Response<http_body_util::Full<hyper::body::Bytes>>, hyper::Error> {
+    Ok(hyper::Response::new(http_body_util::Full::from(format!(
+        "{:?}",
+        req.version()
+    ))))
+}
+
+struct Certificate {
+    cert_path: std::path::PathBuf,
+    key_path: std::path::PathBuf,
+    pem: String,
+}
+
+fn certificate(dir: &std::path::Path, name: &str) -> Certificate {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let cert_path = dir.join(format!("{}.crt", name));
+    let key_path = dir.join(format!("{}.key", name));
+    std::fs::write(&cert_path, cert.pem()).unwrap();
+    std::fs::write(&key_path, key_pair.serialize_pem()).unwrap();
+    Certificate {
+        cert_path,
+        key_path,
+        pem: cert.pem(),
+    }
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("service-tls-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+async fn start(tls: Option<Arc<service::server::Tls>>) -> std::net::SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(service::server::serve(
+        listener,
+        tls,
+        hyper::service::service_fn(version),
+    ));
+    addr
+}
+
+fn client(addr: std::net::SocketAddr, pem: &str) -> reqwest::Client {
+    reqwest::Client::builder()
+        .tls_certs_only([reqwest::Certificate::from_pem(pem.as_bytes()).unwrap()])
+        .resolve("localhost", addr)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn plain_http1_and_h2c() {
+    let addr = start(None).await;
+    let url = format!("http://{}/", addr);
+
+    let res = reqwest::get(url.as_str()).await.unwrap();
+    assert_eq!(res.version(), reqwest::Version::HTTP_11);
+    assert_eq!(res.text().await.unwrap(), "HTTP/1.1");
+
+    let h2c = reqwest::Client::builder()
+        .http2_prior_knowledge()
+        .build()
+        .unwrap();
+    let res = h2c.get(url.as_str()).send().await.unwrap();
+    assert_eq!(res.version(), reqwest::Version::HTTP_2);
+    assert_eq!(res.text().await.unwrap(), "HTTP/2.0");
+}
+
+#[tokio::test]
+async fn tls_negotiates_h2_and_reloads() {
+    let dir = temp_dir("reload");
+    let first = certificate(&dir, "server");
+    let tls = Arc::new(
+        service::server::Tls::new(
+            first.cert_path.to_str().unwrap(),
+            first.key_path.to_str().unwrap(),
+        )
+        .unwrap(),
+    );
+    let addr = start(Some(tls.clone())).await;
+    let url = format!("https://localhost:{}/", addr.port());
+
+    let res = client(addr, &first.pem)
+        .get(url.as_str())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), reqwest::Version::HTTP_2);
+    assert_eq!(res.text().await.unwrap(), "HTTP/2.0");
+
+    // a broken certificate is rejected and the current one is kept
+    std::fs::write(&first.cert_path, "not a certificate").unwrap();
+    assert!(tls.reload().is_err());
+    let res = client(addr, &first.pem).get(url.as_str()).send().await;
+    assert!(res.is_ok());
+
+    // the new certificate is served to the new connections
+    let second = certificate(&dir, "server");
+    tls.reload().unwrap();
+    let res = client(addr, &second.pem).get(url.as_str()).send().await;
+    assert!(res.is_ok());
+    let res = client(addr, &first.pem).get(url.as_str()).send().await;
+    assert!(res.is_err());
+
+    std::fs::remove_dir_all(dir).ok();
+}